@@ -2,7 +2,7 @@
 
 set -ex
 
-crates=("event-sauce" "storage-sqlx")
+crates=("event-sauce" "storage-sqlx" "storage-memory")
 
 cargo clean --doc
 
@@ -23,10 +23,12 @@ macro_rules! fail {
 
 struct EntityAttributes {
     entity_name: String,
+    custom_purge: bool,
 }
 
 fn parse_entity_attributes(input: &[Attribute]) -> syn::Result<EntityAttributes> {
     let mut entity_name = None;
+    let mut custom_purge = None;
 
     for attr in input {
         let meta = attr
@@ -38,6 +40,10 @@ fn parse_entity_attributes(input: &[Attribute]) -> syn::Result<EntityAttributes>
                 for value in list.nested.iter() {
                     match value {
                         NestedMeta::Meta(meta) => match meta {
+                            Meta::Path(path) if path.is_ident("custom_purge") => {
+                                try_set!(custom_purge, true, path)
+                            }
+
                             Meta::NameValue(MetaNameValue {
                                 path,
                                 lit: Lit::Str(val),
@@ -77,7 +83,10 @@ fn parse_entity_attributes(input: &[Attribute]) -> syn::Result<EntityAttributes>
         )
     })?;
 
-    Ok(EntityAttributes { entity_name })
+    Ok(EntityAttributes {
+        entity_name,
+        custom_purge: custom_purge.unwrap_or(false),
+    })
 }
 
 /// Return the name of the field which is to become the entity ID field
@@ -113,12 +122,24 @@ fn expand_derive_entity_struct(
 ) -> syn::Result<proc_macro2::TokenStream> {
     let ident = &input.ident;
 
-    let EntityAttributes { entity_name } = parse_entity_attributes(&input.attrs)?;
+    let EntityAttributes {
+        entity_name,
+        custom_purge,
+    } = parse_entity_attributes(&input.attrs)?;
 
     let entity_id_field = find_entity_id_field(&fields)?;
 
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
+    // Default aggregate storage, unless the user wants to implement it by hand
+    let purgeable = if custom_purge {
+        None
+    } else {
+        Some(quote!(
+            impl #impl_generics event_sauce::Purgeable for #ident #ty_generics #where_clause {}
+        ))
+    };
+
     Ok(quote!(
         impl #impl_generics event_sauce::Entity for #ident #ty_generics #where_clause {
             const ENTITY_TYPE: &'static str = #entity_name;
@@ -127,6 +148,8 @@ fn expand_derive_entity_struct(
                 self.#entity_id_field
             }
         }
+
+        #purgeable
     ))
 }
 
@@ -1,4 +1,4 @@
-use super::{parse_event_data_attributes, EventDataAttributes};
+use super::{parse_event_data_attributes, CustomTriggers, EventDataAttributes};
 use proc_macro2::{Ident, TokenStream};
 use quote::quote;
 use syn::{Data, DataEnum, DeriveInput, Variant};
@@ -24,7 +24,27 @@ fn impl_try_from(
 fn expand_derive_event_data_enum(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let ident = &input.ident;
 
-    let EventDataAttributes { entity } = parse_event_data_attributes(&input.attrs)?;
+    let EventDataAttributes {
+        entity,
+        version,
+        custom_triggers,
+    } = parse_event_data_attributes(&input.attrs)?;
+
+    // Each variant is stored as its own event type, so carries its own schema version
+    if version.is_some() {
+        return Err(syn::Error::new_spanned(
+            input,
+            "version must be set on each variant's event data, not on the enum",
+        ));
+    }
+
+    // Enum events run the triggers of their variants' event data
+    if !matches!(custom_triggers, CustomTriggers::None) {
+        return Err(syn::Error::new_spanned(
+            input,
+            "custom_triggers must be set on each variant's event data, not on the enum",
+        ));
+    }
 
     let variants = match input.data {
         Data::Enum(DataEnum { ref variants, .. }) => variants.iter(),
@@ -42,6 +62,45 @@ fn expand_derive_event_data_enum(input: &DeriveInput) -> syn::Result<proc_macro2
         )
         .collect::<syn::Result<Vec<TokenStream>>>()?;
 
+    // Each variant is stored with the event type of its event data, which has the same name
+    let pii_arms = variants
+        .clone()
+        .map(|Variant { ident: variant, .. }| {
+            let event_type = variant.to_string();
+
+            quote!(#event_type => Some(<#variant as event_sauce::EventData>::PII_FIELDS))
+        })
+        .collect::<Vec<TokenStream>>();
+
+    // Each variant runs the trigger of its own event builder
+    let trigger_bounds = variants
+        .clone()
+        .map(|Variant { ident: variant, .. }| {
+            quote!(
+                <#variant as event_sauce::EventData>::Builder:
+                    event_sauce::RunTrigger<#entity, #variant, Txn>
+            )
+        })
+        .collect::<Vec<TokenStream>>();
+
+    let trigger_arms = variants
+        .clone()
+        .map(|Variant { ident: variant, .. }| {
+            quote!(
+                Some(#ident::#variant(data)) => {
+                    let event = event.with_data(data);
+
+                    <<#variant as event_sauce::EventData>::Builder as event_sauce::RunTrigger<
+                        #entity,
+                        #variant,
+                        Txn,
+                    >>::run_trigger(entity, &event, tx)
+                    .await
+                }
+            )
+        })
+        .collect::<Vec<TokenStream>>();
+
     let match_arms = variants
         .map(|Variant { ident: variant, .. }| quote!(#ident::#variant))
         .collect::<Vec<TokenStream>>();
@@ -59,12 +118,46 @@ fn expand_derive_event_data_enum(input: &DeriveInput) -> syn::Result<proc_macro2
                     #(#match_arms(data) => data.event_type()),*
                 }
             }
+
+            fn schema_version(&self) -> i32 {
+                match self {
+                    #(#match_arms(data) => event_sauce::EventData::schema_version(data)),*
+                }
+            }
         }
 
-        impl #impl_generics event_sauce::EnumEventData for #ident #ty_generics #where_clause {}
+        impl #impl_generics event_sauce::EnumEventData for #ident #ty_generics #where_clause {
+            fn pii_fields(event_type: &str) -> Option<&'static [&'static str]> {
+                match event_type {
+                    #(#pii_arms,)*
+                    _ => None,
+                }
+            }
+        }
 
         impl event_sauce::ActionEntityBuilder<#ident> for #entity {}
 
+        impl<Txn> event_sauce::RunEnumTrigger<#entity, Txn> for #ident
+        where
+            Txn: event_sauce::StorageBackendTransaction + Send,
+            #(#trigger_bounds,)*
+        {
+            fn run_enum_trigger<'a>(
+                entity: &'a #entity,
+                mut event: event_sauce::Event<Self>,
+                tx: &'a mut Txn,
+            ) -> std::pin::Pin<
+                Box<dyn std::future::Future<Output = Result<(), Txn::Error>> + Send + 'a>,
+            > {
+                Box::pin(async move {
+                    match event.data.take() {
+                        #(#trigger_arms)*
+                        None => Ok(()),
+                    }
+                })
+            }
+        }
+
         #(#conversions)*
     ))
 }
@@ -1,5 +1,5 @@
-use super::{parse_event_data_attributes, EventDataAttributes};
-use quote::quote;
+use super::{parse_event_data_attributes, parse_pii_fields, CustomTriggers, EventDataAttributes};
+use quote::{format_ident, quote};
 use syn::{Data, DataStruct, DeriveInput, Fields, FieldsNamed};
 
 enum BuilderType {
@@ -16,41 +16,139 @@ fn expand_derive_event_data_struct(
     let ident = &input.ident;
     let ident_string = ident.to_string();
 
-    let EventDataAttributes { entity } = parse_event_data_attributes(&input.attrs)?;
+    let EventDataAttributes {
+        entity,
+        version,
+        custom_triggers,
+    } = parse_event_data_attributes(&input.attrs)?;
+
+    // Events without an explicit version use the trait's default of 1
+    let schema_version = version.map(|version| {
+        quote!(
+            fn schema_version(&self) -> i32 {
+                #version
+            }
+        )
+    });
+
+    let pii_fields = parse_pii_fields(input)?;
+
+    // Events without PII fields use the trait's default of none
+    let pii_fields = if pii_fields.is_empty() {
+        None
+    } else {
+        Some(quote!(
+            const PII_FIELDS: &'static [&'static str] = &[#(#pii_fields),*];
+        ))
+    };
 
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-    let (builder_impl, event_builder) = match builder_type {
+    let (builder_impl, event_builder, trigger) = match builder_type {
         BuilderType::Create => (
             quote!(event_sauce::CreateEntityBuilder),
             quote!(event_sauce::CreateEventBuilder),
+            "OnCreated",
         ),
         BuilderType::Update => (
             quote!(event_sauce::UpdateEntityBuilder),
             quote!(event_sauce::UpdateEventBuilder),
+            "OnUpdated",
         ),
         BuilderType::Delete => (
             quote!(event_sauce::DeleteEntityBuilder),
             quote!(event_sauce::DeleteEventBuilder),
+            "OnDeleted",
         ),
         BuilderType::Purge => (
             quote!(event_sauce::PurgeEntityBuilder),
             quote!(event_sauce::PurgeEventBuilder),
+            "OnPurged",
         ),
     };
 
+    // Only this kind of event's trigger and the conflict triggers can be implemented by hand
+    if let CustomTriggers::Only(triggers) = &custom_triggers {
+        let known = [trigger, "OnConflicted", "OnConflictResolved"];
+
+        if let Some(unknown) = triggers
+            .iter()
+            .find(|path| !known.iter().any(|name| path.is_ident(name)))
+        {
+            return Err(syn::Error::new_spanned(
+                unknown,
+                format!(
+                    "expected one of {}, OnConflicted or OnConflictResolved",
+                    trigger
+                ),
+            ));
+        }
+    }
+
+    // Noop triggers, except for those the user wants to implement by hand
+    let trigger_impl = if custom_triggers.contains(trigger) {
+        None
+    } else {
+        let trigger = format_ident!("{}", trigger);
+
+        Some(quote!(
+            impl<Txn> event_sauce::#trigger<#ident, Txn> for #entity
+            where
+                Txn: event_sauce::StorageBackendTransaction + Send,
+            {
+            }
+        ))
+    };
+
+    // Conflict triggers are run by the builder of the conflicting event data, so are keyed on this
+    // event data as `EDC`
+    let conflicted_impl = if custom_triggers.contains("OnConflicted") {
+        None
+    } else {
+        Some(quote!(
+            impl<EDA, Txn> event_sauce::OnConflicted<EDA, #ident, Txn> for #entity
+            where
+                EDA: event_sauce::EventData + Sync,
+                Txn: event_sauce::StorageBackendTransaction + Send,
+            {
+            }
+        ))
+    };
+
+    let conflict_resolved_impl = if custom_triggers.contains("OnConflictResolved") {
+        None
+    } else {
+        Some(quote!(
+            impl<Txn> event_sauce::OnConflictResolved<#ident, Txn> for #entity
+            where
+                Txn: event_sauce::StorageBackendTransaction + Send,
+            {
+            }
+        ))
+    };
+
     Ok(quote!(
         impl #impl_generics event_sauce::EventData for #ident #ty_generics #where_clause {
             type Entity = #entity;
 
-            type Builder = #event_builder <#ident>;
+            type Builder = #event_builder<#ident, event_sauce::Triggered>;
 
             fn event_type(&self) -> &'static str {
                 #ident_string
             }
+
+            #schema_version
+
+            #pii_fields
         }
 
         impl #builder_impl<#ident> for #entity {}
+
+        #trigger_impl
+
+        #conflicted_impl
+
+        #conflict_resolved_impl
     ))
 }
 
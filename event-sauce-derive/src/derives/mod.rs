@@ -3,7 +3,10 @@ pub mod enum_event_data;
 pub mod event_data;
 
 use proc_macro2::Span;
-use syn::{Attribute, Meta, NestedMeta, Path};
+use syn::{
+    ext::IdentExt, Attribute, Data, DataStruct, DeriveInput, Lit, Meta, MetaNameValue, NestedMeta,
+    Path,
+};
 
 /// Attempt to assign a value to a variable, failing if the variable is already populated.
 ///
@@ -22,12 +25,34 @@ macro_rules! fail {
         return Err(syn::Error::new_spanned($t, $m));
     };
 }
+/// Triggers of an event data's entity that are implemented by hand instead of as noops
+enum CustomTriggers {
+    None,
+    All,
+    Only(Vec<Path>),
+}
+
+impl CustomTriggers {
+    /// Whether the trigger trait named `trigger` is implemented by hand
+    fn contains(&self, trigger: &str) -> bool {
+        match self {
+            Self::None => false,
+            Self::All => true,
+            Self::Only(triggers) => triggers.iter().any(|path| path.is_ident(trigger)),
+        }
+    }
+}
+
 struct EventDataAttributes {
     entity: Path,
+    version: Option<i32>,
+    custom_triggers: CustomTriggers,
 }
 
 fn parse_event_data_attributes(input: &[Attribute]) -> syn::Result<EventDataAttributes> {
     let mut entity = None;
+    let mut version = None;
+    let mut custom_triggers = None;
 
     for attr in input {
         let meta = attr
@@ -39,8 +64,40 @@ fn parse_event_data_attributes(input: &[Attribute]) -> syn::Result<EventDataAttr
                 for value in list.nested.iter() {
                     match value {
                         NestedMeta::Meta(meta) => match meta {
+                            Meta::Path(path) if path.is_ident("custom_triggers") => {
+                                try_set!(custom_triggers, CustomTriggers::All, path)
+                            }
+
+                            Meta::List(list) if list.path.is_ident("custom_triggers") => {
+                                let triggers = list
+                                    .nested
+                                    .iter()
+                                    .map(|trigger| match trigger {
+                                        NestedMeta::Meta(Meta::Path(path)) => Ok(path.clone()),
+                                        u => Err(syn::Error::new_spanned(
+                                            u,
+                                            "expected the name of a trigger trait",
+                                        )),
+                                    })
+                                    .collect::<syn::Result<Vec<Path>>>()?;
+
+                                if triggers.is_empty() {
+                                    fail!(list, "expected at least one trigger trait");
+                                }
+
+                                try_set!(custom_triggers, CustomTriggers::Only(triggers), list)
+                            }
+
                             Meta::Path(path) => try_set!(entity, path.clone(), path),
 
+                            Meta::NameValue(MetaNameValue {
+                                path,
+                                lit: Lit::Int(value),
+                                ..
+                            }) if path.is_ident("version") => {
+                                try_set!(version, value.base10_parse()?, value)
+                            }
+
                             u => fail!(u, "unexpected attribute"),
                         },
                         u => fail!(u, "unexpected attribute"),
@@ -58,5 +115,211 @@ fn parse_event_data_attributes(input: &[Attribute]) -> syn::Result<EventDataAttr
         )
     })?;
 
-    Ok(EventDataAttributes { entity })
+    Ok(EventDataAttributes {
+        entity,
+        version,
+        custom_triggers: custom_triggers.unwrap_or(CustomTriggers::None),
+    })
+}
+
+/// A `#[serde(rename_all = "...")]` rule applied to the field names of a struct
+#[derive(Clone, Copy)]
+enum RenameRule {
+    Lowercase,
+    Uppercase,
+    PascalCase,
+    CamelCase,
+    SnakeCase,
+    ScreamingSnakeCase,
+    KebabCase,
+    ScreamingKebabCase,
+}
+
+impl RenameRule {
+    fn parse(name: &syn::LitStr) -> syn::Result<Self> {
+        match name.value().as_str() {
+            "lowercase" => Ok(Self::Lowercase),
+            "UPPERCASE" => Ok(Self::Uppercase),
+            "PascalCase" => Ok(Self::PascalCase),
+            "camelCase" => Ok(Self::CamelCase),
+            "snake_case" => Ok(Self::SnakeCase),
+            "SCREAMING_SNAKE_CASE" => Ok(Self::ScreamingSnakeCase),
+            "kebab-case" => Ok(Self::KebabCase),
+            "SCREAMING-KEBAB-CASE" => Ok(Self::ScreamingKebabCase),
+            _ => fail!(name, "unknown serde rename rule"),
+        }
+    }
+
+    /// Rename a `snake_case` field in the same way as serde
+    fn apply(self, field: &str) -> String {
+        match self {
+            Self::Lowercase | Self::SnakeCase => field.to_string(),
+            Self::Uppercase | Self::ScreamingSnakeCase => field.to_ascii_uppercase(),
+            Self::PascalCase | Self::CamelCase => {
+                let mut renamed = String::new();
+                let mut capitalize = matches!(self, Self::PascalCase);
+
+                for c in field.chars() {
+                    if c == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        renamed.push(c.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        renamed.push(c);
+                    }
+                }
+
+                renamed
+            }
+            Self::KebabCase => field.replace('_', "-"),
+            Self::ScreamingKebabCase => field.replace('_', "-").to_ascii_uppercase(),
+        }
+    }
+}
+
+/// Get the serialized name from a serde `rename` or `rename_all` attribute
+///
+/// Both `rename = "..."` and `rename(serialize = "...")` are accepted. Only the serialized name
+/// matters, as that's the name of the field in stored payloads.
+fn parse_serde_rename(meta: &Meta) -> syn::Result<Option<syn::LitStr>> {
+    match meta {
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(name),
+            ..
+        }) => Ok(Some(name.clone())),
+        Meta::List(list) => {
+            let mut serialize = None;
+
+            for value in list.nested.iter() {
+                match value {
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                        path,
+                        lit: Lit::Str(name),
+                        ..
+                    })) if path.is_ident("serialize") => try_set!(serialize, name.clone(), path),
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, .. }))
+                        if path.is_ident("deserialize") => {}
+                    u => fail!(u, "unsupported serde attribute"),
+                }
+            }
+
+            Ok(serialize)
+        }
+        u => fail!(u, "unsupported serde attribute"),
+    }
+}
+
+/// Parse the `#[serde(...)]` attributes in a list of attributes, ignoring all others
+fn serde_attributes(input: &[Attribute]) -> syn::Result<Vec<Meta>> {
+    let mut metas = Vec::new();
+
+    for attr in input.iter().filter(|attr| attr.path.is_ident("serde")) {
+        let meta = attr
+            .parse_meta()
+            .map_err(|e| syn::Error::new_spanned(attr, e))?;
+
+        if let Meta::List(list) = meta {
+            for value in list.nested {
+                if let NestedMeta::Meta(meta) = value {
+                    metas.push(meta);
+                }
+            }
+        }
+    }
+
+    Ok(metas)
+}
+
+/// Get the serialized names of the fields of a struct marked with `#[event_sauce(pii)]`
+///
+/// Field names follow the struct's `#[serde(rename_all = "...")]` rule, and fields renamed with
+/// `#[serde(rename = "...")]` use their new name. Serde attributes that stop a PII field from
+/// being found by name, like `#[serde(flatten)]`, fail the derive.
+fn parse_pii_fields(input: &DeriveInput) -> syn::Result<Vec<String>> {
+    let fields = match &input.data {
+        Data::Struct(DataStruct { fields, .. }) => fields,
+        _ => return Ok(Vec::new()),
+    };
+
+    let mut rename_all = None;
+    let mut transparent = None;
+
+    for meta in serde_attributes(&input.attrs)? {
+        if meta.path().is_ident("rename_all") {
+            if let Some(rule) = parse_serde_rename(&meta)? {
+                try_set!(rename_all, RenameRule::parse(&rule)?, meta)
+            }
+        } else if meta.path().is_ident("transparent") {
+            transparent = Some(meta);
+        }
+    }
+
+    let mut pii_fields = Vec::new();
+
+    for field in fields.iter() {
+        let mut pii = None;
+
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("event_sauce"))
+        {
+            let meta = attr
+                .parse_meta()
+                .map_err(|e| syn::Error::new_spanned(attr, e))?;
+
+            match meta {
+                Meta::List(list) => {
+                    for value in list.nested.iter() {
+                        match value {
+                            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("pii") => {
+                                try_set!(pii, true, path)
+                            }
+                            u => fail!(u, "unexpected attribute"),
+                        }
+                    }
+                }
+                u => fail!(u, "unexpected attribute"),
+            }
+        }
+
+        if pii.is_none() {
+            continue;
+        }
+
+        if let Some(transparent) = &transparent {
+            fail!(
+                transparent,
+                "pii fields can't be found in the payloads of transparent structs"
+            );
+        }
+
+        let mut rename = None;
+
+        for meta in serde_attributes(&field.attrs)? {
+            if meta.path().is_ident("rename") {
+                if let Some(name) = parse_serde_rename(&meta)? {
+                    try_set!(rename, name.value(), meta)
+                }
+            } else if meta.path().is_ident("flatten") {
+                fail!(meta, "pii fields can't be flattened");
+            }
+        }
+
+        match (&field.ident, rename) {
+            (_, Some(rename)) => pii_fields.push(rename),
+            (Some(ident), None) => {
+                let name = ident.unraw().to_string();
+
+                pii_fields.push(match rename_all {
+                    Some(rule) => rule.apply(&name),
+                    None => name,
+                })
+            }
+            (None, None) => fail!(field, "only named fields can be marked as pii"),
+        }
+    }
+
+    Ok(pii_fields)
 }
@@ -0,0 +1,232 @@
+//! Apply multiple events to an entity and store them together
+
+use crate::{
+    DBEvent, DeleteBuilder, DeleteEntityBuilder, DeleteEventBuilder, Event, EventData, RunTrigger,
+    StorageBackendTransaction, StorageBuilder, UpdateEntityBuilder, UpdateEventBuilder,
+};
+use std::convert::TryFrom;
+
+/// The events applied in a batch, in the order they were applied
+///
+/// This is implemented for nested tuples of events like `(((), Event<A>), Event<B>)`, which are
+/// built up by [`BatchBuilder::try_update`]. Keeping the type of each event lets storage backends
+/// run its trigger with [`BatchTriggers`].
+pub trait BatchEvents: Send + Sync {
+    /// Append every event in the batch to `db_events` as a [`DBEvent`]
+    fn push_db_events(&self, db_events: &mut Vec<DBEvent>) -> Result<(), serde_json::Error>;
+}
+
+impl BatchEvents for () {
+    fn push_db_events(&self, _db_events: &mut Vec<DBEvent>) -> Result<(), serde_json::Error> {
+        Ok(())
+    }
+}
+
+impl<Rest, ED> BatchEvents for (Rest, Event<ED>)
+where
+    Rest: BatchEvents,
+    ED: EventData + Send + Sync,
+{
+    fn push_db_events(&self, db_events: &mut Vec<DBEvent>) -> Result<(), serde_json::Error> {
+        self.0.push_db_events(db_events)?;
+
+        db_events.push(DBEvent::try_from(&self.1)?);
+
+        Ok(())
+    }
+}
+
+/// Run the trigger of every event in a batch
+///
+/// Storage backends call this with the final state of the entity once the batch's events are
+/// stored, running each event's trigger through [`RunTrigger`] in the order the events were
+/// applied.
+#[async_trait::async_trait]
+pub trait BatchTriggers<E, Txn>: BatchEvents
+where
+    Txn: StorageBackendTransaction,
+{
+    /// Run the trigger of each event for `entity`
+    async fn run_triggers(&self, entity: &E, tx: &mut Txn) -> Result<(), Txn::Error>;
+}
+
+#[async_trait::async_trait]
+impl<E, Txn> BatchTriggers<E, Txn> for ()
+where
+    E: Sync,
+    Txn: StorageBackendTransaction + Send,
+{
+    async fn run_triggers(&self, _entity: &E, _tx: &mut Txn) -> Result<(), Txn::Error> {
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, Txn, Rest, ED> BatchTriggers<E, Txn> for (Rest, Event<ED>)
+where
+    E: Sync,
+    Txn: StorageBackendTransaction + Send,
+    Rest: BatchTriggers<E, Txn>,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, Txn>,
+{
+    async fn run_triggers(&self, entity: &E, tx: &mut Txn) -> Result<(), Txn::Error> {
+        self.0.run_triggers(entity, tx).await?;
+
+        ED::Builder::run_trigger(entity, &self.1, tx).await
+    }
+}
+
+/// A wrapper around an entity and every event applied to it so far, used to persist them to the
+/// database at the same time
+///
+/// This is created by chaining [`StorageBuilder::try_update`] or [`BatchBuilder::try_update`]
+/// calls, and is persisted like a [`StorageBuilder`]. All events and the final state of the entity
+/// are stored in a single transaction. Once the entity is persisted, the trigger of each event is
+/// run in the order the events were applied, with the final state of the entity.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let user = User::try_create(UserCreated { name, email })?
+///     .try_update(UserEmailVerified)?
+///     .try_update(UserRoleChanged { role })?
+///     .persist(&store)
+///     .await?;
+/// ```
+pub struct BatchBuilder<Ent, Events> {
+    /// Every event applied to the entity, in the order they were applied
+    pub events: Events,
+
+    /// Entity to persist, with every event applied
+    pub entity: Ent,
+}
+
+impl<Ent, Events> BatchBuilder<Ent, Events>
+where
+    Events: BatchEvents,
+{
+    /// Apply another update event to the entity
+    #[allow(clippy::type_complexity)]
+    pub fn try_update<ED, B>(
+        self,
+        builder: B,
+    ) -> Result<BatchBuilder<Ent::Output, (Events, Event<ED>)>, Ent::Error>
+    where
+        Ent: UpdateEntityBuilder<ED>,
+        ED: EventData + Send + Sync,
+        B: Into<UpdateEventBuilder<ED>>,
+    {
+        let StorageBuilder { event, entity } = self.entity.try_update(builder)?;
+
+        Ok(BatchBuilder {
+            events: (self.events, event),
+            entity,
+        })
+    }
+
+    /// Finish the batch by deleting the entity
+    #[allow(clippy::type_complexity)]
+    pub fn try_delete<ED, B>(
+        self,
+        builder: B,
+    ) -> Result<DeleteBatchBuilder<Ent, (Events, Event<ED>)>, Ent::Error>
+    where
+        Ent: DeleteEntityBuilder<ED>,
+        ED: EventData + Send + Sync,
+        B: Into<DeleteEventBuilder<ED>>,
+    {
+        let DeleteBuilder { event, entity } = self.entity.try_delete(builder)?;
+
+        Ok(DeleteBatchBuilder {
+            events: (self.events, event),
+            entity,
+        })
+    }
+
+    /// Convert every event in the batch into a [`DBEvent`], in the order they were applied
+    pub fn db_events(&self) -> Result<Vec<DBEvent>, serde_json::Error> {
+        let mut db_events = Vec::new();
+
+        self.events.push_db_events(&mut db_events)?;
+
+        Ok(db_events)
+    }
+}
+
+impl<Ent, ED> From<StorageBuilder<Ent, ED>> for BatchBuilder<Ent, ((), Event<ED>)>
+where
+    ED: EventData + Send + Sync,
+{
+    fn from(builder: StorageBuilder<Ent, ED>) -> Self {
+        Self {
+            events: ((), builder.event),
+            entity: builder.entity,
+        }
+    }
+}
+
+impl<Ent, ED> StorageBuilder<Ent, ED>
+where
+    ED: EventData + Send + Sync,
+{
+    /// Apply another update event to the entity, storing both events together
+    ///
+    /// See [`BatchBuilder`].
+    #[allow(clippy::type_complexity)]
+    pub fn try_update<EDU, B>(
+        self,
+        builder: B,
+    ) -> Result<BatchBuilder<Ent::Output, (((), Event<ED>), Event<EDU>)>, Ent::Error>
+    where
+        Ent: UpdateEntityBuilder<EDU>,
+        EDU: EventData + Send + Sync,
+        B: Into<UpdateEventBuilder<EDU>>,
+    {
+        BatchBuilder::from(self).try_update(builder)
+    }
+
+    /// Delete the entity, storing both events together
+    ///
+    /// See [`DeleteBatchBuilder`].
+    #[allow(clippy::type_complexity)]
+    pub fn try_delete<EDD, B>(
+        self,
+        builder: B,
+    ) -> Result<DeleteBatchBuilder<Ent, (((), Event<ED>), Event<EDD>)>, Ent::Error>
+    where
+        Ent: DeleteEntityBuilder<EDD>,
+        EDD: EventData + Send + Sync,
+        B: Into<DeleteEventBuilder<EDD>>,
+    {
+        BatchBuilder::from(self).try_delete(builder)
+    }
+}
+
+/// A wrapper around an entity and every event applied to it, ending with a deletion event, used
+/// to delete the entity in the database
+///
+/// This is created by [`BatchBuilder::try_delete`], and is deleted like a [`DeleteBuilder`]. All
+/// events are stored and the entity removed in a single transaction. The trigger of each event is
+/// run in the order the events were applied, before the entity is removed.
+pub struct DeleteBatchBuilder<Ent, Events> {
+    /// Every event applied to the entity, ending with the deletion event
+    pub events: Events,
+
+    /// Entity to delete
+    pub entity: Ent,
+}
+
+impl<Ent, Events> DeleteBatchBuilder<Ent, Events>
+where
+    Events: BatchEvents,
+{
+    /// Convert every event in the batch into a [`DBEvent`], in the order they were applied
+    pub fn db_events(&self) -> Result<Vec<DBEvent>, serde_json::Error> {
+        let mut db_events = Vec::new();
+
+        self.events.push_db_events(&mut db_events)?;
+
+        Ok(db_events)
+    }
+}
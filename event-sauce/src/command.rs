@@ -0,0 +1,101 @@
+//! Handle commands against the current state of an entity
+
+use crate::{ActionEntityBuilder, ActionEventBuilder, AggregateAction, EnumEventData, Event};
+use uuid::Uuid;
+
+/// A request to change an entity
+///
+/// Commands are handled by a [`CommandHandler`], which decides which events to emit based on the
+/// command and the entity's current state. Storage backends run commands with
+/// [`StorageBackendExecute::execute`](crate::StorageBackendExecute::execute).
+pub trait Command {
+    /// The entity this command acts on
+    type Entity: ActionEntityBuilder<Self::Event>;
+
+    /// The events this command can emit
+    ///
+    /// When the entity is loaded to handle the command, this enum must have a variant for every
+    /// event type stored against the entity.
+    type Event: EnumEventData;
+
+    /// The error returned when the command is rejected
+    type Error;
+
+    /// The number of times the command is attempted if the entity is modified concurrently.
+    /// Defaults to `3`.
+    const MAX_ATTEMPTS: usize = 3;
+
+    /// The ID of the entity to run this command against, or `None` if the command creates a new
+    /// entity
+    fn entity_id(&self) -> Option<Uuid>;
+}
+
+/// Decide which events a [`Command`] emits
+pub trait CommandHandler<C>
+where
+    C: Command,
+{
+    /// Validate a command against the current state of its entity, returning the events to apply
+    ///
+    /// `entity` is `None` if the command creates a new entity, or if the entity doesn't exist.
+    /// Returning an empty list leaves the entity unchanged. This method may be called more than
+    /// once for the same command if the entity is modified concurrently, so should not have side
+    /// effects.
+    fn handle(
+        &self,
+        command: &C,
+        entity: Option<&C::Entity>,
+    ) -> Result<Vec<ActionEventBuilder<C::Event>>, C::Error>;
+
+    /// Handle a command and apply the resulting events to the entity
+    ///
+    /// `version` is the current version of the entity's event stream, used to detect concurrent
+    /// modifications when the events are persisted. It is ignored if `entity` is `None`, as a new
+    /// entity ID is generated for the first event. Returns the updated entity, or `None` if the
+    /// entity doesn't exist and no events were emitted, along with the events to persist.
+    #[allow(clippy::type_complexity)]
+    fn try_decide(
+        &self,
+        command: &C,
+        entity: Option<C::Entity>,
+        version: i32,
+    ) -> Result<
+        (Option<C::Entity>, Vec<Event<C::Event>>),
+        CommandError<C::Error, <C::Entity as AggregateAction<C::Event>>::Error>,
+    > {
+        let builders = self
+            .handle(command, entity.as_ref())
+            .map_err(CommandError::Rejected)?;
+
+        let version = if entity.is_some() { version } else { 0 };
+
+        let mut entity = entity;
+        let mut events = Vec::with_capacity(builders.len());
+
+        for (builder, version) in builders.into_iter().zip(version + 1..) {
+            let mut event = builder.build(&entity);
+
+            event.version = Some(version);
+
+            entity = Some(
+                C::Entity::try_aggregate_action(entity, &event).map_err(CommandError::Aggregate)?,
+            );
+
+            events.push(event);
+        }
+
+        Ok((entity, events))
+    }
+}
+
+/// An error returned by [`CommandHandler::try_decide`]
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError<R, A> {
+    /// The command was rejected by its handler
+    #[error("Command rejected: {0}")]
+    Rejected(R),
+
+    /// An event emitted by the handler could not be applied to the entity
+    #[error("Failed to apply event: {0}")]
+    Aggregate(A),
+}
@@ -1,6 +1,6 @@
 //! Database storage for [`Event`]s
 
-use crate::{event::Event, EventData};
+use crate::{event::Event, EncryptionKey, EnumEventData, EventData};
 use chrono::{DateTime, Utc};
 use std::convert::TryFrom;
 use uuid::Uuid;
@@ -36,6 +36,18 @@ pub struct DBEvent {
     /// The ID of the entity (user, organisation, etc) that this event aggregates into
     pub entity_id: Uuid,
 
+    /// The position of this event in its entity's event stream, starting at `1`
+    ///
+    /// Each version may only occur once per entity. This is enforced by the database to prevent
+    /// concurrent writers from interleaving events.
+    pub version: Option<i32>,
+
+    /// The version of the event payload's schema, as returned by [`EventData::schema_version`]
+    ///
+    /// This is used to find which upcasters must be applied to the payload of events stored with
+    /// an older schema before they can be deserialized. See [`Upcasters`](crate::Upcasters).
+    pub schema_version: i32,
+
     /// Event data
     ///
     /// This is a generic [`serde_json::Value`] representation of the event payload. It is
@@ -43,11 +55,33 @@ pub struct DBEvent {
     /// purged, this will be `None` for security/compliance reasons - the data must be deleted from
     /// both the event log and the aggregate tables. Check the `purged_at` or `purger_id` fields to
     /// check the purge status.
+    ///
+    /// Encrypted payloads are stored as ciphertext, which is decrypted by `Event::try_from()`.
+    /// These are left in place when the event is purged. See [`EncryptionKey`].
     pub data: Option<serde_json::Value>,
 
+    /// Whether `data` holds an encrypted payload
+    ///
+    /// This is set by [`DBEvent::encrypt`] and cleared by [`DBEvent::decrypt`]. Backends that don't
+    /// support encryption don't need to store it.
+    #[cfg_attr(feature = "sqlx", sqlx(default))]
+    pub encrypted: bool,
+
     /// The ID of the creator of this event
     pub session_id: Option<Uuid>,
 
+    /// The ID shared by all events resulting from the same originating request or command
+    pub correlation_id: Option<Uuid>,
+
+    /// The ID of the event or command that directly caused this event
+    pub causation_id: Option<Uuid>,
+
+    /// Free-form event metadata
+    ///
+    /// This is a JSON object representation of [`Event::metadata`]. It maps to a `jsonb` column
+    /// in Postgres.
+    pub metadata: serde_json::Value,
+
     /// The time at which this event was created
     pub created_at: DateTime<Utc>,
 
@@ -58,6 +92,36 @@ pub struct DBEvent {
 
     /// The time at which this event was purged, if any
     pub purged_at: Option<DateTime<Utc>>,
+
+    /// The key used to decrypt this event's payload, if it is encrypted
+    ///
+    /// This is not a column of the events table. Storage backends that support encryption select
+    /// it alongside the event from where entity keys are stored. It is `None` for events that
+    /// aren't encrypted, and for encrypted events whose entity has been purged. See
+    /// [`DBEvent::decrypt`].
+    #[cfg_attr(feature = "sqlx", sqlx(default))]
+    pub encryption_key: Option<EncryptionKey>,
+}
+
+impl DBEvent {
+    /// Attempt to convert an event with an enum variant as its data payload into a `DBEvent`
+    ///
+    /// Enum payloads are serialized as `{ "event_type": ..., "data": ... }`, so only the variant's
+    /// data is stored. This is the inverse of [`Event::try_from_db_event`].
+    pub fn try_from_enum_event<EDENUM>(event: &Event<EDENUM>) -> Result<Self, serde_json::Error>
+    where
+        EDENUM: EnumEventData,
+    {
+        let mut db_event = DBEvent::try_from(event)?;
+
+        db_event.data = db_event.data.map(|mut data| {
+            data.get_mut("data")
+                .map(serde_json::Value::take)
+                .unwrap_or(serde_json::Value::Null)
+        });
+
+        Ok(db_event)
+    }
 }
 
 impl<S: EventData + serde::Serialize> TryFrom<Event<S>> for DBEvent {
@@ -68,7 +132,25 @@ impl<S: EventData + serde::Serialize> TryFrom<Event<S>> for DBEvent {
     /// This serialises the `data` field into a [`serde_json::Value`]. All other fields are left as
     /// is.
     fn try_from(other: Event<S>) -> Result<DBEvent, Self::Error> {
-        let data: Option<serde_json::Value> = if let Some(d) = other.data {
+        DBEvent::try_from(&other)
+    }
+}
+
+impl<S: EventData + serde::Serialize> TryFrom<&Event<S>> for DBEvent {
+    type Error = serde_json::Error;
+
+    /// Attempt to convert a borrowed [`Event`] into a `DBEvent`
+    ///
+    /// This is used by storage backends that need the event after it has been persisted, for
+    /// example to pass it to an entity's triggers.
+    fn try_from(other: &Event<S>) -> Result<DBEvent, Self::Error> {
+        let schema_version = other
+            .data
+            .as_ref()
+            .map(EventData::schema_version)
+            .unwrap_or(1);
+
+        let data: Option<serde_json::Value> = if let Some(d) = &other.data {
             Some(serde_json::to_value(d)?)
         } else {
             None
@@ -77,14 +159,21 @@ impl<S: EventData + serde::Serialize> TryFrom<Event<S>> for DBEvent {
         Ok(DBEvent {
             id: other.id,
             sequence_number: None,
-            event_type: other.event_type,
-            entity_type: other.entity_type,
+            event_type: other.event_type.clone(),
+            entity_type: other.entity_type.clone(),
             entity_id: other.entity_id,
+            version: other.version,
+            schema_version,
             session_id: other.session_id,
+            correlation_id: other.correlation_id,
+            causation_id: other.causation_id,
+            metadata: serde_json::to_value(&other.metadata)?,
             purger_id: other.purger_id,
             created_at: other.created_at,
             purged_at: other.purged_at,
             data,
+            encrypted: false,
+            encryption_key: None,
         })
     }
 }
@@ -0,0 +1,180 @@
+//! Per-entity encryption of stored event payloads
+
+use crate::db_event::DBEvent;
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use std::fmt;
+
+/// Length of the nonce prepended to each ciphertext
+const NONCE_LEN: usize = 12;
+
+/// An error encrypting or decrypting an event payload
+#[derive(Debug, thiserror::Error)]
+pub enum EncryptionError {
+    /// The entity's key is not a valid AES-256 key
+    #[error("Encryption key is not a valid AES-256 key")]
+    InvalidKey,
+
+    /// The payload could not be encrypted
+    #[error("Failed to encrypt event payload")]
+    Encrypt,
+
+    /// The payload could not be decrypted with the entity's key, or has been tampered with
+    #[error("Failed to decrypt event payload")]
+    Decrypt,
+
+    /// The payload is encrypted, but its entity's key wasn't loaded and the event isn't purged
+    #[error("Encryption key of an event that isn't purged is missing")]
+    MissingKey,
+
+    /// The stored ciphertext is not in the format written by [`DBEvent::encrypt`]
+    #[error("Encrypted event payload is malformed: {0}")]
+    Malformed(&'static str),
+
+    /// The payload could not be serialized before encryption or deserialized after decryption
+    #[error("Failed to convert event payload: {0}")]
+    Payload(#[from] serde_json::Error),
+}
+
+/// A key used to encrypt the event payloads of a single entity
+///
+/// Storage backends that support encryption generate one key per entity, and store it separately
+/// from the event log. Purging the entity destroys its key, which makes its events' payloads
+/// unreadable without having to modify the events themselves. This is known as crypto-shredding.
+///
+/// Payloads are encrypted with AES-256-GCM.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(transparent))]
+pub struct EncryptionKey(Vec<u8>);
+
+impl EncryptionKey {
+    /// Generate a new random key
+    pub fn generate() -> Self {
+        Self(Aes256Gcm::generate_key(OsRng).to_vec())
+    }
+
+    /// Get the raw bytes of this key
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn cipher(&self) -> Result<Aes256Gcm, EncryptionError> {
+        Aes256Gcm::new_from_slice(&self.0).map_err(|_| EncryptionError::InvalidKey)
+    }
+}
+
+impl From<Vec<u8>> for EncryptionKey {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Keys must not end up in logs
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+impl DBEvent {
+    /// Whether this event's payload is encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Encrypt this event's payload with its entity's key
+    ///
+    /// The payload is replaced with a JSON string holding the base64 encoded nonce and ciphertext,
+    /// and [`DBEvent::encrypted`] is set. The ciphertext is bound to the event's ID, so it can't be
+    /// moved to another event. Events without a payload are left as is.
+    pub fn encrypt(&mut self, key: &EncryptionKey) -> Result<(), EncryptionError> {
+        let data = match &self.data {
+            Some(data) => serde_json::to_vec(data)?,
+            None => return Ok(()),
+        };
+
+        let cipher = key.cipher()?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &data,
+                    aad: self.id.as_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::Encrypt)?;
+
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(ciphertext);
+
+        self.data = Some(serde_json::Value::String(base64::encode(encrypted)));
+        self.encrypted = true;
+
+        Ok(())
+    }
+
+    /// Decrypt this event's payload using the key in [`DBEvent::encryption_key`]
+    ///
+    /// If the payload is encrypted but the event has no key, the payload is set to `None` if the
+    /// event is purged, as its key has been destroyed. Otherwise the key is missing because it
+    /// wasn't loaded with the event, and [`EncryptionError::MissingKey`] is returned. Events with a
+    /// plaintext payload are left as is.
+    ///
+    /// The key is removed from the event once it has been used. If decryption fails, the event
+    /// isn't modified.
+    pub fn decrypt(&mut self) -> Result<(), EncryptionError> {
+        if !self.is_encrypted() {
+            self.encryption_key = None;
+
+            return Ok(());
+        }
+
+        let key = match &self.encryption_key {
+            Some(key) => key,
+            None if self.purged_at.is_some() => {
+                self.data = None;
+                self.encrypted = false;
+
+                return Ok(());
+            }
+            None => return Err(EncryptionError::MissingKey),
+        };
+
+        let encrypted = self
+            .data
+            .as_ref()
+            .and_then(|data| data.as_str())
+            .ok_or(EncryptionError::Malformed("must be a string"))
+            .and_then(|encrypted| {
+                base64::decode(encrypted).map_err(|_| EncryptionError::Malformed("invalid base64"))
+            })?;
+
+        if encrypted.len() < NONCE_LEN {
+            return Err(EncryptionError::Malformed("too short"));
+        }
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+
+        let cipher = key.cipher()?;
+
+        let data = cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: self.id.as_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::Decrypt)?;
+
+        self.data = Some(serde_json::from_slice(&data)?);
+        self.encrypted = false;
+        self.encryption_key = None;
+
+        Ok(())
+    }
+}
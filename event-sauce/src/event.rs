@@ -1,11 +1,23 @@
 //! An event
 
-use crate::{db_event::DBEvent, EnumEventData, EventData};
+use crate::{db_event::DBEvent, EncryptionError, EnumEventData, EventData};
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
-use std::convert::TryFrom;
+use std::{collections::HashMap, convert::TryFrom};
 use uuid::Uuid;
 
+/// An error converting a stored [`DBEvent`] into an [`Event`]
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    /// The payload could not be deserialized into the event's data
+    #[error("Failed to convert event payload: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    /// The encrypted payload could not be decrypted
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+}
+
 /// Event definition
 #[derive(Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
 pub struct Event<D>
@@ -29,9 +41,29 @@ where
     /// The ID of the entity (user, organisation, etc) that this event aggregates into
     pub entity_id: Uuid,
 
+    /// The position of this event in its entity's event stream, starting at `1`
+    ///
+    /// This is assigned by the storage backend when the event is persisted. If an expected version
+    /// is set when building the event, this field will be populated with the version the event
+    /// must be stored at, and persisting will fail if another event has been stored at that
+    /// version in the meantime.
+    pub version: Option<i32>,
+
     /// The ID of the creator of this event
     pub session_id: Option<Uuid>,
 
+    /// The ID shared by all events resulting from the same originating request or command
+    ///
+    /// This is used to trace a chain of events back to whatever started it.
+    pub correlation_id: Option<Uuid>,
+
+    /// The ID of the event or command that directly caused this event
+    pub causation_id: Option<Uuid>,
+
+    /// Free-form metadata attached to this event, like a request ID or client IP address
+    #[serde(default)]
+    pub metadata: HashMap<String, serde_json::Value>,
+
     /// Purger subject ID
     ///
     /// Will be `None` if event is not purged
@@ -51,13 +83,42 @@ where
     pub purged_at: Option<DateTime<Utc>>,
 }
 
+impl<D> Event<D>
+where
+    D: EventData,
+{
+    /// Replace the data payload of this event, keeping all of its other fields
+    pub fn with_data<ED>(self, data: ED) -> Event<ED>
+    where
+        ED: EventData,
+    {
+        Event {
+            id: self.id,
+            event_type: self.event_type,
+            entity_type: self.entity_type,
+            entity_id: self.entity_id,
+            version: self.version,
+            session_id: self.session_id,
+            correlation_id: self.correlation_id,
+            causation_id: self.causation_id,
+            metadata: self.metadata,
+            purger_id: self.purger_id,
+            created_at: self.created_at,
+            purged_at: self.purged_at,
+            data: Some(data),
+        }
+    }
+}
+
 impl<EDENUM> Event<EDENUM>
 where
     EDENUM: EnumEventData + for<'de> Deserialize<'de>,
 {
     /// Attempt to convert a general [`DBEvent`] to an event with an enum variant as its data
     /// payload.
-    pub fn try_from_db_event(db_event: DBEvent) -> Result<Event<EDENUM>, serde_json::Error> {
+    pub fn try_from_db_event(mut db_event: DBEvent) -> Result<Event<EDENUM>, DecodeError> {
+        db_event.decrypt()?;
+
         let intermediate =
             serde_json::json!({ "data": db_event.data, "event_type": db_event.event_type });
         let enum_data: EDENUM = serde_json::from_value(intermediate)?;
@@ -67,7 +128,11 @@ where
             event_type: db_event.event_type,
             entity_type: db_event.entity_type,
             entity_id: db_event.entity_id,
+            version: db_event.version,
             session_id: db_event.session_id,
+            correlation_id: db_event.correlation_id,
+            causation_id: db_event.causation_id,
+            metadata: serde_json::from_value(db_event.metadata)?,
             purger_id: db_event.purger_id,
             created_at: db_event.created_at,
             purged_at: db_event.purged_at,
@@ -85,7 +150,11 @@ where
             event_type: self.event_type,
             entity_type: self.entity_type,
             entity_id: self.entity_id,
+            version: self.version,
             session_id: self.session_id,
+            correlation_id: self.correlation_id,
+            causation_id: self.causation_id,
+            metadata: self.metadata,
             purger_id: self.purger_id,
             created_at: self.created_at,
             purged_at: self.purged_at,
@@ -95,13 +164,17 @@ where
 }
 
 impl<S: EventData + for<'de> Deserialize<'de>> TryFrom<DBEvent> for Event<S> {
-    type Error = serde_json::Error;
+    type Error = DecodeError;
 
     /// Attempt to decode a [`DBEvent`] into an `Event`
     ///
     /// [`DBEvent`]s carry their paylaod as a [`serde_json::Value`]. This method will attempt to
     /// [deserialise that structure into the specialised event payload `S`.
     ///
+    /// Encrypted payloads are decrypted first using the event's
+    /// [`encryption_key`](DBEvent::encryption_key). If the key has been destroyed by a purge, the
+    /// payload is `None`.
+    ///
     /// # Examples
     ///
     /// ## Read a `UserRegistered` event
@@ -126,11 +199,18 @@ impl<S: EventData + for<'de> Deserialize<'de>> TryFrom<DBEvent> for Event<S> {
     ///        entity_type: "user".to_string(),
     ///        # id,
     ///        # entity_id,
+    ///        # version: None,
     ///        # session_id: None,
+    ///        # correlation_id: None,
+    ///        # causation_id: None,
+    ///        # metadata: serde_json::json!({}),
     ///        # purger_id: None,
     ///        # created_at,
     ///        # purged_at: None,
     ///        # sequence_number: None,
+    ///        # schema_version: 1,
+    ///        # encrypted: false,
+    ///        # encryption_key: None,
     ///
     ///        // ...
     ///    };
@@ -149,7 +229,11 @@ impl<S: EventData + for<'de> Deserialize<'de>> TryFrom<DBEvent> for Event<S> {
     ///        entity_type: "user".to_string(),
     ///        # id,
     ///        # entity_id,
+    ///        # version: None,
     ///        # session_id: None,
+    ///        # correlation_id: None,
+    ///        # causation_id: None,
+    ///        # metadata: Default::default(),
     ///        # purger_id: None,
     ///        # created_at,
     ///        # purged_at: None,
@@ -157,7 +241,9 @@ impl<S: EventData + for<'de> Deserialize<'de>> TryFrom<DBEvent> for Event<S> {
     /// ```
     ///
     /// [`DBEvent`]: crate::db_event::DBEvent
-    fn try_from(other: DBEvent) -> Result<Event<S>, Self::Error> {
+    fn try_from(mut other: DBEvent) -> Result<Event<S>, Self::Error> {
+        other.decrypt()?;
+
         let data: Option<S> = if let Some(d) = other.data {
             serde_json::from_value(d)?
         } else {
@@ -169,7 +255,11 @@ impl<S: EventData + for<'de> Deserialize<'de>> TryFrom<DBEvent> for Event<S> {
             event_type: other.event_type,
             entity_type: other.entity_type,
             entity_id: other.entity_id,
+            version: other.version,
             session_id: other.session_id,
+            correlation_id: other.correlation_id,
+            causation_id: other.causation_id,
+            metadata: serde_json::from_value(other.metadata)?,
             purger_id: other.purger_id,
             created_at: other.created_at,
             purged_at: other.purged_at,
@@ -1,4 +1,7 @@
-use crate::{event_builder::EventBuilder, EnumEventData};
+use crate::{
+    event_builder::{EventBuilder, EventTracing},
+    EnumEventData,
+};
 use crate::{Entity, Event};
 use chrono::Utc;
 use uuid::Uuid;
@@ -11,6 +14,7 @@ where
 {
     payload: EDENUM,
     session_id: Option<Uuid>,
+    tracing: EventTracing,
 }
 
 impl<EDENUM> ActionEventBuilder<EDENUM>
@@ -24,7 +28,11 @@ where
             event_type: String::from(self.payload.event_type()),
             entity_type: E::entity_type(),
             entity_id: entity.as_ref().map_or(Uuid::new_v4(), |e| e.entity_id()),
+            version: None,
             session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
             purger_id: None,
             created_at: Utc::now(),
             purged_at: None,
@@ -42,6 +50,7 @@ where
         Self {
             payload,
             session_id: None,
+            tracing: EventTracing::default(),
         }
     }
 
@@ -51,4 +60,17 @@ where
 
         self
     }
+
+    fn tracing_mut(&mut self) -> Option<&mut EventTracing> {
+        Some(&mut self.tracing)
+    }
+}
+
+impl<EDENUM> From<EDENUM> for ActionEventBuilder<EDENUM>
+where
+    EDENUM: EnumEventData,
+{
+    fn from(payload: EDENUM) -> Self {
+        Self::new(payload)
+    }
 }
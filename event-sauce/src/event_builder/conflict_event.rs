@@ -1,6 +1,6 @@
 //! Event builder
 
-use crate::event_builder::EventBuilder;
+use crate::event_builder::{EventBuilder, EventTracing};
 use crate::{ConflictData, Entity, Event, EventData};
 use chrono::Utc;
 use uuid::Uuid;
@@ -9,6 +9,7 @@ use uuid::Uuid;
 pub struct ConflictEventBuilder<EDA: EventData, EDC: EventData> {
     payload: ConflictData<EDA, EDC>,
     session_id: Option<Uuid>,
+    tracing: EventTracing,
 }
 
 impl<EDA, EDC> ConflictEventBuilder<EDA, EDC>
@@ -23,7 +24,11 @@ where
             event_type: String::from(self.payload.event_type()),
             entity_type: EDA::Entity::entity_type(),
             entity_id: entity.entity_id(),
+            version: None,
             session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
             purger_id: None,
             created_at: Utc::now(),
             purged_at: None,
@@ -39,7 +44,11 @@ where
             event_type: String::from(self.payload.event_type()),
             entity_type: EDA::Entity::entity_type(),
             entity_id: self.payload.applied_event.entity_id,
+            version: None,
             session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
             purger_id: None,
             created_at: Utc::now(),
             purged_at: None,
@@ -58,6 +67,7 @@ where
         Self {
             payload,
             session_id: None,
+            tracing: EventTracing::default(),
         }
     }
 
@@ -67,6 +77,10 @@ where
 
         self
     }
+
+    fn tracing_mut(&mut self) -> Option<&mut EventTracing> {
+        Some(&mut self.tracing)
+    }
 }
 
 impl<EDA, EDC> From<ConflictData<EDA, EDC>> for ConflictEventBuilder<EDA, EDC>
@@ -0,0 +1,71 @@
+//! Event builder
+
+use crate::event_builder::{EventBuilder, EventTracing};
+use crate::{ConflictResolved, Entity, Event, EventData};
+use chrono::Utc;
+use uuid::Uuid;
+
+/// Conflict resolution event builder
+pub struct ConflictResolvedEventBuilder<EDC: EventData> {
+    payload: ConflictResolved<EDC>,
+    session_id: Option<Uuid>,
+    tracing: EventTracing,
+}
+
+impl<EDC> ConflictResolvedEventBuilder<EDC>
+where
+    EDC: EventData,
+{
+    /// Consume the builder and produce the final event
+    pub fn build(self, entity: &EDC::Entity) -> Event<ConflictResolved<EDC>> {
+        Event {
+            id: Uuid::new_v4(),
+            event_type: String::from(self.payload.event_type()),
+            entity_type: EDC::Entity::entity_type(),
+            entity_id: entity.entity_id(),
+            version: None,
+            session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
+            purger_id: None,
+            created_at: Utc::now(),
+            purged_at: None,
+            data: Some(self.payload),
+        }
+    }
+}
+
+impl<EDC> EventBuilder<ConflictResolved<EDC>> for ConflictResolvedEventBuilder<EDC>
+where
+    EDC: EventData,
+{
+    /// Create a new event builder with a given event data payload
+    fn new(payload: ConflictResolved<EDC>) -> Self {
+        Self {
+            payload,
+            session_id: None,
+            tracing: EventTracing::default(),
+        }
+    }
+
+    /// Set the session ID field of the event
+    fn session_id(mut self, session_id: Uuid) -> Self {
+        self.session_id = Some(session_id);
+
+        self
+    }
+
+    fn tracing_mut(&mut self) -> Option<&mut EventTracing> {
+        Some(&mut self.tracing)
+    }
+}
+
+impl<EDC> From<ConflictResolved<EDC>> for ConflictResolvedEventBuilder<EDC>
+where
+    EDC: EventData,
+{
+    fn from(payload: ConflictResolved<EDC>) -> Self {
+        Self::new(payload)
+    }
+}
@@ -1,12 +1,16 @@
 //! Event builder
 
-use crate::event_builder::EventBuilder;
-use crate::{Entity, Event, EventData};
+use crate::event_builder::{EventBuilder, EventTracing};
+use crate::{Entity, Event, EventData, Triggered, Untriggered};
 use chrono::Utc;
+use std::marker::PhantomData;
 use uuid::Uuid;
 
 /// Event creation builder
 ///
+/// `T` is the trigger mode of the builder, which decides whether persisting the event runs the
+/// entity's triggers. See [`Triggered`].
+///
 /// # Examples
 ///
 /// ## Create an event with overridden entity ID
@@ -57,9 +61,11 @@ use uuid::Uuid;
 ///
 /// assert_eq!(event.entity_id, user_id);
 /// ```
-pub struct CreateEventBuilder<D: EventData> {
+pub struct CreateEventBuilder<D: EventData, T = Untriggered> {
     payload: D,
     session_id: Option<Uuid>,
+    tracing: EventTracing,
+    triggers: PhantomData<T>,
     entity_id: Uuid,
 }
 
@@ -67,11 +73,22 @@ impl<D> CreateEventBuilder<D>
 where
     D: EventData,
 {
+    /// Create a new event builder with a given event data payload, in the default
+    /// [`Untriggered`] mode
+    pub fn new(payload: D) -> Self {
+        <Self as EventBuilder<D>>::new(payload)
+    }
+
     /// Create a new builder with a given entity ID
     pub fn new_with_entity_id(payload: D, entity_id: Uuid) -> Self {
         Self::new(payload).entity_id(entity_id)
     }
+}
 
+impl<D, T> CreateEventBuilder<D, T>
+where
+    D: EventData,
+{
     /// Set a custom entity ID for the event
     pub fn entity_id(mut self, entity_id: Uuid) -> Self {
         self.entity_id = entity_id;
@@ -86,7 +103,11 @@ where
             event_type: String::from(self.payload.event_type()),
             entity_type: D::Entity::entity_type(),
             entity_id: self.entity_id,
+            version: None,
             session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
             purger_id: None,
             created_at: Utc::now(),
             purged_at: None,
@@ -95,7 +116,7 @@ where
     }
 }
 
-impl<D> EventBuilder<D> for CreateEventBuilder<D>
+impl<D, T> EventBuilder<D> for CreateEventBuilder<D, T>
 where
     D: EventData,
 {
@@ -104,6 +125,8 @@ where
         Self {
             payload,
             session_id: None,
+            tracing: EventTracing::default(),
+            triggers: PhantomData,
             entity_id: Uuid::new_v4(),
         }
     }
@@ -114,9 +137,13 @@ where
 
         self
     }
+
+    fn tracing_mut(&mut self) -> Option<&mut EventTracing> {
+        Some(&mut self.tracing)
+    }
 }
 
-impl<D> From<D> for CreateEventBuilder<D>
+impl<D, T> From<D> for CreateEventBuilder<D, T>
 where
     D: EventData,
 {
@@ -124,3 +151,18 @@ where
         Self::new(payload)
     }
 }
+
+impl<D> From<CreateEventBuilder<D, Triggered>> for CreateEventBuilder<D>
+where
+    D: EventData,
+{
+    fn from(builder: CreateEventBuilder<D, Triggered>) -> Self {
+        Self {
+            payload: builder.payload,
+            session_id: builder.session_id,
+            tracing: builder.tracing,
+            entity_id: builder.entity_id,
+            triggers: PhantomData,
+        }
+    }
+}
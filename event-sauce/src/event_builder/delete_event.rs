@@ -1,8 +1,9 @@
 //! Event builder
 
-use crate::event_builder::EventBuilder;
-use crate::{Entity, Event, EventData};
+use crate::event_builder::{EventBuilder, EventTracing};
+use crate::{Entity, Event, EventData, Triggered, Untriggered};
 use chrono::Utc;
+use std::marker::PhantomData;
 use uuid::Uuid;
 
 /// Delete event builder
@@ -10,6 +11,9 @@ use uuid::Uuid;
 /// Build an [`Event`] from a given payload used to delete entities. The deletion behaviour is
 /// defined by the entity's implementation of the [`crate::AggregateDelete`] trait.
 ///
+/// `T` is the trigger mode of the builder, which decides whether persisting the event runs the
+/// entity's triggers. See [`Triggered`].
+///
 /// # Examples
 ///
 /// ## Build a deletion even with session ID
@@ -51,12 +55,25 @@ use uuid::Uuid;
 /// let updated = user.try_delete(UserDeleted)?;
 /// # Ok(()) }
 /// ```
-pub struct DeleteEventBuilder<D: EventData> {
+pub struct DeleteEventBuilder<D: EventData, T = Untriggered> {
     payload: D,
     session_id: Option<Uuid>,
+    tracing: EventTracing,
+    triggers: PhantomData<T>,
 }
 
 impl<D> DeleteEventBuilder<D>
+where
+    D: EventData,
+{
+    /// Create a new event builder with a given event data payload, in the default
+    /// [`Untriggered`] mode
+    pub fn new(payload: D) -> Self {
+        <Self as EventBuilder<D>>::new(payload)
+    }
+}
+
+impl<D, T> DeleteEventBuilder<D, T>
 where
     D: EventData,
 {
@@ -67,7 +84,11 @@ where
             event_type: String::from(self.payload.event_type()),
             entity_type: D::Entity::entity_type(),
             entity_id: entity.entity_id(),
+            version: None,
             session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
             purger_id: None,
             created_at: Utc::now(),
             purged_at: None,
@@ -83,7 +104,11 @@ where
             event_type: String::from(self.payload.event_type()),
             entity_type: D::Entity::entity_type(),
             entity_id,
+            version: None,
             session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
             purger_id: None,
             created_at: Utc::now(),
             purged_at: None,
@@ -92,7 +117,7 @@ where
     }
 }
 
-impl<D> EventBuilder<D> for DeleteEventBuilder<D>
+impl<D, T> EventBuilder<D> for DeleteEventBuilder<D, T>
 where
     D: EventData,
 {
@@ -101,6 +126,8 @@ where
         Self {
             payload,
             session_id: None,
+            tracing: EventTracing::default(),
+            triggers: PhantomData,
         }
     }
 
@@ -110,9 +137,13 @@ where
 
         self
     }
+
+    fn tracing_mut(&mut self) -> Option<&mut EventTracing> {
+        Some(&mut self.tracing)
+    }
 }
 
-impl<D> From<D> for DeleteEventBuilder<D>
+impl<D, T> From<D> for DeleteEventBuilder<D, T>
 where
     D: EventData,
 {
@@ -120,3 +151,17 @@ where
         Self::new(payload)
     }
 }
+
+impl<D> From<DeleteEventBuilder<D, Triggered>> for DeleteEventBuilder<D>
+where
+    D: EventData,
+{
+    fn from(builder: DeleteEventBuilder<D, Triggered>) -> Self {
+        Self {
+            payload: builder.payload,
+            session_id: builder.session_id,
+            tracing: builder.tracing,
+            triggers: PhantomData,
+        }
+    }
+}
@@ -1,19 +1,36 @@
 mod action_event;
 mod conflict_event;
+mod conflict_resolved_event;
 mod create_event;
 mod delete_event;
 mod purge_event;
 mod update_event;
 
+use crate::{Event, EventData};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 pub use action_event::ActionEventBuilder;
 pub use conflict_event::ConflictEventBuilder;
+pub use conflict_resolved_event::ConflictResolvedEventBuilder;
 pub use create_event::CreateEventBuilder;
 pub use delete_event::DeleteEventBuilder;
 pub use purge_event::PurgeEventBuilder;
 pub use update_event::UpdateEventBuilder;
 
+/// Correlation ID, causation ID and metadata of the event contained within a builder
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventTracing {
+    /// The ID shared by all events resulting from the same originating request or command
+    pub correlation_id: Option<Uuid>,
+
+    /// The ID of the event or command that directly caused this event
+    pub causation_id: Option<Uuid>,
+
+    /// Free-form metadata entries
+    pub metadata: HashMap<String, serde_json::Value>,
+}
+
 /// Methods common to all event builders
 pub trait EventBuilder<D>: Sized {
     /// Create a new builder with a given payload
@@ -21,4 +38,53 @@ pub trait EventBuilder<D>: Sized {
 
     /// Set the session ID on the event contained within the builder
     fn session_id(self, session_id: Uuid) -> Self;
+
+    /// Get the tracing fields of the event contained within the builder
+    ///
+    /// Builders that don't record tracing fields can leave this as the default, in which case
+    /// [`correlation_id`](EventBuilder::correlation_id),
+    /// [`causation_id`](EventBuilder::causation_id) and [`metadata`](EventBuilder::metadata) do
+    /// nothing.
+    fn tracing_mut(&mut self) -> Option<&mut EventTracing> {
+        None
+    }
+
+    /// Set the ID shared by all events resulting from the same originating request or command
+    fn correlation_id(mut self, correlation_id: Uuid) -> Self {
+        if let Some(tracing) = self.tracing_mut() {
+            tracing.correlation_id = Some(correlation_id);
+        }
+
+        self
+    }
+
+    /// Set the ID of the event or command that directly caused this event
+    fn causation_id(mut self, causation_id: Uuid) -> Self {
+        if let Some(tracing) = self.tracing_mut() {
+            tracing.causation_id = Some(causation_id);
+        }
+
+        self
+    }
+
+    /// Add a metadata entry to the event, replacing any existing value for `key`
+    fn metadata(mut self, key: &str, value: serde_json::Value) -> Self {
+        if let Some(tracing) = self.tracing_mut() {
+            tracing.metadata.insert(key.to_string(), value);
+        }
+
+        self
+    }
+
+    /// Mark the event as caused by a previous event
+    ///
+    /// The causation ID is set to the ID of `cause`. The correlation ID is carried over from
+    /// `cause`, or set to its ID if `cause` is the first event in the chain.
+    fn caused_by<ED>(self, cause: &Event<ED>) -> Self
+    where
+        ED: EventData,
+    {
+        self.causation_id(cause.id)
+            .correlation_id(cause.correlation_id.unwrap_or(cause.id))
+    }
 }
@@ -1,7 +1,9 @@
 //! Event builder
 
-use crate::{Entity, Event, EventBuilder, EventData};
+use crate::event_builder::EventTracing;
+use crate::{Entity, Event, EventBuilder, EventData, Triggered, Untriggered};
 use chrono::Utc;
+use std::marker::PhantomData;
 use uuid::Uuid;
 
 /// Purge event builder
@@ -9,6 +11,9 @@ use uuid::Uuid;
 /// Build an [`Event`] from a session id, used to purge entities. Purging deletes the entities.
 /// It keeps the event history but sets the `data` property of all events related to the entity to None.
 ///
+/// `T` is the trigger mode of the builder, which decides whether persisting the event runs the
+/// entity's triggers. See [`Triggered`].
+///
 /// # Examples
 ///
 /// ## Build a purge event with session ID
@@ -48,12 +53,25 @@ use uuid::Uuid;
 /// // purge_builder.purge(&store).await?;
 /// # Ok(()) }
 /// ```
-pub struct PurgeEventBuilder<D: EventData> {
+pub struct PurgeEventBuilder<D: EventData, T = Untriggered> {
     session_id: Option<Uuid>,
+    tracing: EventTracing,
+    triggers: PhantomData<T>,
     payload: D,
 }
 
-impl<D: EventData> PurgeEventBuilder<D> {
+impl<D> PurgeEventBuilder<D>
+where
+    D: EventData,
+{
+    /// Create a new event builder with a given event data payload, in the default
+    /// [`Untriggered`] mode
+    pub fn new(payload: D) -> Self {
+        <Self as EventBuilder<D>>::new(payload)
+    }
+}
+
+impl<D: EventData, T> PurgeEventBuilder<D, T> {
     /// Consume the builder and produce the final event
     pub fn build(self, entity: &D::Entity) -> Event<D> {
         self.build_with_entity_id(entity.entity_id())
@@ -67,23 +85,20 @@ impl<D: EventData> PurgeEventBuilder<D> {
             event_type: String::from(self.payload.event_type()),
             entity_type: D::Entity::entity_type(),
             entity_id,
+            version: None,
             session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
             purger_id: self.session_id,
             created_at: Utc::now(),
             purged_at: Some(Utc::now()),
             data: None,
         }
     }
-
-    fn new(payload: D) -> Self {
-        Self {
-            session_id: None,
-            payload,
-        }
-    }
 }
 
-impl<D> EventBuilder<D> for PurgeEventBuilder<D>
+impl<D, T> EventBuilder<D> for PurgeEventBuilder<D, T>
 where
     D: EventData,
 {
@@ -92,6 +107,8 @@ where
         Self {
             payload,
             session_id: None,
+            tracing: EventTracing::default(),
+            triggers: PhantomData,
         }
     }
 
@@ -101,9 +118,13 @@ where
 
         self
     }
+
+    fn tracing_mut(&mut self) -> Option<&mut EventTracing> {
+        Some(&mut self.tracing)
+    }
 }
 
-impl<D> From<D> for PurgeEventBuilder<D>
+impl<D, T> From<D> for PurgeEventBuilder<D, T>
 where
     D: EventData,
 {
@@ -111,3 +132,17 @@ where
         Self::new(payload)
     }
 }
+
+impl<D> From<PurgeEventBuilder<D, Triggered>> for PurgeEventBuilder<D>
+where
+    D: EventData,
+{
+    fn from(builder: PurgeEventBuilder<D, Triggered>) -> Self {
+        Self {
+            session_id: builder.session_id,
+            tracing: builder.tracing,
+            payload: builder.payload,
+            triggers: PhantomData,
+        }
+    }
+}
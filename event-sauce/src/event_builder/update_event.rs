@@ -1,12 +1,16 @@
 //! Event builder
 
-use crate::event_builder::EventBuilder;
-use crate::{Entity, Event, EventData};
+use crate::event_builder::{EventBuilder, EventTracing};
+use crate::{Entity, Event, EventData, Triggered, Untriggered};
 use chrono::Utc;
+use std::marker::PhantomData;
 use uuid::Uuid;
 
 /// Event update builder
 ///
+/// `T` is the trigger mode of the builder, which decides whether persisting the event runs the
+/// entity's triggers. See [`Triggered`].
+///
 /// # Examples
 ///
 /// ## Update an event with a set session ID
@@ -67,15 +71,39 @@ use uuid::Uuid;
 /// assert_eq!(updated.event.entity_id, user_id);
 /// # Ok(()) }
 /// ```
-pub struct UpdateEventBuilder<D: EventData> {
+pub struct UpdateEventBuilder<D: EventData, T = Untriggered> {
     payload: D,
     session_id: Option<Uuid>,
+    tracing: EventTracing,
+    triggers: PhantomData<T>,
+    expected_version: Option<i32>,
 }
 
 impl<D> UpdateEventBuilder<D>
 where
     D: EventData,
 {
+    /// Create a new event builder with a given event data payload, in the default
+    /// [`Untriggered`] mode
+    pub fn new(payload: D) -> Self {
+        <Self as EventBuilder<D>>::new(payload)
+    }
+}
+
+impl<D, T> UpdateEventBuilder<D, T>
+where
+    D: EventData,
+{
+    /// Set the version of the entity's event stream this update is based on
+    ///
+    /// When the event is persisted, the storage backend will check that no other event has been
+    /// stored against the entity since `version`, and fail with a concurrency error otherwise.
+    pub fn expected_version(mut self, version: i32) -> Self {
+        self.expected_version = Some(version);
+
+        self
+    }
+
     /// Consume the builder and produce the final event
     pub fn build(self, entity: &D::Entity) -> Event<D> {
         Event {
@@ -83,7 +111,11 @@ where
             event_type: String::from(self.payload.event_type()),
             entity_type: D::Entity::entity_type(),
             entity_id: entity.entity_id(),
+            version: self.expected_version.map(|version| version + 1),
             session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
             purger_id: None,
             created_at: Utc::now(),
             purged_at: None,
@@ -94,21 +126,37 @@ where
     /// Workaround method to get an entity ID out of entities when implementing
     /// `UpdateEntityBuilder`
     pub(crate) fn build_with_entity_id(self, entity_id: Uuid) -> Event<D> {
-        Event {
+        let (mut event, payload) = self.build_without_payload(entity_id);
+
+        event.data = Some(payload);
+
+        event
+    }
+
+    /// Produce the final event without its data, returning the payload separately so it can be
+    /// checked or changed before being attached to the event
+    pub(crate) fn build_without_payload(self, entity_id: Uuid) -> (Event<D>, D) {
+        let event = Event {
             id: Uuid::new_v4(),
             event_type: String::from(self.payload.event_type()),
             entity_type: D::Entity::entity_type(),
             entity_id,
+            version: self.expected_version.map(|version| version + 1),
             session_id: self.session_id,
+            correlation_id: self.tracing.correlation_id,
+            causation_id: self.tracing.causation_id,
+            metadata: self.tracing.metadata,
             purger_id: None,
             created_at: Utc::now(),
             purged_at: None,
-            data: Some(self.payload),
-        }
+            data: None,
+        };
+
+        (event, self.payload)
     }
 }
 
-impl<D> EventBuilder<D> for UpdateEventBuilder<D>
+impl<D, T> EventBuilder<D> for UpdateEventBuilder<D, T>
 where
     D: EventData,
 {
@@ -117,6 +165,9 @@ where
         Self {
             payload,
             session_id: None,
+            tracing: EventTracing::default(),
+            triggers: PhantomData,
+            expected_version: None,
         }
     }
 
@@ -126,9 +177,13 @@ where
 
         self
     }
+
+    fn tracing_mut(&mut self) -> Option<&mut EventTracing> {
+        Some(&mut self.tracing)
+    }
 }
 
-impl<D> From<D> for UpdateEventBuilder<D>
+impl<D, T> From<D> for UpdateEventBuilder<D, T>
 where
     D: EventData,
 {
@@ -136,3 +191,18 @@ where
         Self::new(payload)
     }
 }
+
+impl<D> From<UpdateEventBuilder<D, Triggered>> for UpdateEventBuilder<D>
+where
+    D: EventData,
+{
+    fn from(builder: UpdateEventBuilder<D, Triggered>) -> Self {
+        Self {
+            payload: builder.payload,
+            session_id: builder.session_id,
+            tracing: builder.tracing,
+            expected_version: builder.expected_version,
+            triggers: PhantomData,
+        }
+    }
+}
@@ -9,20 +9,40 @@
 #![deny(missing_docs)]
 #![deny(broken_intra_doc_links)]
 
+mod batch;
+mod command;
 mod db_event;
+mod encryption;
 mod event;
 mod event_builder;
+mod pii;
 pub mod prelude;
+mod purge;
+mod resolve;
 mod triggers;
+mod upcast;
 
 pub use crate::{
+    batch::{BatchBuilder, BatchEvents, BatchTriggers, DeleteBatchBuilder},
+    command::{Command, CommandError, CommandHandler},
     db_event::DBEvent,
-    event::Event,
+    encryption::{EncryptionError, EncryptionKey},
+    event::{DecodeError, Event},
     event_builder::{
-        ActionEventBuilder, ConflictEventBuilder, CreateEventBuilder, DeleteEventBuilder,
-        EventBuilder, PurgeEventBuilder, UpdateEventBuilder,
+        ActionEventBuilder, ConflictEventBuilder, ConflictResolvedEventBuilder, CreateEventBuilder,
+        DeleteEventBuilder, EventBuilder, EventTracing, PurgeEventBuilder, UpdateEventBuilder,
     },
-    triggers::{OnCreated, OnUpdated},
+    pii::PartialPurge,
+    purge::{PurgeReceipt, PurgeReport, Purgeable, RelatedEntity},
+    resolve::{
+        AggregateResolveConflict, ConflictResolved, KeepApplied, LastWriterWins, MergeFields,
+        ResolutionStrategy, ResolveConflictEntityBuilder,
+    },
+    triggers::{
+        OnConflictResolved, OnConflicted, OnCreated, OnDeleted, OnPurged, OnUpdated,
+        RunEnumTrigger, RunTrigger, Triggered, Untriggered,
+    },
+    upcast::Upcasters,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -41,6 +61,17 @@ pub trait Entity {
     fn entity_id(&self) -> Uuid;
 }
 
+/// An entity that can be stored as a snapshot of its current state
+///
+/// Loading an entity with a long event history can be slow. Storage backends that support
+/// snapshots will periodically store the serialized entity, and load it by replaying only the
+/// events persisted after the latest snapshot.
+pub trait Snapshot: Entity + Serialize + for<'de> Deserialize<'de> {
+    /// The number of events that must be replayed on top of the latest snapshot before a new
+    /// snapshot is taken
+    const SNAPSHOT_FREQUENCY: usize = 100;
+}
+
 /// An event's data payload
 pub trait EventData: Serialize + Sized {
     /// The entity to bind this event to
@@ -52,6 +83,21 @@ pub trait EventData: Serialize + Sized {
     /// Get the event type/identifier in PascalCase like `UserCreated` or `PasswordChanged`
     fn event_type(&self) -> &'static str;
 
+    /// The version of this payload's schema
+    ///
+    /// This should be incremented whenever the serialized form of the payload changes, and an
+    /// upcaster registered to convert payloads stored with the previous version. See [`Upcasters`].
+    /// Defaults to `1`.
+    fn schema_version(&self) -> i32 {
+        1
+    }
+
+    /// The names of this payload's fields that contain personally identifiable information
+    ///
+    /// These are the only fields removed from stored events by a [`PartialPurge`]. Fields are
+    /// marked with `#[event_sauce(pii)]` when deriving event data. Defaults to no fields.
+    const PII_FIELDS: &'static [&'static str] = &[];
+
     /// Convert the event into a builder with a given session ID
     ///
     /// This is a convenience method to shorten `Event {}.into_builder().session_id(id)` to
@@ -67,7 +113,15 @@ pub trait EventData: Serialize + Sized {
 }
 
 /// Event payloads that can be different variants of an enum.
-pub trait EnumEventData: EventData {}
+pub trait EnumEventData: EventData {
+    /// Get the [`EventData::PII_FIELDS`] of the variant stored with the given event type
+    ///
+    /// Returns `None` if no variant is stored with that event type. Derived implementations
+    /// return the PII fields of each variant's event data. Defaults to `None`.
+    fn pii_fields(_event_type: &str) -> Option<&'static [&'static str]> {
+        None
+    }
+}
 
 /// The `EventData` of the `Event` that can be conflicted with an already applied `Event<EDA>`.
 pub trait ConflictCheck<EDA>: EventData
@@ -79,7 +133,8 @@ where
     /// Returns either `Ok(EventData)` in case of no conflicts or [`ConflictData`] with `Self` in
     /// place of `EDC`, describing the conflict otherwise.
     ///
-    /// This function will be called during [`UpdateEntityBuilder::try_update`](trait.UpdateEntityBuilder.html#method.try_update).
+    /// This function is called by [`UpdateEntityBuilder::try_update_checked`] for each event
+    /// applied since the update was made.
     fn check_conflict(self, applied_event: &Event<EDA>) -> Result<Self, ConflictData<EDA, Self>>;
 }
 
@@ -285,6 +340,79 @@ where
 
         Ok(StorageBuilder::new(entity, event))
     }
+
+    /// Update the entity with an event, checking it for conflicts with events applied since the
+    /// update was made
+    ///
+    /// `applied_events` are the events stored against the entity after the version the update was
+    /// based on, in order. The update's payload is passed to [`ConflictCheck::check_conflict`] for
+    /// each of them. If no conflict is found, the entity is updated as with
+    /// [`UpdateEntityBuilder::try_update`]. Otherwise, the entity is flagged as conflicted with
+    /// [`ConflictEntityBuilder::try_flag_conflict`] using the first conflict found, and the
+    /// update is discarded.
+    fn try_update_checked<EDA, B, I>(
+        self,
+        builder: B,
+        applied_events: I,
+    ) -> Result<CheckedUpdateBuilder<Self, EDA, ED>, <Self as AggregateUpdate<ED>>::Error>
+    where
+        Self: ConflictEntityBuilder<EDA, ED> + AggregateUpdate<ED, Output = Self>,
+        <Self as AggregateConflict<EDA, ED>>::Error: Into<<Self as AggregateUpdate<ED>>::Error>,
+        EDA: EventData,
+        ED: ConflictCheck<EDA>,
+        B: Into<UpdateEventBuilder<ED>>,
+        I: IntoIterator<Item = Event<EDA>>,
+    {
+        let (mut event, mut data) = builder.into().build_without_payload(self.entity_id());
+
+        for applied_event in applied_events {
+            data = match data.check_conflict(&applied_event) {
+                Ok(data) => data,
+                Err(conflict) => {
+                    let mut conflict_event =
+                        ConflictEventBuilder::from(conflict).build_with_entity_id();
+
+                    // The conflict replaces the update, so keeps its context
+                    conflict_event.version = event.version;
+                    conflict_event.session_id = event.session_id;
+                    conflict_event.correlation_id = event.correlation_id;
+                    conflict_event.causation_id = event.causation_id;
+                    conflict_event.metadata = event.metadata;
+
+                    let entity = AggregateConflict::try_aggregate_conflict(self, &conflict_event)
+                        .map_err(Into::into)?;
+
+                    return Ok(CheckedUpdateBuilder::Conflict(StorageBuilder::new(
+                        entity,
+                        conflict_event,
+                    )));
+                }
+            };
+        }
+
+        event.data = Some(data);
+
+        let entity = self.try_aggregate_update(&event)?;
+
+        Ok(CheckedUpdateBuilder::Update(StorageBuilder::new(
+            entity, event,
+        )))
+    }
+}
+
+/// The result of [`UpdateEntityBuilder::try_update_checked`]
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum CheckedUpdateBuilder<Ent, EDA, ED>
+where
+    EDA: EventData,
+    ED: EventData,
+{
+    /// The update doesn't conflict with any applied events, so can be persisted
+    Update(StorageBuilder<Ent, ED>),
+
+    /// The update conflicts with an applied event, so the conflict should be persisted instead
+    Conflict(StorageBuilder<Ent, ConflictData<EDA, ED>>),
 }
 
 /// A wrapper trait around [`AggregateDelete`] to handle event-sauce integration boilerplate
@@ -362,6 +490,30 @@ where
 
         Ok(ActionBuilder::new(entity, event))
     }
+
+    /// Rebuild an entity by applying a history of events in order
+    ///
+    /// Each event is passed to [`AggregateAction::try_aggregate_action`] along with the entity
+    /// produced by the previous event. Returns `Ok(None)` if `events` is empty.
+    fn try_replay<I>(events: I) -> Result<Option<Self>, Self::Error>
+    where
+        I: IntoIterator<Item = Event<EDENUM>>,
+    {
+        Self::try_replay_from(None, events)
+    }
+
+    /// Apply a history of events in order on top of an existing entity
+    ///
+    /// This is used to continue replaying from a known state like a [`Snapshot`]. Returns `entity`
+    /// unchanged if `events` is empty.
+    fn try_replay_from<I>(entity: Option<Self>, events: I) -> Result<Option<Self>, Self::Error>
+    where
+        I: IntoIterator<Item = Event<EDENUM>>,
+    {
+        events.into_iter().try_fold(entity, |entity, event| {
+            Self::try_aggregate_action(entity, &event).map(Some)
+        })
+    }
 }
 
 /// Implemented for all backend storage providers (Postgres, etc)
@@ -377,6 +529,116 @@ pub trait StorageBackend {
     // async fn transaction(&self) -> Result<Self::Transaction, Self::Error>;
 }
 
+/// Load entities from a storage backend by replaying their event history
+///
+/// The event log is the source of truth for an entity's state. Implementations of this trait
+/// must read every event stored against the given entity ID in the order in which it was
+/// persisted, and fold it into an entity using [`ActionEntityBuilder::try_replay`].
+#[async_trait::async_trait]
+pub trait StorageBackendLoad: StorageBackend {
+    /// Load an entity by replaying all events stored against the given entity ID
+    ///
+    /// `EDENUM` must have a variant for every event type stored against the entity. Returns
+    /// `Ok(None)` if no events exist for the entity, or if the entity has been purged.
+    async fn load<E, EDENUM>(&self, entity_id: Uuid) -> Result<Option<E>, Self::Error>
+    where
+        E: ActionEntityBuilder<EDENUM> + Send,
+        E::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send;
+}
+
+/// Load entities from a storage backend starting from their latest [`Snapshot`]
+#[async_trait::async_trait]
+pub trait StorageBackendLoadSnapshot: StorageBackend {
+    /// Load an entity from its latest snapshot, replaying only the events stored after it
+    ///
+    /// If there is no snapshot, all events are replayed as with [`StorageBackendLoad::load`]. If at
+    /// least [`Snapshot::SNAPSHOT_FREQUENCY`] events were replayed, a new snapshot of the loaded
+    /// entity is stored.
+    async fn load_from_snapshot<E, EDENUM>(
+        &self,
+        entity_id: Uuid,
+    ) -> Result<Option<E>, Self::Error>
+    where
+        E: ActionEntityBuilder<EDENUM> + Snapshot + Send + Sync,
+        E::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send;
+}
+
+/// Run [`Command`]s against entities in a storage backend
+#[async_trait::async_trait]
+pub trait StorageBackendExecute: StorageBackend {
+    /// Load the command's entity, handle the command and persist the resulting events and entity
+    /// in a single transaction
+    ///
+    /// If the entity is modified by another writer between being loaded and the events being
+    /// persisted, the whole process is retried up to [`Command::MAX_ATTEMPTS`] times. Returns the
+    /// updated entity, or `None` if the entity doesn't exist and the handler emitted no events.
+    ///
+    /// Each event emitted by the command runs the trigger of its variant's event data, in the
+    /// order the events were emitted, after the updated entity has been persisted.
+    async fn execute<H, C>(
+        &self,
+        handler: &H,
+        command: C,
+    ) -> Result<Option<C::Entity>, Self::Error>
+    where
+        H: CommandHandler<C> + Sync,
+        C: Command + Send + Sync,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Entity: Persistable<Self::Transaction> + Send + Sync,
+        <C::Entity as AggregateAction<C::Event>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Event:
+            RunEnumTrigger<C::Entity, Self::Transaction> + for<'de> Deserialize<'de> + Send + Sync;
+}
+
+/// Update entities in a storage backend, detecting conflicts with concurrent changes
+#[async_trait::async_trait]
+pub trait StorageBackendUpdateChecked: StorageBackend {
+    /// Load an entity and update it based on an earlier version of it, for example the version a
+    /// client last saw before sending the update
+    ///
+    /// Events stored against the entity after `base_version` are loaded and passed to
+    /// [`UpdateEntityBuilder::try_update_checked`]. Either the update or a [`ConflictData`] event
+    /// is persisted along with the resulting entity, running the matching trigger. Fails with a
+    /// not found error if the entity doesn't exist.
+    async fn update_checked<E, EDENUM, ED, B>(
+        &self,
+        entity_id: Uuid,
+        base_version: i32,
+        builder: B,
+    ) -> Result<CheckedUpdate<E>, Self::Error>
+    where
+        E: ActionEntityBuilder<EDENUM>
+            + UpdateEntityBuilder<ED>
+            + ConflictEntityBuilder<EDENUM, ED>
+            + AggregateUpdate<ED, Output = E>
+            + Persistable<Self::Transaction>
+            + Send
+            + Sync,
+        <E as AggregateAction<EDENUM>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        <E as AggregateUpdate<ED>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        <E as AggregateConflict<EDENUM, ED>>::Error: Into<<E as AggregateUpdate<ED>>::Error>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send + Sync,
+        ED: ConflictCheck<EDENUM> + Send + Sync,
+        ED::Builder: RunTrigger<E, ED, Self::Transaction>,
+        ConflictEventBuilder<EDENUM, ED>:
+            RunTrigger<E, ConflictData<EDENUM, ED>, Self::Transaction>,
+        B: Into<UpdateEventBuilder<ED>> + Send;
+}
+
+/// The outcome of [`StorageBackendUpdateChecked::update_checked`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckedUpdate<Ent> {
+    /// The update was applied, giving the updated entity
+    Updated(Ent),
+
+    /// The update conflicted with a concurrent change and was discarded, giving the entity
+    /// flagged as conflicted
+    Conflicted(Ent),
+}
+
 /// Storage backend transaction
 #[async_trait::async_trait]
 pub trait StorageBackendTransaction {
@@ -403,6 +665,16 @@ where
     pub fn new(entity: Ent, event: Event<ED>) -> Self {
         Self { event, entity }
     }
+
+    /// Set the version of the entity's event stream this change is based on
+    ///
+    /// This is equivalent to calling [`UpdateEventBuilder::expected_version`] when building the
+    /// event.
+    pub fn expected_version(mut self, version: i32) -> Self {
+        self.event.version = Some(version + 1);
+
+        self
+    }
 }
 
 /// A wrapper around a tuple of event and entity, used to delete an entity in the database
@@ -450,27 +722,40 @@ where
 }
 
 /// DOCS
+///
+/// The transaction type parameter defaults to the backend's transaction type and only exists so
+/// the storage backend can be inferred from the transaction passed to
+/// [`stage_persist`](StorageBuilderPersist::stage_persist), even when more than one backend is in
+/// use.
 #[async_trait::async_trait]
-pub trait StorageBuilderPersist<S, E>
+pub trait StorageBuilderPersist<S, E, Txn = <S as StorageBackend>::Transaction>
 where
-    S: StorageBackend,
-    E: Persistable<S::Transaction, E>,
+    S: StorageBackend<Transaction = Txn>,
+    Txn: StorageBackendTransaction,
+    E: Persistable<Txn, E>,
 {
-    /// Stage a deletion in a given transaction
-    async fn stage_persist(self, tx: &mut S::Transaction) -> Result<E, S::Error>;
+    /// Stage a persist in a given transaction
+    ///
+    /// Implementations must run the entity's trigger for the event through [`RunTrigger`] after
+    /// the entity is persisted.
+    async fn stage_persist(self, tx: &mut Txn) -> Result<E, S::Error>;
 
     /// Delete immediately
     async fn persist(self, store: &S) -> Result<E, S::Error>;
 }
 
 /// DOCS
+///
+/// The transaction type parameter works the same way as on [`StorageBuilderPersist`].
 #[async_trait::async_trait]
-pub trait DeleteBuilderPersist<S>
+pub trait DeleteBuilderPersist<S, Txn = <S as StorageBackend>::Transaction>
 where
-    S: StorageBackend,
+    S: StorageBackend<Transaction = Txn>,
 {
     /// Stage a deletion in a given transaction
-    async fn stage_delete(self, tx: &mut S::Transaction) -> Result<(), S::Error>;
+    ///
+    /// Implementations must run the entity's [`OnDeleted`] trigger before the entity is removed.
+    async fn stage_delete(self, tx: &mut Txn) -> Result<(), S::Error>;
 
     /// Delete immediately
     async fn delete(self, store: &S) -> Result<(), S::Error>;
@@ -482,6 +767,8 @@ pub struct PurgeBuilder<Ent: Entity, ED: EventData> {
     pub event: Event<ED>,
     /// The entity to purge
     pub entity: Ent,
+    /// Only remove PII from the entity's events instead of their whole payloads, if set
+    pub partial: Option<PartialPurge>,
 }
 
 impl<ED, Ent> PurgeBuilder<Ent, ED>
@@ -491,7 +778,19 @@ where
 {
     /// Create a new entity/event pair
     pub fn new(entity: Ent, event: Event<ED>) -> Self {
-        Self { event, entity }
+        Self {
+            event,
+            entity,
+            partial: None,
+        }
+    }
+
+    /// Only remove the fields of the entity's events that contain personally identifiable
+    /// information, keeping the rest of each payload
+    pub fn partial(mut self, partial: PartialPurge) -> Self {
+        self.partial = Some(partial);
+
+        self
     }
 }
 
@@ -502,16 +801,42 @@ where
 /// The implementation must:
 ///
 /// - remove all eventdata for all events with the entity_id provided, but preserve all other event data.
+///   If the builder has a [`PartialPurge`] set, only remove the event data it selects.
 /// - remove the entity aggregation
 /// - insert the purge event in the store. The event data of the purge event must be preserved.
+///
+/// The transaction type parameter works the same way as on [`StorageBuilderPersist`].
 #[async_trait::async_trait]
-pub trait PurgeBuilderExecute<S>
+pub trait PurgeBuilderExecute<S, Txn = <S as StorageBackend>::Transaction>
 where
-    S: StorageBackend,
+    S: StorageBackend<Transaction = Txn>,
 {
     /// Stage the entity purge in a given transaction
-    async fn stage_purge(self, tx: &mut S::Transaction) -> Result<(), S::Error>;
+    ///
+    /// Implementations must run the entity's [`OnPurged`] trigger after the entity is purged.
+    async fn stage_purge(self, tx: &mut Txn) -> Result<(), S::Error>;
 
     /// Purge an entity
     async fn purge(self, store: &S) -> Result<(), S::Error>;
 }
+
+/// Report on what purging an entity removes, for compliance records
+///
+/// This must be implemented for [`PurgeBuilder`] by backend storages that support purge reports.
+/// [`PurgeBuilderReport::purge_with_receipt`] must purge the entity the same way as
+/// [`PurgeBuilderExecute::purge`].
+#[async_trait::async_trait]
+pub trait PurgeBuilderReport<S>
+where
+    S: StorageBackend,
+{
+    /// Report what purging the entity would remove, without purging it
+    ///
+    /// The report is built by reading from the store only. Nothing is removed and the entity's
+    /// [`OnPurged`] trigger isn't run, so purge failures that only happen part way through the
+    /// purge itself aren't reported.
+    async fn dry_run(self, store: &S) -> Result<PurgeReport, S::Error>;
+
+    /// Purge the entity, returning a receipt that can be kept as evidence of the purge
+    async fn purge_with_receipt(self, store: &S) -> Result<PurgeReceipt, S::Error>;
+}
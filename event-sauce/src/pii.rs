@@ -0,0 +1,89 @@
+//! Purge personally identifiable information from stored events
+
+use crate::{db_event::DBEvent, EncryptionError, EnumEventData};
+
+/// A purge that only removes the fields of an entity's events that contain personally
+/// identifiable information (PII)
+///
+/// By default, purging an entity removes the whole payload of each of its events. A partial purge
+/// instead removes only the fields listed in each event type's [`EventData::PII_FIELDS`], keeping
+/// the rest of the event's history. Fields are marked as PII with `#[event_sauce(pii)]` when
+/// deriving event data.
+///
+/// The entity's aggregate is still removed, and its events are still marked as purged.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use event_sauce::PartialPurge;
+///
+/// user.try_purge(UserPurged)
+///     .partial(PartialPurge::new::<UserEventData>().redact_with(serde_json::json!("[redacted]")))
+///     .purge(&store)
+///     .await?;
+/// ```
+///
+/// [`EventData::PII_FIELDS`]: crate::EventData::PII_FIELDS
+#[derive(Debug, Clone)]
+pub struct PartialPurge {
+    pii_fields: fn(&str) -> Option<&'static [&'static str]>,
+    redacted: Option<serde_json::Value>,
+}
+
+impl PartialPurge {
+    /// Purge the PII fields of every variant of `EDENUM`
+    ///
+    /// `EDENUM` should have a variant for every event type stored against the entity. Events of
+    /// other types are purged entirely, as they can't be checked for PII.
+    pub fn new<EDENUM>() -> Self
+    where
+        EDENUM: EnumEventData,
+    {
+        Self {
+            pii_fields: EDENUM::pii_fields,
+            redacted: None,
+        }
+    }
+
+    /// Replace PII fields with the given value instead of removing them
+    ///
+    /// This is useful if the payloads must still deserialize into their event data after the
+    /// purge.
+    pub fn redact_with(mut self, value: serde_json::Value) -> Self {
+        self.redacted = Some(value);
+
+        self
+    }
+
+    /// Remove or redact the PII fields of a stored event's payload
+    ///
+    /// Encrypted payloads are decrypted first, so the event must have its
+    /// [`encryption_key`](DBEvent::encryption_key) set.
+    pub fn apply(&self, event: &mut DBEvent) -> Result<(), EncryptionError> {
+        event.decrypt()?;
+
+        let (pii_fields, data) = match ((self.pii_fields)(&event.event_type), event.data.as_mut()) {
+            (Some(pii_fields), Some(serde_json::Value::Object(data))) => (pii_fields, data),
+            (Some(&[]), Some(_)) => return Ok(()),
+            (_, None) => return Ok(()),
+            // Payloads of unknown event types, or that aren't objects, can't be partially purged
+            _ => {
+                event.data = None;
+
+                return Ok(());
+            }
+        };
+
+        for field in pii_fields {
+            match (&self.redacted, data.get_mut(*field)) {
+                (Some(redacted), Some(value)) => *value = redacted.clone(),
+                (None, Some(_)) => {
+                    data.remove(*field);
+                }
+                (_, None) => (),
+            }
+        }
+
+        Ok(())
+    }
+}
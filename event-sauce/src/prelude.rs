@@ -2,5 +2,7 @@
 
 pub use crate::{
     CreateEntityBuilder, DeleteBuilderPersist, DeleteEntityBuilder, Entity, EventBuilder,
-    EventData, PurgeBuilderExecute, PurgeEntityBuilder, StorageBuilderPersist, UpdateEntityBuilder,
+    EventData, PurgeBuilderExecute, PurgeBuilderReport, PurgeEntityBuilder, StorageBackendExecute,
+    StorageBackendLoad, StorageBackendLoadSnapshot, StorageBackendUpdateChecked,
+    StorageBuilderPersist, UpdateEntityBuilder,
 };
@@ -0,0 +1,149 @@
+//! Reports of what a purge removes, for compliance records
+
+use crate::{db_event::DBEvent, Entity, EventData, PurgeBuilder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Where an entity's aggregate state is stored, so it can be removed when the entity is purged
+///
+/// This is implemented by the `event_sauce_derive::Entity` derive with the defaults
+/// below: the aggregate row is stored in the table named after the entity's
+/// [`ENTITY_TYPE`](Entity::ENTITY_TYPE), keyed by an `id` column, and no other tables depend on
+/// it. Entities stored differently can opt out with `#[event_sauce(custom_purge)]` and implement
+/// this trait by hand:
+///
+/// ```rust,ignore
+/// use event_sauce::Purgeable;
+///
+/// #[derive(event_sauce_derive::Entity)]
+/// #[event_sauce(entity_name = "users", custom_purge)]
+/// struct User {
+///     #[event_sauce(id)]
+///     user_id: Uuid,
+/// }
+///
+/// impl Purgeable for User {
+///     const AGGREGATE_TABLE: &'static str = "accounts.users";
+///
+///     const AGGREGATE_ID_COLUMN: &'static str = "user_id";
+///
+///     const DEPENDENT_TABLES: &'static [(&'static str, &'static str)] =
+///         &[("accounts.user_addresses", "user_id")];
+/// }
+/// ```
+///
+/// Storage backends quote these identifiers, so they're case sensitive. Schema-qualified names
+/// are separated by a `.`. Anything more involved can be removed in the entity's
+/// [`OnPurged`](crate::OnPurged) trigger.
+pub trait Purgeable: Entity {
+    /// The table the entity's aggregate row is stored in
+    const AGGREGATE_TABLE: &'static str = Self::ENTITY_TYPE;
+
+    /// The column of [`Purgeable::AGGREGATE_TABLE`] holding the entity's ID
+    const AGGREGATE_ID_COLUMN: &'static str = "id";
+
+    /// Tables with rows that depend on the aggregate, along with the column holding the entity's
+    /// ID
+    ///
+    /// These rows are removed before the aggregate row, in the order they're listed.
+    const DEPENDENT_TABLES: &'static [(&'static str, &'static str)] = &[];
+}
+
+/// An entity linked to a purged entity through a shared correlation ID, or by being caused by one
+/// of its events
+///
+/// Related entities aren't purged along with the entity. They are reported so they can be checked
+/// for data about the purged entity.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RelatedEntity {
+    /// The entity's type
+    pub entity_type: String,
+    /// The entity's ID
+    pub entity_id: Uuid,
+}
+
+/// What purging an entity removes from the store
+///
+/// This is returned by [`PurgeBuilderReport::dry_run`](crate::PurgeBuilderReport::dry_run)
+/// before a purge is run, and included in the [`PurgeReceipt`] once it has been.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PurgeReport {
+    /// The purged entity's type
+    pub entity_type: String,
+    /// The purged entity's ID
+    pub entity_id: Uuid,
+    /// The number of events whose payloads are purged, not including the purge event itself
+    pub event_count: u64,
+    /// The distinct types of the purged events, in alphabetical order
+    pub event_types: Vec<String>,
+    /// The creation time of the oldest purged event
+    pub first_event_at: Option<DateTime<Utc>>,
+    /// The creation time of the newest purged event
+    pub last_event_at: Option<DateTime<Utc>>,
+    /// The number of aggregate rows removed
+    pub aggregate_rows: u64,
+    /// Other entities linked to the purged entity, which are left as is
+    pub related_entities: Vec<RelatedEntity>,
+}
+
+impl PurgeReport {
+    /// Create a report of the given events of an entity being purged
+    ///
+    /// The aggregate rows and related entities must be filled in by the storage backend.
+    pub fn new(entity_type: String, entity_id: Uuid, events: &[DBEvent]) -> Self {
+        let mut event_types = events
+            .iter()
+            .map(|event| event.event_type.clone())
+            .collect::<Vec<_>>();
+
+        event_types.sort();
+        event_types.dedup();
+
+        Self {
+            entity_type,
+            entity_id,
+            event_count: events.len() as u64,
+            event_types,
+            first_event_at: events.iter().map(|event| event.created_at).min(),
+            last_event_at: events.iter().map(|event| event.created_at).max(),
+            aggregate_rows: 0,
+            related_entities: Vec::new(),
+        }
+    }
+}
+
+/// Evidence that an entity was purged
+///
+/// Receipts contain no data from the purged events, so can be kept after the purge to show when,
+/// by whom and how an entity was purged.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PurgeReceipt {
+    /// The ID of the stored purge event
+    pub purge_event_id: Uuid,
+    /// The ID of the session that purged the entity
+    pub purger_id: Option<Uuid>,
+    /// The time the entity was purged at
+    pub purged_at: DateTime<Utc>,
+    /// Whether only the PII fields of the entity's events were removed
+    pub partial: bool,
+    /// What the purge removed
+    pub report: PurgeReport,
+}
+
+impl<Ent, ED> PurgeBuilder<Ent, ED>
+where
+    Ent: Entity,
+    ED: EventData,
+{
+    /// Create a receipt for this purge once it has been run
+    pub fn receipt(&self, report: PurgeReport) -> PurgeReceipt {
+        PurgeReceipt {
+            purge_event_id: self.event.id,
+            purger_id: self.event.session_id,
+            purged_at: self.event.created_at,
+            partial: self.partial.is_some(),
+            report,
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Resolve conflicts flagged on entities
+
+use crate::{ConflictData, ConflictResolvedEventBuilder, Entity, Event, EventData, StorageBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::convert::Infallible;
+use uuid::Uuid;
+
+/// The resolution of a conflict previously stored as a [`ConflictData`] event
+///
+/// Generic type parameters:
+/// * `EDC` (Event Data Conflicting) is the event data that conflicted, and is applied to the
+///   entity in place of the conflicting event if the resolution keeps it.
+///
+/// Conflicts can be resolved automatically with a [`ResolutionStrategy`] using
+/// [`ConflictResolved::try_from_strategy`], or by hand later with [`ConflictResolved::new`], for
+/// example after a user has picked which change to keep.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let resolution = ConflictResolved::try_from_strategy(&conflict_event, &LastWriterWins)?;
+///
+/// let user = user.try_resolve_conflict(resolution)?.persist(&store).await?;
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConflictResolved<EDC>
+where
+    EDC: EventData,
+{
+    /// The ID of the [`ConflictData`] event being resolved
+    pub conflict_event_id: Uuid,
+
+    /// The name of the strategy used to resolve the conflict, or `None` if it was resolved by hand
+    pub strategy: Option<String>,
+
+    /// The event data to apply to the entity, or `None` to leave the entity as the applied event
+    /// left it
+    pub resolved_event_data: Option<EDC>,
+}
+
+impl<EDC> ConflictResolved<EDC>
+where
+    EDC: EventData,
+{
+    /// Resolve a conflict by hand, applying the given event data if any
+    pub fn new(conflict_event_id: Uuid, resolved_event_data: Option<EDC>) -> Self {
+        Self {
+            conflict_event_id,
+            strategy: None,
+            resolved_event_data,
+        }
+    }
+
+    /// Resolve a conflict automatically using a strategy
+    ///
+    /// If the conflict event has been purged, there is nothing left to apply so the entity is left
+    /// as is.
+    pub fn try_from_strategy<EDA, S>(
+        conflict_event: &Event<ConflictData<EDA, EDC>>,
+        strategy: &S,
+    ) -> Result<Self, S::Error>
+    where
+        EDA: EventData,
+        S: ResolutionStrategy<EDA, EDC>,
+    {
+        let resolved_event_data = match &conflict_event.data {
+            Some(conflict) => strategy.resolve(conflict)?,
+            None => None,
+        };
+
+        Ok(Self {
+            conflict_event_id: conflict_event.id,
+            strategy: Some(strategy.name().to_string()),
+            resolved_event_data,
+        })
+    }
+}
+
+impl<EDC> EventData for ConflictResolved<EDC>
+where
+    EDC: EventData,
+{
+    type Entity = EDC::Entity;
+
+    type Builder = ConflictResolvedEventBuilder<EDC>;
+
+    fn event_type(&self) -> &'static str {
+        "ConflictResolved"
+    }
+}
+
+impl<EDC> Event<ConflictResolved<EDC>>
+where
+    EDC: EventData + Clone,
+{
+    /// Get the event to apply to the entity to resolve the conflict, if the resolution keeps any
+    /// event data
+    ///
+    /// The returned event shares this event's ID and context, so can be passed to an
+    /// [`AggregateUpdate`](crate::AggregateUpdate) implementation from
+    /// [`AggregateResolveConflict::try_aggregate_resolve_conflict`].
+    pub fn resolved_event(&self) -> Option<Event<EDC>> {
+        let data = self.data.as_ref()?.resolved_event_data.clone()?;
+
+        Some(Event {
+            id: self.id,
+            event_type: String::from(data.event_type()),
+            entity_type: self.entity_type.clone(),
+            entity_id: self.entity_id,
+            version: self.version,
+            session_id: self.session_id,
+            correlation_id: self.correlation_id,
+            causation_id: self.causation_id,
+            metadata: self.metadata.clone(),
+            purger_id: self.purger_id,
+            created_at: self.created_at,
+            purged_at: self.purged_at,
+            data: Some(data),
+        })
+    }
+}
+
+/// Add the ability to resolve a conflict flagged on an entity
+pub trait AggregateResolveConflict<EDC>: Sized
+where
+    EDC: EventData,
+{
+    /// The error type to return when the entity could not be updated
+    type Error;
+
+    /// Attempt to apply the passed event to this entity
+    ///
+    /// Implementations of this function should clear the "merge conflict" flag set by
+    /// [`AggregateConflict::try_aggregate_conflict`](crate::AggregateConflict::try_aggregate_conflict),
+    /// and apply [`Event::resolved_event`] if there is one.
+    fn try_aggregate_resolve_conflict(
+        self,
+        event: &Event<ConflictResolved<EDC>>,
+    ) -> Result<Self, Self::Error>;
+}
+
+/// A wrapper trait around [`AggregateResolveConflict`] to handle event-sauce integration
+/// boilerplate
+pub trait ResolveConflictEntityBuilder<EDC>: AggregateResolveConflict<EDC> + Entity
+where
+    EDC: EventData<Entity = Self>,
+{
+    /// Resolve a conflict on the entity with an event
+    fn try_resolve_conflict<B>(
+        self,
+        builder: B,
+    ) -> Result<StorageBuilder<Self, ConflictResolved<EDC>>, Self::Error>
+    where
+        B: Into<ConflictResolvedEventBuilder<EDC>>,
+    {
+        let event = builder.into().build(&self);
+
+        let entity = self.try_aggregate_resolve_conflict(&event)?;
+
+        Ok(StorageBuilder::new(entity, event))
+    }
+}
+
+/// A way to decide automatically which change to keep when two events conflict
+///
+/// Implement this trait to add custom strategies. See [`LastWriterWins`], [`KeepApplied`] and
+/// [`MergeFields`] for the built in strategies.
+pub trait ResolutionStrategy<EDA, EDC>
+where
+    EDA: EventData,
+    EDC: EventData,
+{
+    /// The error type to return when the conflict could not be resolved
+    type Error;
+
+    /// The name of this strategy, stored in [`ConflictResolved::strategy`]
+    fn name(&self) -> &'static str;
+
+    /// Decide which event data to apply to the entity, if any
+    ///
+    /// Returning `None` leaves the entity as the applied event left it.
+    fn resolve(&self, conflict: &ConflictData<EDA, EDC>) -> Result<Option<EDC>, Self::Error>;
+}
+
+/// Apply the conflicting event data, overwriting the changes made by the applied event
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LastWriterWins;
+
+impl<EDA, EDC> ResolutionStrategy<EDA, EDC> for LastWriterWins
+where
+    EDA: EventData,
+    EDC: EventData + Clone,
+{
+    type Error = Infallible;
+
+    fn name(&self) -> &'static str {
+        "LastWriterWins"
+    }
+
+    fn resolve(&self, conflict: &ConflictData<EDA, EDC>) -> Result<Option<EDC>, Self::Error> {
+        Ok(Some(conflict.conflicting_event_data.clone()))
+    }
+}
+
+/// Discard the conflicting event data, keeping the changes made by the applied event
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeepApplied;
+
+impl<EDA, EDC> ResolutionStrategy<EDA, EDC> for KeepApplied
+where
+    EDA: EventData,
+    EDC: EventData,
+{
+    type Error = Infallible;
+
+    fn name(&self) -> &'static str {
+        "KeepApplied"
+    }
+
+    fn resolve(&self, _conflict: &ConflictData<EDA, EDC>) -> Result<Option<EDC>, Self::Error> {
+        Ok(None)
+    }
+}
+
+/// Merge the JSON payloads of both events field by field
+///
+/// Fields of the conflicting event data that the applied event didn't touch are kept. Fields
+/// present in both payloads take the applied event's value, so the merged event data never undoes
+/// the applied change. Payloads that don't serialize to JSON objects can't be merged, so are
+/// resolved as with [`KeepApplied`].
+///
+/// Enum payloads of the applied event are expected to be adjacently tagged with
+/// `#[serde(tag = "event_type", content = "data")]`, as is required to store them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MergeFields;
+
+impl<EDA, EDC> ResolutionStrategy<EDA, EDC> for MergeFields
+where
+    EDA: EventData,
+    EDC: EventData + DeserializeOwned,
+{
+    type Error = serde_json::Error;
+
+    fn name(&self) -> &'static str {
+        "MergeFields"
+    }
+
+    fn resolve(&self, conflict: &ConflictData<EDA, EDC>) -> Result<Option<EDC>, Self::Error> {
+        let applied = match &conflict.applied_event.data {
+            Some(data) => serde_json::to_value(data)?,
+            None => serde_json::Value::Null,
+        };
+
+        // Unwrap adjacently tagged enum payloads to get at the event's fields
+        let applied = match applied {
+            serde_json::Value::Object(mut applied)
+                if applied.get("event_type").and_then(|tag| tag.as_str())
+                    == Some(conflict.applied_event.event_type.as_str()) =>
+            {
+                applied.remove("data").unwrap_or(serde_json::Value::Null)
+            }
+            applied => applied,
+        };
+
+        let applied = match applied {
+            serde_json::Value::Object(applied) => applied,
+            _ => return Ok(None),
+        };
+
+        let mut merged = match serde_json::to_value(&conflict.conflicting_event_data)? {
+            serde_json::Value::Object(merged) => merged,
+            _ => return Ok(None),
+        };
+
+        for (field, value) in applied {
+            if let Some(merged_value) = merged.get_mut(&field) {
+                *merged_value = value;
+            }
+        }
+
+        serde_json::from_value(serde_json::Value::Object(merged)).map(Some)
+    }
+}
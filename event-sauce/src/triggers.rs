@@ -1,23 +1,39 @@
-//! Traits attached to events to allow side effects when creating/updating entities
+//! Traits attached to entities to allow side effects when events are persisted
+//!
+//! Triggers are run by storage backends in the same transaction as the event that caused them, so
+//! any changes they make are rolled back if the transaction fails. Likewise, returning an error
+//! from a trigger aborts the transaction.
+//!
+//! Triggers are only run for event data whose builder is in the [`Triggered`] mode. The event data
+//! derives (`CreateEventData`, `UpdateEventData`, etc) use that mode, and implement the relevant
+//! triggers as noops for their entity. To implement all triggers for an event by hand, add
+//! `custom_triggers` to the event's attribute like `#[event_sauce(User, custom_triggers)]`. To
+//! only implement some of them, list their traits like
+//! `#[event_sauce(User, custom_triggers(OnUpdated, OnConflicted))]`, and the others are still
+//! implemented as noops. Event data implemented by hand defaults to the [`Untriggered`] mode, so
+//! its entity doesn't need to implement any trigger traits.
 
-use crate::EventData;
-use std::fmt::Debug;
+use crate::{
+    ActionEventBuilder, ConflictData, ConflictEventBuilder, ConflictResolved,
+    ConflictResolvedEventBuilder, CreateEventBuilder, DeleteEventBuilder, EnumEventData, Event,
+    EventData, PurgeEventBuilder, StorageBackendTransaction, UpdateEventBuilder,
+};
+use std::{future::Future, pin::Pin};
 
 /// Perform actions on an entity when it is created
 ///
 /// This trait can be implemented to allow side effects like emitting of events or calling HTTP APIs
 /// to be performed when an entity is created.
-pub trait OnCreated<ED>
+#[async_trait::async_trait]
+pub trait OnCreated<ED, Txn>: Sync
 where
-    ED: EventData,
+    ED: EventData + Sync,
+    Txn: StorageBackendTransaction + Send,
 {
-    /// The error type to return if the trigger failed
-    type E: Debug;
-
-    /// On create trigger
+    /// On create trigger, called after the entity has been persisted
     ///
     /// Defaults to a noop
-    fn on_created(&self) -> Result<(), Self::E> {
+    async fn on_created(&self, _event: &Event<ED>, _tx: &mut Txn) -> Result<(), Txn::Error> {
         Ok(())
     }
 }
@@ -26,17 +42,282 @@ where
 ///
 /// This trait can be implemented to allow side effects like emitting of events or calling HTTP APIs
 /// to be performed when an entity is updated.
-pub trait OnUpdated<ED>
+#[async_trait::async_trait]
+pub trait OnUpdated<ED, Txn>: Sync
 where
-    ED: EventData,
+    ED: EventData + Sync,
+    Txn: StorageBackendTransaction + Send,
+{
+    /// On update trigger, called after the entity has been persisted
+    ///
+    /// Defaults to a noop
+    async fn on_updated(&self, _event: &Event<ED>, _tx: &mut Txn) -> Result<(), Txn::Error> {
+        Ok(())
+    }
+}
+
+/// Perform actions on an entity when it is deleted
+#[async_trait::async_trait]
+pub trait OnDeleted<ED, Txn>: Sync
+where
+    ED: EventData + Sync,
+    Txn: StorageBackendTransaction + Send,
 {
-    /// The error type to return if the trigger failed
-    type E: Debug;
+    /// On delete trigger, called before the entity is removed from the backing store
+    ///
+    /// Defaults to a noop
+    async fn on_deleted(&self, _event: &Event<ED>, _tx: &mut Txn) -> Result<(), Txn::Error> {
+        Ok(())
+    }
+}
 
-    /// On update trigger
+/// Perform actions on an entity when it is purged
+#[async_trait::async_trait]
+pub trait OnPurged<ED, Txn>: Sync
+where
+    ED: EventData + Sync,
+    Txn: StorageBackendTransaction + Send,
+{
+    /// On purge trigger, called after the entity and its event data have been purged
     ///
     /// Defaults to a noop
-    fn on_updated(&self) -> Result<(), Self::E> {
+    async fn on_purged(&self, _event: &Event<ED>, _tx: &mut Txn) -> Result<(), Txn::Error> {
         Ok(())
     }
 }
+
+/// Perform actions on an entity when an event conflicting with an applied event of type `EDA` is
+/// persisted
+///
+/// See [`ConflictCheck`](crate::ConflictCheck).
+#[async_trait::async_trait]
+pub trait OnConflicted<EDA, EDC, Txn>: Sync
+where
+    EDA: EventData + Sync,
+    EDC: EventData + Sync,
+    Txn: StorageBackendTransaction + Send,
+{
+    /// On conflict trigger, called after the entity has been persisted
+    ///
+    /// Defaults to a noop
+    async fn on_conflicted(
+        &self,
+        _event: &Event<ConflictData<EDA, EDC>>,
+        _tx: &mut Txn,
+    ) -> Result<(), Txn::Error> {
+        Ok(())
+    }
+}
+
+/// Perform actions on an entity when a conflict on it is resolved
+///
+/// See [`ConflictResolved`].
+#[async_trait::async_trait]
+pub trait OnConflictResolved<EDC, Txn>: Sync
+where
+    EDC: EventData + Sync,
+    Txn: StorageBackendTransaction + Send,
+{
+    /// On conflict resolution trigger, called after the entity has been persisted
+    ///
+    /// Defaults to a noop
+    async fn on_conflict_resolved(
+        &self,
+        _event: &Event<ConflictResolved<EDC>>,
+        _tx: &mut Txn,
+    ) -> Result<(), Txn::Error> {
+        Ok(())
+    }
+}
+
+/// Trigger mode of event builders that don't run any triggers
+///
+/// This is the default mode of [`CreateEventBuilder`], [`UpdateEventBuilder`],
+/// [`DeleteEventBuilder`] and [`PurgeEventBuilder`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Untriggered;
+
+/// Trigger mode of event builders that run their entity's triggers
+///
+/// Event data opts into triggers by using a builder in this mode, like
+/// `type Builder = UpdateEventBuilder<Self, Triggered>`, which the event data derives do. The
+/// entity must then implement the trigger traits matching the builder's kind of event, for
+/// example [`OnUpdated`], as well as [`OnConflicted`] and [`OnConflictResolved`] for conflicts
+/// with the event data.
+///
+/// Builders in this mode can be converted into builders in the default [`Untriggered`] mode,
+/// which is the mode taken by the entity builder methods. Which triggers are run depends only on
+/// the [`EventData::Builder`] of the event data, not on the builder passed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Triggered;
+
+/// Run the trigger matching the kind of an event
+///
+/// This is implemented for each event builder, and is used by storage backends to call the
+/// correct trigger for an [`EventData::Builder`]. Builders in the [`Untriggered`] mode don't run
+/// any triggers. Enum events are not associated with a single kind of change, so don't run any
+/// triggers here. See [`RunEnumTrigger`] to run the trigger of an enum event's variant.
+///
+/// Conflicts are run by the builder of the conflicting event data, so the triggers for
+/// [`ConflictData`] and [`ConflictResolved`] events are opted into by the same event data whose
+/// changes conflicted.
+#[async_trait::async_trait]
+pub trait RunTrigger<E, ED, Txn>
+where
+    ED: EventData,
+    Txn: StorageBackendTransaction,
+{
+    /// Run the trigger for `entity`
+    async fn run_trigger(entity: &E, event: &Event<ED>, tx: &mut Txn) -> Result<(), Txn::Error>;
+}
+
+/// Run the trigger matching the variant of an enum event
+///
+/// This is implemented by the `EnumEventData` derive, and is used by storage backends to run
+/// the trigger of each variant's own [`EventData::Builder`] for events emitted by a
+/// [`Command`](crate::Command).
+pub trait RunEnumTrigger<E, Txn>: EnumEventData
+where
+    Txn: StorageBackendTransaction,
+{
+    /// Run the trigger for `entity` matching the variant of `event`
+    ///
+    /// Events without data, for example purged events, don't run any triggers.
+    fn run_enum_trigger<'a>(
+        entity: &'a E,
+        event: Event<Self>,
+        tx: &'a mut Txn,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Txn::Error>> + Send + 'a>>;
+}
+
+/// Implement [`RunTrigger`] for an event builder in both trigger modes
+///
+/// `Untriggered` builders run nothing for any event. `Triggered` builders run `$trigger` for their
+/// own events, as well as the conflict triggers for conflicts with their event data.
+macro_rules! impl_run_trigger {
+    ($builder:ident, $trigger:ident, $method:ident) => {
+        #[async_trait::async_trait]
+        impl<E, ED, EDX, Txn> RunTrigger<E, EDX, Txn> for $builder<ED, Untriggered>
+        where
+            E: Sync,
+            ED: EventData,
+            EDX: EventData + Sync,
+            Txn: StorageBackendTransaction + Send,
+        {
+            async fn run_trigger(
+                _entity: &E,
+                _event: &Event<EDX>,
+                _tx: &mut Txn,
+            ) -> Result<(), Txn::Error> {
+                Ok(())
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl<E, ED, Txn> RunTrigger<E, ED, Txn> for $builder<ED, Triggered>
+        where
+            E: $trigger<ED, Txn>,
+            ED: EventData + Sync,
+            Txn: StorageBackendTransaction + Send,
+        {
+            async fn run_trigger(
+                entity: &E,
+                event: &Event<ED>,
+                tx: &mut Txn,
+            ) -> Result<(), Txn::Error> {
+                entity.$method(event, tx).await
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl<E, EDA, EDC, Txn> RunTrigger<E, ConflictData<EDA, EDC>, Txn>
+            for $builder<EDC, Triggered>
+        where
+            E: OnConflicted<EDA, EDC, Txn>,
+            EDA: EventData + Sync,
+            EDC: EventData + Sync,
+            Txn: StorageBackendTransaction + Send,
+        {
+            async fn run_trigger(
+                entity: &E,
+                event: &Event<ConflictData<EDA, EDC>>,
+                tx: &mut Txn,
+            ) -> Result<(), Txn::Error> {
+                entity.on_conflicted(event, tx).await
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl<E, EDC, Txn> RunTrigger<E, ConflictResolved<EDC>, Txn> for $builder<EDC, Triggered>
+        where
+            E: OnConflictResolved<EDC, Txn>,
+            EDC: EventData + Sync,
+            Txn: StorageBackendTransaction + Send,
+        {
+            async fn run_trigger(
+                entity: &E,
+                event: &Event<ConflictResolved<EDC>>,
+                tx: &mut Txn,
+            ) -> Result<(), Txn::Error> {
+                entity.on_conflict_resolved(event, tx).await
+            }
+        }
+    };
+}
+
+impl_run_trigger!(CreateEventBuilder, OnCreated, on_created);
+impl_run_trigger!(UpdateEventBuilder, OnUpdated, on_updated);
+impl_run_trigger!(DeleteEventBuilder, OnDeleted, on_deleted);
+impl_run_trigger!(PurgeEventBuilder, OnPurged, on_purged);
+
+#[async_trait::async_trait]
+impl<E, EDA, EDC, Txn> RunTrigger<E, ConflictData<EDA, EDC>, Txn> for ConflictEventBuilder<EDA, EDC>
+where
+    E: Sync,
+    EDA: EventData + Sync,
+    EDC: EventData + Sync,
+    EDC::Builder: RunTrigger<E, ConflictData<EDA, EDC>, Txn>,
+    Txn: StorageBackendTransaction + Send,
+{
+    async fn run_trigger(
+        entity: &E,
+        event: &Event<ConflictData<EDA, EDC>>,
+        tx: &mut Txn,
+    ) -> Result<(), Txn::Error> {
+        EDC::Builder::run_trigger(entity, event, tx).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, EDENUM, EDX, Txn> RunTrigger<E, EDX, Txn> for ActionEventBuilder<EDENUM>
+where
+    E: Sync,
+    EDENUM: EnumEventData,
+    EDX: EventData + Sync,
+    Txn: StorageBackendTransaction + Send,
+{
+    async fn run_trigger(
+        _entity: &E,
+        _event: &Event<EDX>,
+        _tx: &mut Txn,
+    ) -> Result<(), Txn::Error> {
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, EDC, Txn> RunTrigger<E, ConflictResolved<EDC>, Txn> for ConflictResolvedEventBuilder<EDC>
+where
+    E: Sync,
+    EDC: EventData + Sync,
+    EDC::Builder: RunTrigger<E, ConflictResolved<EDC>, Txn>,
+    Txn: StorageBackendTransaction + Send,
+{
+    async fn run_trigger(
+        entity: &E,
+        event: &Event<ConflictResolved<EDC>>,
+        tx: &mut Txn,
+    ) -> Result<(), Txn::Error> {
+        EDC::Builder::run_trigger(entity, event, tx).await
+    }
+}
@@ -0,0 +1,79 @@
+//! Upgrade stored event payloads to the latest schema version
+
+use crate::db_event::DBEvent;
+use std::{collections::HashMap, fmt, sync::Arc};
+
+type Upcaster = Arc<dyn Fn(serde_json::Value) -> serde_json::Value + Send + Sync>;
+
+/// A registry of functions that convert stored event payloads from older schema versions
+///
+/// Each upcaster converts the payload of a given event type from one schema version to the next.
+/// When an event is read, upcasters are applied in order until no upcaster exists for the event's
+/// schema version, at which point the payload is deserialized as normal.
+///
+/// # Examples
+///
+/// ```rust
+/// use event_sauce::Upcasters;
+///
+/// // Version 2 of `UserCreated` renamed `name` to `full_name`
+/// let upcasters = Upcasters::new().register("UserCreated", 1, |mut data| {
+///     if let Some(name) = data.as_object_mut().and_then(|data| data.remove("name")) {
+///         data["full_name"] = name;
+///     }
+///
+///     data
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub struct Upcasters {
+    upcasters: HashMap<(String, i32), Upcaster>,
+}
+
+impl Upcasters {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an upcaster that converts payloads of `event_type` from `from_version` to
+    /// `from_version + 1`
+    ///
+    /// Registering a second upcaster for the same event type and version replaces the first.
+    pub fn register<F>(mut self, event_type: &str, from_version: i32, upcaster: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> serde_json::Value + Send + Sync + 'static,
+    {
+        self.upcasters
+            .insert((event_type.to_string(), from_version), Arc::new(upcaster));
+
+        self
+    }
+
+    /// Upgrade an event's payload to the latest registered schema version
+    ///
+    /// Purged events have no payload, but their schema version is still updated. Encrypted
+    /// payloads are decrypted first. If that fails, the event is returned as is so the error is
+    /// surfaced when it's deserialized.
+    pub fn upcast(&self, mut event: DBEvent) -> DBEvent {
+        if event.decrypt().is_err() {
+            return event;
+        }
+
+        while let Some(upcaster) = self
+            .upcasters
+            .get(&(event.event_type.clone(), event.schema_version))
+        {
+            event.data = event.data.map(|data| upcaster(data));
+            event.schema_version += 1;
+        }
+
+        event
+    }
+}
+
+impl fmt::Debug for Upcasters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.upcasters.keys()).finish()
+    }
+}
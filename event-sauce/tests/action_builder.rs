@@ -294,3 +294,66 @@ fn apply_enum_event_delete() -> Result<(), EventError> {
 
     Ok(())
 }
+
+#[test]
+fn replay_events() -> Result<(), EventError> {
+    let created = User::try_action(
+        UserEventData::UserCreated(UserCreated {
+            name: String::from("Henry Jekyll"),
+        })
+        .into_builder(),
+        None,
+    )?;
+
+    let updated = User::try_action(
+        UserEventData::UserUpdated(UserUpdated {
+            name: String::from("Edward Hyde"),
+        })
+        .into_builder(),
+        Some(created.entity.clone()),
+    )?;
+
+    let replayed = User::try_replay(vec![created.event, updated.event])?;
+
+    assert_eq!(replayed, Some(updated.entity));
+
+    Ok(())
+}
+
+#[test]
+fn replay_no_events() -> Result<(), EventError> {
+    let replayed = User::try_replay(Vec::new())?;
+
+    assert_eq!(replayed, None);
+
+    Ok(())
+}
+
+#[test]
+fn replay_from_entity() -> Result<(), EventError> {
+    let created = User::try_action(
+        UserEventData::UserCreated(UserCreated {
+            name: String::from("Henry Jekyll"),
+        })
+        .into_builder(),
+        None,
+    )?;
+
+    let updated = User::try_action(
+        UserEventData::UserUpdated(UserUpdated {
+            name: String::from("Edward Hyde"),
+        })
+        .into_builder(),
+        Some(created.entity.clone()),
+    )?;
+
+    let replayed = User::try_replay_from(Some(created.entity.clone()), vec![updated.event])?;
+
+    assert_eq!(replayed, Some(updated.entity));
+
+    let unchanged = User::try_replay_from(Some(created.entity.clone()), Vec::new())?;
+
+    assert_eq!(unchanged, Some(created.entity));
+
+    Ok(())
+}
@@ -0,0 +1,355 @@
+use event_sauce::{
+    AggregateCreate, AggregateResolveConflict, AggregateUpdate, ConflictData, ConflictEventBuilder,
+    ConflictResolved, CreateEntityBuilder, Event, EventBuilder, EventData, KeepApplied,
+    LastWriterWins, MergeFields, ResolutionStrategy, ResolveConflictEntityBuilder,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "users")]
+struct User {
+    #[event_sauce(id)]
+    id: Uuid,
+    name: String,
+    email: String,
+    conflicted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::CreateEventData)]
+#[event_sauce(User)]
+struct UserCreated {
+    name: String,
+    email: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User)]
+struct UserNameChanged {
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User)]
+struct UserProfileChanged {
+    name: String,
+    email: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User)]
+struct UserVerified;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::EnumEventData)]
+#[serde(tag = "event_type", content = "data")]
+#[event_sauce(User)]
+enum UserEventData {
+    UserNameChanged(UserNameChanged),
+    UserProfileChanged(UserProfileChanged),
+}
+
+impl event_sauce::AggregateAction<UserEventData> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_action(
+        entity: Option<Self>,
+        _event: &Event<UserEventData>,
+    ) -> Result<Self, Self::Error> {
+        entity.ok_or("User must exist")
+    }
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to create User from UserCreated event")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.name.clone(),
+            email: data.email.clone(),
+            conflicted: false,
+        })
+    }
+}
+
+impl AggregateUpdate<UserNameChanged> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<UserNameChanged>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to update User from UserNameChanged event")?;
+
+        Ok(User {
+            name: data.name.clone(),
+            ..self
+        })
+    }
+}
+
+impl AggregateUpdate<UserProfileChanged> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<UserProfileChanged>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to update User from UserProfileChanged event")?;
+
+        Ok(User {
+            name: data.name.clone(),
+            email: data.email.clone(),
+            ..self
+        })
+    }
+}
+
+impl AggregateUpdate<UserVerified> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, _event: &Event<UserVerified>) -> Result<Self, Self::Error> {
+        Ok(self)
+    }
+}
+
+impl AggregateResolveConflict<UserProfileChanged> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_resolve_conflict(
+        self,
+        event: &Event<ConflictResolved<UserProfileChanged>>,
+    ) -> Result<Self, Self::Error> {
+        let user = User {
+            conflicted: false,
+            ..self
+        };
+
+        match event.resolved_event() {
+            Some(event) => user.try_aggregate_update(&event),
+            None => Ok(user),
+        }
+    }
+}
+
+impl ResolveConflictEntityBuilder<UserProfileChanged> for User {}
+
+/// A user whose name was changed, and a conflicting profile change based on the original user
+fn conflicted_user() -> (User, Event<ConflictData<UserEventData, UserProfileChanged>>) {
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .entity;
+
+    let applied_event = UserEventData::UserNameChanged(UserNameChanged {
+        name: "Beans Bobby".to_string(),
+    })
+    .into_builder()
+    .build(&Some(user.clone()));
+
+    let conflict_event = ConflictEventBuilder::new(ConflictData {
+        applied_event,
+        conflicting_event_data: UserProfileChanged {
+            name: "Bob Beans".to_string(),
+            email: "bob@bea.ns".to_string(),
+        },
+    })
+    .build(&user);
+
+    let user = User {
+        name: "Beans Bobby".to_string(),
+        conflicted: true,
+        ..user
+    };
+
+    (user, conflict_event)
+}
+
+#[test]
+fn last_writer_wins() -> Result<(), &'static str> {
+    let (user, conflict_event) = conflicted_user();
+
+    let resolution = ConflictResolved::try_from_strategy(&conflict_event, &LastWriterWins)
+        .expect("Last writer wins never fails");
+
+    assert_eq!(resolution.conflict_event_id, conflict_event.id);
+    assert_eq!(resolution.strategy.as_deref(), Some("LastWriterWins"));
+
+    let builder = user.clone().try_resolve_conflict(resolution)?;
+
+    assert_eq!(builder.event.event_type, "ConflictResolved");
+    assert_eq!(builder.event.entity_id, user.id);
+    assert_eq!(
+        builder.entity,
+        User {
+            id: user.id,
+            name: "Bob Beans".to_string(),
+            email: "bob@bea.ns".to_string(),
+            conflicted: false,
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn keep_applied() -> Result<(), &'static str> {
+    let (user, conflict_event) = conflicted_user();
+
+    let resolution = ConflictResolved::try_from_strategy(&conflict_event, &KeepApplied)
+        .expect("Keep applied never fails");
+
+    assert_eq!(resolution.resolved_event_data, None);
+
+    let builder = user.clone().try_resolve_conflict(resolution)?;
+
+    assert_eq!(
+        builder.entity,
+        User {
+            conflicted: false,
+            ..user
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn merge_fields() -> Result<(), &'static str> {
+    let (user, conflict_event) = conflicted_user();
+
+    let resolution = ConflictResolved::try_from_strategy(&conflict_event, &MergeFields)
+        .expect("Failed to merge payloads");
+
+    // The name was changed by both events so keeps the applied value, but the email was only
+    // changed by the conflicting event
+    assert_eq!(
+        resolution.resolved_event_data,
+        Some(UserProfileChanged {
+            name: "Beans Bobby".to_string(),
+            email: "bob@bea.ns".to_string(),
+        })
+    );
+
+    let builder = user.clone().try_resolve_conflict(resolution)?;
+
+    assert_eq!(builder.entity.name, "Beans Bobby");
+    assert_eq!(builder.entity.email, "bob@bea.ns");
+    assert!(!builder.entity.conflicted);
+
+    Ok(())
+}
+
+#[test]
+fn merge_fields_unit_struct() {
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .entity;
+
+    let conflict_event = ConflictEventBuilder::new(ConflictData {
+        applied_event: UserVerified.into_builder().build(&user),
+        conflicting_event_data: UserProfileChanged {
+            name: "Bob Beans".to_string(),
+            email: "bob@bea.ns".to_string(),
+        },
+    })
+    .build(&user);
+
+    let merged = ConflictResolved::try_from_strategy(&conflict_event, &MergeFields)
+        .expect("Failed to merge payloads");
+    let kept = ConflictResolved::try_from_strategy(&conflict_event, &KeepApplied)
+        .expect("Keep applied never fails");
+
+    // The applied event has no fields to merge, so it's resolved as with `KeepApplied`
+    assert_eq!(merged.resolved_event_data, None);
+    assert_eq!(merged.resolved_event_data, kept.resolved_event_data);
+}
+
+#[test]
+fn resolved_by_hand() -> Result<(), &'static str> {
+    let (user, conflict_event) = conflicted_user();
+
+    let session_id = Uuid::new_v4();
+
+    let builder = user.clone().try_resolve_conflict(
+        ConflictResolved::new(
+            conflict_event.id,
+            Some(UserProfileChanged {
+                name: "Robert Beans".to_string(),
+                email: "bobby@bea.ns".to_string(),
+            }),
+        )
+        .into_builder()
+        .session_id(session_id),
+    )?;
+
+    assert_eq!(builder.event.session_id, Some(session_id));
+    assert_eq!(
+        builder
+            .event
+            .data
+            .as_ref()
+            .and_then(|data| data.strategy.clone()),
+        None
+    );
+    assert_eq!(builder.entity.name, "Robert Beans");
+    assert!(!builder.entity.conflicted);
+
+    Ok(())
+}
+
+/// Prefer whichever name is longer
+struct LongestName;
+
+impl ResolutionStrategy<UserEventData, UserProfileChanged> for LongestName {
+    type Error = &'static str;
+
+    fn name(&self) -> &'static str {
+        "LongestName"
+    }
+
+    fn resolve(
+        &self,
+        conflict: &ConflictData<UserEventData, UserProfileChanged>,
+    ) -> Result<Option<UserProfileChanged>, Self::Error> {
+        match &conflict.applied_event.data {
+            Some(UserEventData::UserNameChanged(applied))
+                if applied.name.len() >= conflict.conflicting_event_data.name.len() =>
+            {
+                Ok(None)
+            }
+            Some(_) => Ok(Some(conflict.conflicting_event_data.clone())),
+            None => Err("Applied event has been purged"),
+        }
+    }
+}
+
+#[test]
+fn custom_strategy() {
+    let (_user, conflict_event) = conflicted_user();
+
+    let resolution = ConflictResolved::try_from_strategy(&conflict_event, &LongestName);
+
+    assert!(matches!(
+        resolution,
+        Ok(ConflictResolved {
+            strategy: Some(ref strategy),
+            resolved_event_data: None,
+            ..
+        }) if strategy == "LongestName"
+    ));
+}
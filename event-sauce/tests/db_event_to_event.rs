@@ -2,7 +2,7 @@ use chrono::Utc;
 use core::convert::TryFrom;
 use event_sauce::{
     ActionEventBuilder, AggregateCreate, AggregateDelete, AggregateUpdate, DBEvent, EnumEventData,
-    Event, EventData,
+    Event, EventBuilder, EventData,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -124,6 +124,9 @@ pub enum EventError {
     /// Error converting event data from JSON to object.
     #[error("Conversion error: {0}")]
     ConversionError(#[from] serde_json::Error),
+    /// Error decoding a stored event.
+    #[error("Decode error: {0}")]
+    DecodeError(#[from] event_sauce::DecodeError),
 }
 
 #[test]
@@ -135,10 +138,17 @@ fn into_event() -> Result<(), EventError> {
     let db_event = DBEvent {
         id: Uuid::new_v4(),
         sequence_number: Some(42),
+        schema_version: 1,
+        encrypted: false,
+        encryption_key: None,
         event_type: String::from(event_data.event_type()),
         entity_type: String::from("User"),
         entity_id: Uuid::new_v4(),
+        version: Some(1),
         session_id: Some(Uuid::new_v4()),
+        correlation_id: None,
+        causation_id: None,
+        metadata: serde_json::json!({}),
         created_at: Utc::now(),
         purger_id: None,
         purged_at: None,
@@ -161,10 +171,17 @@ fn into_enum_event() -> Result<(), EventError> {
     let db_event = DBEvent {
         id: Uuid::new_v4(),
         sequence_number: Some(42),
+        schema_version: 1,
+        encrypted: false,
+        encryption_key: None,
         event_type: String::from(event_data.event_type()),
         entity_type: String::from("User"),
         entity_id: Uuid::new_v4(),
+        version: Some(1),
         session_id: Some(Uuid::new_v4()),
+        correlation_id: None,
+        causation_id: None,
+        metadata: serde_json::json!({}),
         created_at: Utc::now(),
         purger_id: None,
         purged_at: None,
@@ -192,3 +209,42 @@ fn into_enum_event() -> Result<(), EventError> {
 
     Ok(())
 }
+
+#[test]
+fn tracing_round_trip() -> Result<(), EventError> {
+    let user = User {
+        id: Uuid::new_v4(),
+        name: String::from("Fred"),
+    };
+
+    let cause = UserUpdated {
+        name: String::from("Fred"),
+    }
+    .into_builder()
+    .build(&user);
+
+    let event = UserUpdated {
+        name: String::from("Freddy"),
+    }
+    .into_builder()
+    .caused_by(&cause)
+    .metadata("request_id", serde_json::json!("abc123"))
+    .build(&user);
+
+    // The first event in a chain is its own correlation ID
+    assert_eq!(event.correlation_id, Some(cause.id));
+    assert_eq!(event.causation_id, Some(cause.id));
+
+    let db_event = DBEvent::try_from(event.clone())?;
+
+    assert_eq!(
+        db_event.metadata,
+        serde_json::json!({ "request_id": "abc123" })
+    );
+
+    let round_tripped = Event::<UserUpdated>::try_from(db_event)?;
+
+    assert_eq!(round_tripped, event);
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use core::convert::TryFrom;
+use event_sauce::{
+    prelude::*, AggregateCreate, DBEvent, DecodeError, EncryptionError, EncryptionKey, Event,
+    Upcasters,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "users")]
+pub struct User {
+    #[event_sauce(id)]
+    pub id: Uuid,
+
+    pub name: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+    event_sauce_derive::CreateEventData,
+)]
+#[event_sauce(User)]
+pub struct UserCreated {
+    pub name: String,
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event.data.as_ref().ok_or("Event data must be populated")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.name.clone(),
+        })
+    }
+}
+
+fn created_event() -> DBEvent {
+    let event = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .event;
+
+    DBEvent::try_from(event).expect("Failed to serialize event")
+}
+
+/// An encrypted event, as it would be read from storage along with its entity's key
+fn encrypted_event() -> (DBEvent, EncryptionKey) {
+    let key = EncryptionKey::generate();
+
+    let mut db_event = created_event();
+
+    db_event.encrypt(&key).expect("Failed to encrypt event");
+
+    (db_event, key)
+}
+
+#[test]
+fn encrypt_payload() {
+    let (db_event, _key) = encrypted_event();
+
+    assert!(db_event.is_encrypted());
+    assert!(!db_event
+        .data
+        .as_ref()
+        .map(|data| data.to_string())
+        .unwrap_or_default()
+        .contains("Bobby Beans"));
+}
+
+#[test]
+fn decrypt_in_try_from() {
+    let (mut db_event, key) = encrypted_event();
+
+    db_event.encryption_key = Some(key);
+
+    let event = Event::<UserCreated>::try_from(db_event).expect("Failed to decrypt event");
+
+    assert_eq!(
+        event.data,
+        Some(UserCreated {
+            name: "Bobby Beans".to_string()
+        })
+    );
+}
+
+#[test]
+fn destroyed_key() {
+    let (mut db_event, _key) = encrypted_event();
+
+    db_event.purged_at = Some(chrono::Utc::now());
+
+    let event = Event::<UserCreated>::try_from(db_event).expect("Failed to read shredded event");
+
+    assert_eq!(event.data, None);
+}
+
+#[test]
+fn missing_key() {
+    let (db_event, _key) = encrypted_event();
+
+    assert!(matches!(
+        Event::<UserCreated>::try_from(db_event),
+        Err(DecodeError::Encryption(EncryptionError::MissingKey))
+    ));
+}
+
+#[test]
+fn plaintext_lookalike() {
+    let mut db_event = created_event();
+
+    // Plaintext payloads are never decrypted, whatever they contain
+    db_event.data = Some(serde_json::json!({ "$encrypted": "not really" }));
+
+    db_event.decrypt().expect("Failed to read plaintext event");
+
+    assert_eq!(
+        db_event.data,
+        Some(serde_json::json!({ "$encrypted": "not really" }))
+    );
+}
+
+#[test]
+fn wrong_key() {
+    let (mut db_event, _key) = encrypted_event();
+
+    db_event.encryption_key = Some(EncryptionKey::generate());
+
+    assert!(matches!(
+        Event::<UserCreated>::try_from(db_event),
+        Err(DecodeError::Encryption(EncryptionError::Decrypt))
+    ));
+}
+
+#[test]
+fn payload_moved_to_another_event() {
+    let (mut db_event, key) = encrypted_event();
+
+    db_event.id = Uuid::new_v4();
+    db_event.encryption_key = Some(key);
+
+    assert!(matches!(
+        Event::<UserCreated>::try_from(db_event),
+        Err(DecodeError::Encryption(EncryptionError::Decrypt))
+    ));
+}
+
+#[test]
+fn upcast_encrypted_event() {
+    let (mut db_event, key) = encrypted_event();
+
+    db_event.encryption_key = Some(key);
+
+    let upcasters = Upcasters::new().register("UserCreated", 1, |mut data| {
+        data["name"] = serde_json::json!("Beans Bobby");
+
+        data
+    });
+
+    let db_event = upcasters.upcast(db_event);
+
+    assert!(!db_event.is_encrypted());
+    assert!(db_event.encryption_key.is_none());
+    assert_eq!(
+        db_event.data,
+        Some(serde_json::json!({ "name": "Beans Bobby" }))
+    );
+}
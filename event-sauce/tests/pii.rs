@@ -0,0 +1,225 @@
+use core::convert::TryFrom;
+use event_sauce::{
+    prelude::*, AggregateAction, AggregateCreate, DBEvent, EncryptionKey, EnumEventData, Event,
+    EventData, PartialPurge,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "users")]
+pub struct User {
+    #[event_sauce(id)]
+    pub id: Uuid,
+
+    pub name: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+    event_sauce_derive::CreateEventData,
+)]
+#[event_sauce(User)]
+pub struct UserCreated {
+    #[event_sauce(pii)]
+    pub name: String,
+
+    #[event_sauce(pii)]
+    #[serde(rename = "emailAddress")]
+    pub email: String,
+
+    pub plan: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+    event_sauce_derive::CreateEventData,
+)]
+#[event_sauce(User)]
+pub struct UserImported {
+    pub plan: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+    event_sauce_derive::CreateEventData,
+)]
+#[serde(rename_all = "camelCase")]
+#[event_sauce(User)]
+pub struct UserInvited {
+    #[event_sauce(pii)]
+    pub full_name: String,
+
+    #[event_sauce(pii)]
+    pub r#type: String,
+
+    #[event_sauce(pii)]
+    #[serde(rename(serialize = "invitee", deserialize = "inviteeEmail"))]
+    pub invitee_email: String,
+
+    pub invited_by: Uuid,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+    event_sauce_derive::EnumEventData,
+)]
+#[serde(tag = "event_type", content = "data")]
+#[event_sauce(User)]
+pub enum UserEventData {
+    UserCreated(UserCreated),
+    UserImported(UserImported),
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event.data.as_ref().ok_or("Event data must be populated")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.name.clone(),
+        })
+    }
+}
+
+impl AggregateCreate<UserImported> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserImported>) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: event.entity_id,
+            name: String::new(),
+        })
+    }
+}
+
+impl AggregateCreate<UserInvited> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserInvited>) -> Result<Self, Self::Error> {
+        let data = event.data.as_ref().ok_or("Event data must be populated")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.full_name.clone(),
+        })
+    }
+}
+
+impl AggregateAction<UserEventData> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_action(
+        _entity: Option<Self>,
+        _event: &Event<UserEventData>,
+    ) -> Result<Self, Self::Error> {
+        Err("Not used in this test suite")
+    }
+}
+
+fn created_event() -> DBEvent {
+    let event = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+        plan: "free".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .event;
+
+    DBEvent::try_from(event).expect("Failed to serialize event")
+}
+
+#[test]
+fn derive_pii_fields() {
+    assert_eq!(UserCreated::PII_FIELDS, &["name", "emailAddress"]);
+    assert!(UserImported::PII_FIELDS.is_empty());
+    assert_eq!(UserInvited::PII_FIELDS, &["fullName", "type", "invitee"]);
+
+    assert_eq!(
+        UserEventData::pii_fields("UserCreated"),
+        Some(&["name", "emailAddress"][..])
+    );
+    assert_eq!(UserEventData::pii_fields("UserImported"), Some(&[][..]));
+    assert_eq!(UserEventData::pii_fields("UserDeleted"), None);
+}
+
+#[test]
+fn remove_pii_fields() {
+    let mut db_event = created_event();
+
+    PartialPurge::new::<UserEventData>()
+        .apply(&mut db_event)
+        .expect("Failed to purge event");
+
+    assert_eq!(db_event.data, Some(serde_json::json!({ "plan": "free" })));
+}
+
+#[test]
+fn redact_pii_fields() {
+    let mut db_event = created_event();
+
+    PartialPurge::new::<UserEventData>()
+        .redact_with(serde_json::json!("[redacted]"))
+        .apply(&mut db_event)
+        .expect("Failed to purge event");
+
+    assert_eq!(
+        db_event.data,
+        Some(serde_json::json!({
+            "name": "[redacted]",
+            "emailAddress": "[redacted]",
+            "plan": "free"
+        }))
+    );
+
+    // Redacted payloads still deserialize
+    let event = Event::<UserCreated>::try_from(db_event).expect("Failed to read redacted event");
+
+    assert_eq!(event.data.map(|data| data.plan), Some("free".to_string()));
+}
+
+#[test]
+fn unknown_event_type() {
+    let mut db_event = created_event();
+
+    db_event.event_type = "UserRenamed".to_string();
+
+    PartialPurge::new::<UserEventData>()
+        .apply(&mut db_event)
+        .expect("Failed to purge event");
+
+    assert_eq!(db_event.data, None);
+}
+
+#[test]
+fn encrypted_event() {
+    let key = EncryptionKey::generate();
+
+    let mut db_event = created_event();
+
+    db_event.encrypt(&key).expect("Failed to encrypt event");
+    db_event.encryption_key = Some(key);
+
+    PartialPurge::new::<UserEventData>()
+        .apply(&mut db_event)
+        .expect("Failed to purge event");
+
+    assert!(!db_event.is_encrypted());
+    assert_eq!(db_event.data, Some(serde_json::json!({ "plan": "free" })));
+}
@@ -0,0 +1,182 @@
+use core::convert::TryFrom;
+use event_sauce::{
+    prelude::*, AggregateAction, AggregateCreate, DBEvent, Event, EventData, Upcasters,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "users")]
+pub struct User {
+    #[event_sauce(id)]
+    pub id: Uuid,
+
+    pub full_name: String,
+}
+
+/// Version 1 of this event stored the user's name in a `name` field
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+    event_sauce_derive::CreateEventData,
+)]
+#[event_sauce(User, version = 2)]
+pub struct UserCreated {
+    pub full_name: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+    event_sauce_derive::CreateEventData,
+)]
+#[event_sauce(User)]
+pub struct UserImported {
+    pub full_name: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+    event_sauce_derive::EnumEventData,
+)]
+#[serde(tag = "event_type", content = "data")]
+#[event_sauce(User)]
+pub enum UserEventData {
+    UserCreated(UserCreated),
+    UserImported(UserImported),
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event.data.as_ref().ok_or("Event data must be populated")?;
+
+        Ok(User {
+            id: event.entity_id,
+            full_name: data.full_name.clone(),
+        })
+    }
+}
+
+impl AggregateCreate<UserImported> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserImported>) -> Result<Self, Self::Error> {
+        let data = event.data.as_ref().ok_or("Event data must be populated")?;
+
+        Ok(User {
+            id: event.entity_id,
+            full_name: data.full_name.clone(),
+        })
+    }
+}
+
+impl AggregateAction<UserEventData> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_action(
+        _entity: Option<Self>,
+        event: &Event<UserEventData>,
+    ) -> Result<Self, Self::Error> {
+        match event.data {
+            Some(UserEventData::UserCreated(ref data)) => Ok(User {
+                id: event.entity_id,
+                full_name: data.full_name.clone(),
+            }),
+            Some(UserEventData::UserImported(ref data)) => Ok(User {
+                id: event.entity_id,
+                full_name: data.full_name.clone(),
+            }),
+            None => Err("Event data must be populated"),
+        }
+    }
+}
+
+fn upcasters() -> Upcasters {
+    Upcasters::new().register("UserCreated", 1, |mut data| {
+        if let Some(name) = data.as_object_mut().and_then(|data| data.remove("name")) {
+            data["full_name"] = name;
+        }
+
+        data
+    })
+}
+
+fn created_event() -> DBEvent {
+    let event = User::try_create(UserCreated {
+        full_name: "Bobby Beans".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .event;
+
+    DBEvent::try_from(event).expect("Failed to serialize event")
+}
+
+fn v1_event() -> DBEvent {
+    let mut db_event = created_event();
+
+    db_event.schema_version = 1;
+    db_event.data = Some(serde_json::json!({ "name": "Bobby Beans" }));
+
+    db_event
+}
+
+#[test]
+fn derived_schema_version() {
+    let created = UserCreated {
+        full_name: "Bobby Beans".to_string(),
+    };
+    let imported = UserImported {
+        full_name: "Bobby Beans".to_string(),
+    };
+
+    assert_eq!(created.schema_version(), 2);
+    assert_eq!(imported.schema_version(), 1);
+    assert_eq!(UserEventData::UserCreated(created).schema_version(), 2);
+    assert_eq!(UserEventData::UserImported(imported).schema_version(), 1);
+}
+
+#[test]
+fn schema_version_is_stored() {
+    assert_eq!(created_event().schema_version, 2);
+}
+
+#[test]
+fn old_event_fails_without_upcaster() {
+    assert!(Event::<UserCreated>::try_from(v1_event()).is_err());
+}
+
+#[test]
+fn upcast_old_event() {
+    let db_event = upcasters().upcast(v1_event());
+
+    assert_eq!(db_event.schema_version, 2);
+
+    let event = Event::<UserCreated>::try_from(db_event).expect("Failed to deserialize event");
+
+    assert_eq!(
+        event.data,
+        Some(UserCreated {
+            full_name: "Bobby Beans".to_string()
+        })
+    );
+}
+
+#[test]
+fn current_event_is_unchanged() {
+    let db_event = created_event();
+
+    let upcast = upcasters().upcast(db_event.clone());
+
+    assert_eq!(upcast.schema_version, db_event.schema_version);
+    assert_eq!(upcast.data, db_event.data);
+}
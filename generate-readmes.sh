@@ -2,7 +2,7 @@
 
 set -ex
 
-crates=("event-sauce" "storage-sqlx")
+crates=("event-sauce" "storage-sqlx" "storage-memory")
 
 for crate in ${crates[@]}; do
     pushd $crate
@@ -0,0 +1,62 @@
+//! Storage errors
+
+use event_sauce::{DecodeError, EncryptionError};
+use uuid::Uuid;
+
+/// An error returned by the in-memory storage backend
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// An event payload could not be serialized to or deserialized from JSON
+    #[error("Failed to convert event payload: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    /// An event payload could not be encrypted or decrypted
+    #[error("Failed to encrypt or decrypt event payload: {0}")]
+    Encryption(#[from] EncryptionError),
+
+    /// An entity could not be built from its stored events
+    #[error("Failed to aggregate entity from events: {0}")]
+    Aggregate(Box<dyn std::error::Error + Send + Sync>),
+
+    /// A command was rejected by its handler
+    #[error("Command rejected: {0}")]
+    Rejected(Box<dyn std::error::Error + Send + Sync>),
+
+    /// An entity trigger failed, aborting the transaction it was run in
+    ///
+    /// See [`event_sauce::OnCreated`] and the other trigger traits.
+    #[error("Trigger failed: {0}")]
+    Trigger(Box<dyn std::error::Error + Send + Sync>),
+
+    /// Another writer appended an event to the entity's stream first
+    ///
+    /// This is returned when an event could not be stored at the version it was expected to be
+    /// stored at. The entity should be reloaded and the change retried against its new state.
+    #[error("Entity {entity_id} was modified concurrently (expected stream version {expected_version:?})")]
+    VersionConflict {
+        /// The ID of the entity the event was being appended to
+        entity_id: Uuid,
+
+        /// The version the entity's stream was expected to be at, if any
+        expected_version: Option<i32>,
+    },
+
+    /// No events exist for the given entity
+    #[error("No {entity_type} entity with ID {entity_id} was found")]
+    NotFound {
+        /// The entity type, as returned by [`event_sauce::Entity::entity_type`]
+        entity_type: String,
+
+        /// The ID of the missing entity
+        entity_id: Uuid,
+    },
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::Serde(e) => Self::Serde(e),
+            DecodeError::Encryption(e) => Self::Encryption(e),
+        }
+    }
+}
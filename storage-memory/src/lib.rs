@@ -0,0 +1,720 @@
+//! # Event sauce in-memory storage backend
+//!
+//! [![Build Status](https://circleci.com/gh/jamwaffles/event-sauce/tree/master.svg?style=shield)](https://circleci.com/gh/jamwaffles/event-sauce/tree/master)
+//! [![Docs.rs](https://docs.rs/event-sauce-storage-memory/badge.svg)](https://docs.rs/event-sauce-storage-memory)
+//!
+//! In-memory storage adapter for event-sauce.
+//!
+//! `InMemoryStore` keeps all events and entities in memory. It requires no external services,
+//! which makes it useful for testing domain logic in parallel and for prototyping.
+//!
+//! Entities must implement `Persistable` and `Deletable` for `InMemoryStoreTransaction`. This is
+//! usually a single call to `InMemoryStoreTransaction::save_entity` or
+//! `InMemoryStoreTransaction::remove_entity` respectively.
+
+#![deny(missing_docs)]
+#![deny(broken_intra_doc_links)]
+
+mod error;
+
+pub use crate::error::Error;
+use event_sauce::{
+    ActionEntityBuilder, AggregateAction, AggregateConflict, AggregateUpdate, BatchBuilder,
+    BatchTriggers, CheckedUpdate, CheckedUpdateBuilder, Command, CommandError, CommandHandler,
+    ConflictCheck, ConflictData, ConflictEntityBuilder, ConflictEventBuilder, DBEvent, Deletable,
+    DeleteBatchBuilder, DeleteBuilder, DeleteBuilderPersist, Entity, EnumEventData, Event,
+    EventData, PartialPurge, Persistable, PurgeBuilder, PurgeBuilderExecute, PurgeBuilderReport,
+    PurgeReceipt, PurgeReport, RelatedEntity, RunEnumTrigger, RunTrigger, StorageBackend,
+    StorageBackendExecute, StorageBackendLoad, StorageBackendTransaction,
+    StorageBackendUpdateChecked, StorageBuilder, StorageBuilderPersist, Upcasters,
+    UpdateEntityBuilder, UpdateEventBuilder,
+};
+use serde::Deserialize;
+use std::{
+    any::Any,
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use uuid::Uuid;
+
+/// Entities are keyed by their entity type and ID
+type EntityKey = (String, Uuid);
+
+type BoxedEntity = Box<dyn Any + Send + Sync>;
+
+#[derive(Default)]
+struct State {
+    events: Vec<DBEvent>,
+    entities: HashMap<EntityKey, BoxedEntity>,
+}
+
+impl State {
+    fn latest_version(&self, entity_id: Uuid) -> i32 {
+        self.events
+            .iter()
+            .filter(|event| event.entity_id == entity_id)
+            .filter_map(|event| event.version)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    // State is only modified once all checks have passed, so a poisoned lock is still safe to use
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A change staged in a transaction, applied to the store on commit
+enum Staged {
+    Event(DBEvent),
+    Save(EntityKey, BoxedEntity),
+    Remove(EntityKey),
+    Purge(DBEvent, Option<PartialPurge>),
+}
+
+/// A storage backend that holds all events and entities in memory
+///
+/// Clones of a store share the same underlying data.
+#[derive(Clone, Default)]
+pub struct InMemoryStore {
+    state: Arc<Mutex<State>>,
+    upcasters: Upcasters,
+}
+
+impl InMemoryStore {
+    /// Create a new, empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Upgrade events stored with an older schema version using the given upcasters when loading
+    /// entities
+    pub fn with_upcasters(mut self, upcasters: Upcasters) -> Self {
+        self.upcasters = upcasters;
+
+        self
+    }
+
+    /// Start a new transaction
+    ///
+    /// Changes staged in the transaction are not visible in the store until
+    /// [`InMemoryStoreTransaction::commit`] is called.
+    pub fn transaction(&self) -> InMemoryStoreTransaction {
+        InMemoryStoreTransaction {
+            state: self.state.clone(),
+            staged: Vec::new(),
+        }
+    }
+
+    /// Get a copy of a stored entity by ID
+    pub fn get<E>(&self, entity_id: Uuid) -> Option<E>
+    where
+        E: Entity + Clone + 'static,
+    {
+        lock(&self.state)
+            .entities
+            .get(&(E::entity_type(), entity_id))
+            .and_then(|entity| entity.downcast_ref::<E>())
+            .cloned()
+    }
+
+    /// Get all events stored against the given entity ID, in the order they were persisted
+    pub fn events(&self, entity_id: Uuid) -> Vec<DBEvent> {
+        lock(&self.state)
+            .events
+            .iter()
+            .filter(|event| event.entity_id == entity_id)
+            .cloned()
+            .collect()
+    }
+}
+
+impl InMemoryStore {
+    /// Report what purging an entity removes from the store
+    fn purge_report(&self, entity_type: String, entity_id: Uuid) -> PurgeReport {
+        let state = lock(&self.state);
+
+        let events = state
+            .events
+            .iter()
+            .filter(|event| event.entity_id == entity_id)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut report = PurgeReport::new(entity_type.clone(), entity_id, &events);
+
+        // Other entities' events in the same request, or caused by one of this entity's events
+        let mut related_entities = state
+            .events
+            .iter()
+            .filter(|event| event.entity_id != entity_id)
+            .filter(|event| {
+                events.iter().any(|purged| {
+                    (purged.correlation_id.is_some()
+                        && purged.correlation_id == event.correlation_id)
+                        || event.causation_id == Some(purged.id)
+                })
+            })
+            .map(|event| RelatedEntity {
+                entity_type: event.entity_type.clone(),
+                entity_id: event.entity_id,
+            })
+            .collect::<Vec<_>>();
+
+        related_entities.sort();
+        related_entities.dedup();
+
+        report.related_entities = related_entities;
+        report.aggregate_rows = state.entities.contains_key(&(entity_type, entity_id)) as u64;
+
+        report
+    }
+}
+
+impl StorageBackend for InMemoryStore {
+    type Error = Error;
+    type Transaction = InMemoryStoreTransaction;
+}
+
+#[async_trait::async_trait]
+impl StorageBackendLoad for InMemoryStore {
+    async fn load<E, EDENUM>(&self, entity_id: Uuid) -> Result<Option<E>, Error>
+    where
+        E: ActionEntityBuilder<EDENUM> + Send,
+        E::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send,
+    {
+        self.load_with_version::<E, EDENUM>(entity_id)
+            .map(|(entity, _)| entity)
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackendExecute for InMemoryStore {
+    async fn execute<H, C>(&self, handler: &H, command: C) -> Result<Option<C::Entity>, Error>
+    where
+        H: CommandHandler<C> + Sync,
+        C: Command + Send + Sync,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Entity: Persistable<InMemoryStoreTransaction> + Send + Sync,
+        <C::Entity as AggregateAction<C::Event>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Event: RunEnumTrigger<C::Entity, InMemoryStoreTransaction>
+            + for<'de> Deserialize<'de>
+            + Send
+            + Sync,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match self.try_execute(handler, &command).await {
+                Err(Error::VersionConflict { entity_id, .. }) if attempt < C::MAX_ATTEMPTS => {
+                    log::debug!(
+                        "Entity {} was modified concurrently, retrying command (attempt {})",
+                        entity_id,
+                        attempt
+                    );
+
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackendUpdateChecked for InMemoryStore {
+    async fn update_checked<E, EDENUM, ED, B>(
+        &self,
+        entity_id: Uuid,
+        base_version: i32,
+        builder: B,
+    ) -> Result<CheckedUpdate<E>, Error>
+    where
+        E: ActionEntityBuilder<EDENUM>
+            + UpdateEntityBuilder<ED>
+            + ConflictEntityBuilder<EDENUM, ED>
+            + AggregateUpdate<ED, Output = E>
+            + Persistable<InMemoryStoreTransaction>
+            + Send
+            + Sync,
+        <E as AggregateAction<EDENUM>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        <E as AggregateUpdate<ED>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        <E as AggregateConflict<EDENUM, ED>>::Error: Into<<E as AggregateUpdate<ED>>::Error>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send + Sync,
+        ED: ConflictCheck<EDENUM> + Send + Sync,
+        ED::Builder: RunTrigger<E, ED, InMemoryStoreTransaction>,
+        ConflictEventBuilder<EDENUM, ED>:
+            RunTrigger<E, ConflictData<EDENUM, ED>, InMemoryStoreTransaction>,
+        B: Into<UpdateEventBuilder<ED>> + Send,
+    {
+        let (entity, version) = self.load_with_version::<E, EDENUM>(entity_id)?;
+
+        let entity = entity.ok_or_else(|| Error::NotFound {
+            entity_type: E::entity_type(),
+            entity_id,
+        })?;
+
+        let applied_events = self
+            .events(entity_id)
+            .into_iter()
+            .filter(|event| event.entity_type == E::entity_type())
+            .filter(|event| matches!(event.version, Some(v) if v > base_version && v <= version))
+            .collect::<Vec<_>>();
+
+        let applied_events = applied_events
+            .into_iter()
+            .map(|event| Event::try_from_db_event(self.upcasters.upcast(event)))
+            .collect::<Result<Vec<Event<EDENUM>>, _>>()?;
+
+        let checked =
+            UpdateEntityBuilder::<ED>::try_update_checked(entity, builder, applied_events)
+                .map_err(|e| Error::Aggregate(e.into()))?;
+
+        // Persisting fails if another event was stored since the entity was loaded, as it
+        // hasn't been checked for conflicts
+        match checked {
+            CheckedUpdateBuilder::Update(builder) => Ok(CheckedUpdate::Updated(
+                builder.expected_version(version).persist(self).await?,
+            )),
+            CheckedUpdateBuilder::Conflict(builder) => Ok(CheckedUpdate::Conflicted(
+                builder.expected_version(version).persist(self).await?,
+            )),
+        }
+    }
+}
+
+impl InMemoryStore {
+    /// Load an entity along with the current version of its event stream
+    fn load_with_version<E, EDENUM>(&self, entity_id: Uuid) -> Result<(Option<E>, i32), Error>
+    where
+        E: ActionEntityBuilder<EDENUM>,
+        E::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de>,
+    {
+        let entity_type = E::entity_type();
+
+        let db_events = self
+            .events(entity_id)
+            .into_iter()
+            .filter(|event| event.entity_type == entity_type)
+            .collect::<Vec<_>>();
+
+        let version = db_events
+            .iter()
+            .filter_map(|event| event.version)
+            .max()
+            .unwrap_or(0);
+
+        // Purged entities no longer exist, and their event payloads can't be replayed
+        if db_events.iter().any(|event| event.purged_at.is_some()) {
+            return Ok((None, version));
+        }
+
+        let events = db_events
+            .into_iter()
+            .map(|event| Event::try_from_db_event(self.upcasters.upcast(event)))
+            .collect::<Result<Vec<Event<EDENUM>>, _>>()?;
+
+        let entity = E::try_replay(events).map_err(|e| Error::Aggregate(e.into()))?;
+
+        Ok((entity, version))
+    }
+
+    /// Run a command once, failing with [`Error::VersionConflict`] if the entity was modified
+    /// since it was loaded
+    async fn try_execute<H, C>(&self, handler: &H, command: &C) -> Result<Option<C::Entity>, Error>
+    where
+        H: CommandHandler<C> + Sync,
+        C: Command + Sync,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Entity: Persistable<InMemoryStoreTransaction> + Send + Sync,
+        <C::Entity as AggregateAction<C::Event>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Event: RunEnumTrigger<C::Entity, InMemoryStoreTransaction>
+            + for<'de> Deserialize<'de>
+            + Send
+            + Sync,
+    {
+        let (entity, version) = match command.entity_id() {
+            Some(entity_id) => self.load_with_version::<C::Entity, C::Event>(entity_id)?,
+            None => (None, 0),
+        };
+
+        let (entity, events) =
+            handler
+                .try_decide(command, entity, version)
+                .map_err(|e| match e {
+                    CommandError::Rejected(e) => Error::Rejected(e.into()),
+                    CommandError::Aggregate(e) => Error::Aggregate(e.into()),
+                })?;
+
+        let entity = match entity {
+            Some(entity) if !events.is_empty() => entity,
+            // Nothing changed
+            entity => return Ok(entity),
+        };
+
+        let mut tx = self.transaction();
+
+        for event in events.iter() {
+            DBEvent::try_from_enum_event(event)?
+                .persist(&mut tx)
+                .await?;
+        }
+
+        let entity = entity.persist(&mut tx).await?;
+
+        for event in events {
+            C::Event::run_enum_trigger(&entity, event, &mut tx).await?;
+        }
+
+        tx.commit()?;
+
+        Ok(Some(entity))
+    }
+}
+
+/// A transaction against an [`InMemoryStore`]
+///
+/// Dropping the transaction without committing it discards all staged changes.
+pub struct InMemoryStoreTransaction {
+    state: Arc<Mutex<State>>,
+    staged: Vec<Staged>,
+}
+
+impl InMemoryStoreTransaction {
+    /// Stage an entity to be inserted or replaced in the store
+    ///
+    /// This is intended to be called from [`Persistable::persist`] implementations.
+    pub fn save_entity<E>(&mut self, entity: &E)
+    where
+        E: Entity + Clone + Send + Sync + 'static,
+    {
+        self.staged.push(Staged::Save(
+            (E::entity_type(), entity.entity_id()),
+            Box::new(entity.clone()),
+        ));
+    }
+
+    /// Stage an entity to be removed from the store
+    ///
+    /// This is intended to be called from [`Deletable::delete`] implementations.
+    pub fn remove_entity<E>(&mut self, entity: &E)
+    where
+        E: Entity,
+    {
+        self.staged
+            .push(Staged::Remove((E::entity_type(), entity.entity_id())));
+    }
+
+    /// Apply all staged changes to the store
+    ///
+    /// Fails with [`Error::VersionConflict`] if another transaction committed an event at the same
+    /// stream version as one staged in this transaction. No changes are applied in that case.
+    pub fn commit(self) -> Result<(), Error> {
+        let mut state = lock(&self.state);
+
+        // Check all events can be appended before modifying anything
+        for staged in self.staged.iter() {
+            if let Staged::Event(event) | Staged::Purge(event, _) = staged {
+                let version_taken = state.events.iter().any(|existing| {
+                    existing.entity_id == event.entity_id && existing.version == event.version
+                });
+
+                if version_taken {
+                    return Err(Error::VersionConflict {
+                        entity_id: event.entity_id,
+                        expected_version: event.version.map(|version| version - 1),
+                    });
+                }
+            }
+        }
+
+        for staged in self.staged {
+            match staged {
+                Staged::Event(event) => {
+                    push_event(&mut state, event);
+                }
+                Staged::Save(key, entity) => {
+                    state.entities.insert(key, entity);
+                }
+                Staged::Remove(key) => {
+                    state.entities.remove(&key);
+                }
+                Staged::Purge(event, partial) => {
+                    for purged in state
+                        .events
+                        .iter_mut()
+                        .filter(|purged| purged.entity_id == event.entity_id)
+                    {
+                        // Payloads that can't be partially purged are removed entirely
+                        match &partial {
+                            Some(partial) if partial.apply(purged).is_ok() => (),
+                            _ => purged.data = None,
+                        }
+
+                        purged.purged_at = Some(event.created_at);
+                        purged.purger_id = event.session_id;
+                    }
+
+                    state
+                        .entities
+                        .remove(&(event.entity_type.clone(), event.entity_id));
+
+                    push_event(&mut state, event);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Discard all staged changes
+    pub fn rollback(self) {}
+
+    /// The next version of an entity's stream, including events staged in this transaction
+    fn next_version(&self, entity_id: Uuid) -> i32 {
+        let committed = lock(&self.state).latest_version(entity_id);
+
+        let staged = self
+            .staged
+            .iter()
+            .filter_map(|staged| match staged {
+                Staged::Event(event) | Staged::Purge(event, _) if event.entity_id == entity_id => {
+                    event.version
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+
+        committed.max(staged) + 1
+    }
+
+    /// Assign the next stream version to an event, checking it against the expected version
+    fn assign_version(&self, event: &mut DBEvent) -> Result<(), Error> {
+        let next_version = self.next_version(event.entity_id);
+
+        match event.version {
+            Some(version) if version != next_version => Err(Error::VersionConflict {
+                entity_id: event.entity_id,
+                expected_version: Some(version - 1),
+            }),
+            _ => {
+                event.version = Some(next_version);
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn push_event(state: &mut State, mut event: DBEvent) {
+    event.sequence_number = Some(state.events.len() as i32 + 1);
+
+    log::trace!("Persisted event {}: {:?}", event.id, event);
+
+    state.events.push(event);
+}
+
+impl StorageBackendTransaction for InMemoryStoreTransaction {
+    type Error = Error;
+}
+
+#[async_trait::async_trait]
+impl Persistable<InMemoryStoreTransaction, DBEvent> for DBEvent {
+    async fn persist(mut self, tx: &mut InMemoryStoreTransaction) -> Result<Self, Error> {
+        tx.assign_version(&mut self)?;
+
+        tx.staged.push(Staged::Event(self.clone()));
+
+        Ok(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> StorageBuilderPersist<InMemoryStore, E> for StorageBuilder<E, ED>
+where
+    E: Persistable<InMemoryStoreTransaction> + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, InMemoryStoreTransaction>,
+{
+    async fn stage_persist(self, tx: &mut InMemoryStoreTransaction) -> Result<E, Error> {
+        let db_event = DBEvent::try_from(&self.event)?;
+
+        db_event.persist(tx).await?;
+
+        let new = self.entity.persist(tx).await?;
+
+        ED::Builder::run_trigger(&new, &self.event, tx).await?;
+
+        Ok(new)
+    }
+
+    async fn persist(self, store: &InMemoryStore) -> Result<E, Error> {
+        let mut tx = store.transaction();
+
+        let new = self.stage_persist(&mut tx).await?;
+
+        tx.commit()?;
+
+        Ok(new)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> DeleteBuilderPersist<InMemoryStore> for DeleteBuilder<E, ED>
+where
+    E: Deletable<InMemoryStoreTransaction> + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, InMemoryStoreTransaction>,
+{
+    async fn stage_delete(self, tx: &mut InMemoryStoreTransaction) -> Result<(), Error> {
+        let db_event = DBEvent::try_from(&self.event)?;
+
+        db_event.persist(tx).await?;
+
+        ED::Builder::run_trigger(&self.entity, &self.event, tx).await?;
+
+        self.entity.delete(tx).await?;
+
+        Ok(())
+    }
+
+    async fn delete(self, store: &InMemoryStore) -> Result<(), Error> {
+        let mut tx = store.transaction();
+
+        self.stage_delete(&mut tx).await?;
+
+        tx.commit()
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, EV> StorageBuilderPersist<InMemoryStore, E> for BatchBuilder<E, EV>
+where
+    E: Persistable<InMemoryStoreTransaction> + Send + Sync,
+    EV: BatchTriggers<E, InMemoryStoreTransaction>,
+{
+    async fn stage_persist(self, tx: &mut InMemoryStoreTransaction) -> Result<E, Error> {
+        for db_event in self.db_events()? {
+            db_event.persist(tx).await?;
+        }
+
+        let new = self.entity.persist(tx).await?;
+
+        self.events.run_triggers(&new, tx).await?;
+
+        Ok(new)
+    }
+
+    async fn persist(self, store: &InMemoryStore) -> Result<E, Error> {
+        let mut tx = store.transaction();
+
+        let new = self.stage_persist(&mut tx).await?;
+
+        tx.commit()?;
+
+        Ok(new)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, EV> DeleteBuilderPersist<InMemoryStore> for DeleteBatchBuilder<E, EV>
+where
+    E: Deletable<InMemoryStoreTransaction> + Send + Sync,
+    EV: BatchTriggers<E, InMemoryStoreTransaction>,
+{
+    async fn stage_delete(self, tx: &mut InMemoryStoreTransaction) -> Result<(), Error> {
+        for db_event in self.db_events()? {
+            db_event.persist(tx).await?;
+        }
+
+        self.events.run_triggers(&self.entity, tx).await?;
+
+        self.entity.delete(tx).await
+    }
+
+    async fn delete(self, store: &InMemoryStore) -> Result<(), Error> {
+        let mut tx = store.transaction();
+
+        self.stage_delete(&mut tx).await?;
+
+        tx.commit()
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> PurgeBuilderExecute<InMemoryStore> for PurgeBuilder<E, ED>
+where
+    E: Entity + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, InMemoryStoreTransaction>,
+{
+    async fn stage_purge(self, tx: &mut InMemoryStoreTransaction) -> Result<(), Error> {
+        let entity_id = self.entity.entity_id();
+
+        let mut db_event = DBEvent::try_from(&self.event)?;
+
+        // No events means there is nothing to purge
+        if tx.next_version(entity_id) == 1 {
+            return Err(Error::NotFound {
+                entity_type: E::entity_type(),
+                entity_id,
+            });
+        }
+
+        tx.assign_version(&mut db_event)?;
+
+        // The purge event is stored after every other event for the entity has been purged, so
+        // its own data is retained
+        tx.staged.push(Staged::Purge(db_event, self.partial));
+
+        ED::Builder::run_trigger(&self.entity, &self.event, tx).await
+    }
+
+    async fn purge(self, store: &InMemoryStore) -> Result<(), Error> {
+        let mut tx = store.transaction();
+
+        self.stage_purge(&mut tx).await?;
+
+        tx.commit()
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> PurgeBuilderReport<InMemoryStore> for PurgeBuilder<E, ED>
+where
+    E: Entity + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, InMemoryStoreTransaction>,
+{
+    async fn dry_run(self, store: &InMemoryStore) -> Result<PurgeReport, Error> {
+        let report = store.purge_report(E::entity_type(), self.entity.entity_id());
+
+        // No events means there is nothing to purge
+        if report.event_count == 0 {
+            return Err(Error::NotFound {
+                entity_type: E::entity_type(),
+                entity_id: self.entity.entity_id(),
+            });
+        }
+
+        Ok(report)
+    }
+
+    async fn purge_with_receipt(self, store: &InMemoryStore) -> Result<PurgeReceipt, Error> {
+        let receipt = self.receipt(store.purge_report(E::entity_type(), self.entity.entity_id()));
+
+        let mut tx = store.transaction();
+
+        self.stage_purge(&mut tx).await?;
+
+        tx.commit()?;
+
+        Ok(receipt)
+    }
+}
@@ -0,0 +1,231 @@
+use event_sauce::{
+    prelude::*, AggregateCreate, AggregateDelete, AggregateUpdate, Deletable, Event, Persistable,
+};
+use event_sauce_storage_memory::{Error, InMemoryStore, InMemoryStoreTransaction};
+use serde_derive::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "users")]
+struct User {
+    #[event_sauce(id)]
+    id: Uuid,
+    name: String,
+    email: String,
+    verified: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::CreateEventData)]
+#[event_sauce(User)]
+struct UserCreated {
+    name: String,
+    email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User)]
+struct UserEmailChanged {
+    email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User)]
+struct UserVerified;
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::DeleteEventData)]
+#[event_sauce(User)]
+struct UserDeleted;
+
+#[async_trait::async_trait]
+impl Persistable<InMemoryStoreTransaction> for User {
+    async fn persist(self, tx: &mut InMemoryStoreTransaction) -> Result<Self, Error> {
+        tx.save_entity(&self);
+
+        Ok(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl Deletable<InMemoryStoreTransaction> for User {
+    async fn delete(self, tx: &mut InMemoryStoreTransaction) -> Result<(), Error> {
+        tx.remove_entity(&self);
+
+        Ok(())
+    }
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to create User from UserCreated event")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.name.clone(),
+            email: data.email.clone(),
+            verified: false,
+        })
+    }
+}
+
+impl AggregateUpdate<UserEmailChanged> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<UserEmailChanged>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to update User from UserEmailChanged event")?;
+
+        if !data.email.contains('@') {
+            return Err("Invalid email address");
+        }
+
+        Ok(User {
+            email: data.email.clone(),
+            verified: false,
+            ..self
+        })
+    }
+}
+
+impl AggregateUpdate<UserVerified> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, _event: &Event<UserVerified>) -> Result<Self, Self::Error> {
+        Ok(User {
+            verified: true,
+            ..self
+        })
+    }
+}
+
+impl AggregateDelete<UserDeleted> for User {
+    type Error = &'static str;
+}
+
+fn event_types(store: &InMemoryStore, user_id: Uuid) -> Vec<String> {
+    store
+        .events(user_id)
+        .into_iter()
+        .map(|event| event.event_type)
+        .collect()
+}
+
+#[async_std::test]
+async fn create_and_update() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .try_update(UserEmailChanged {
+        email: "beans@bob.by".to_string(),
+    })
+    .expect("Failed to update User from UserEmailChanged event")
+    .try_update(UserVerified)
+    .expect("Failed to update User from UserVerified event")
+    .persist(&store)
+    .await?;
+
+    assert_eq!(user.email, "beans@bob.by");
+    assert!(user.verified);
+    assert_eq!(store.get::<User>(user.id), Some(user.clone()));
+    assert_eq!(
+        event_types(&store, user.id),
+        vec!["UserCreated", "UserEmailChanged", "UserVerified"]
+    );
+    assert_eq!(
+        store
+            .events(user.id)
+            .iter()
+            .map(|event| event.version)
+            .collect::<Vec<_>>(),
+        vec![Some(1), Some(2), Some(3)]
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn staged() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let mut tx = store.transaction();
+
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .try_update(UserVerified)
+    .expect("Failed to update User from UserVerified event")
+    .stage_persist(&mut tx)
+    .await?;
+
+    assert!(store.events(user.id).is_empty());
+    assert_eq!(store.get::<User>(user.id), None);
+
+    tx.commit()?;
+
+    assert_eq!(event_types(&store, user.id).len(), 2);
+    assert_eq!(store.get::<User>(user.id), Some(user));
+
+    Ok(())
+}
+
+#[test]
+fn invalid_event() {
+    let result = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .try_update(UserVerified)
+    .expect("Failed to update User from UserVerified event")
+    .try_update(UserEmailChanged {
+        email: "not an email".to_string(),
+    });
+
+    assert!(matches!(result, Err("Invalid email address")));
+}
+
+#[async_std::test]
+async fn update_and_delete() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .persist(&store)
+    .await?;
+
+    let user_id = user.id;
+
+    user.try_update(UserEmailChanged {
+        email: "beans@bob.by".to_string(),
+    })
+    .expect("Failed to update User from UserEmailChanged event")
+    .try_delete(UserDeleted)
+    .expect("Failed to delete User from UserDeleted event")
+    .delete(&store)
+    .await?;
+
+    assert_eq!(store.get::<User>(user_id), None);
+    assert_eq!(
+        event_types(&store, user_id),
+        vec!["UserCreated", "UserEmailChanged", "UserDeleted"]
+    );
+
+    Ok(())
+}
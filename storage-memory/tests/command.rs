@@ -0,0 +1,393 @@
+use event_sauce::{
+    prelude::*, ActionEventBuilder, AggregateAction, AggregateCreate, AggregateUpdate, Command,
+    CommandHandler, Event, Persistable,
+};
+use event_sauce_storage_memory::{Error, InMemoryStore, InMemoryStoreTransaction};
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "users")]
+struct User {
+    #[event_sauce(id)]
+    id: Uuid,
+    name: String,
+    email: String,
+    email_changes: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::CreateEventData)]
+#[event_sauce(User)]
+struct UserCreated {
+    name: String,
+    email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User)]
+struct UserEmailChanged {
+    email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::EnumEventData)]
+#[serde(tag = "event_type", content = "data")]
+#[event_sauce(User)]
+enum UserEventData {
+    UserCreated(UserCreated),
+    UserEmailChanged(UserEmailChanged),
+}
+
+#[async_trait::async_trait]
+impl Persistable<InMemoryStoreTransaction> for User {
+    async fn persist(self, tx: &mut InMemoryStoreTransaction) -> Result<Self, Error> {
+        tx.save_entity(&self);
+
+        Ok(self)
+    }
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to create User from UserCreated event")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.name.clone(),
+            email: data.email.clone(),
+            email_changes: 0,
+        })
+    }
+}
+
+impl AggregateUpdate<UserEmailChanged> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<UserEmailChanged>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to update User from UserEmailChanged event")?;
+
+        Ok(User {
+            email: data.email.clone(),
+            email_changes: self.email_changes + 1,
+            ..self
+        })
+    }
+}
+
+impl AggregateAction<UserEventData> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_action(
+        entity: Option<Self>,
+        event: &Event<UserEventData>,
+    ) -> Result<Self, Self::Error> {
+        match event.data {
+            Some(UserEventData::UserCreated(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserCreated>()
+                    .map_err(|_| "Failed to convert event into UserCreated")?;
+
+                Self::try_aggregate_create(&event)
+            }
+            Some(UserEventData::UserEmailChanged(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserEmailChanged>()
+                    .map_err(|_| "Failed to convert event into UserEmailChanged")?;
+
+                entity
+                    .ok_or("User must exist to apply UserEmailChanged event")?
+                    .try_aggregate_update(&event)
+            }
+            None => entity.ok_or("User must exist to apply an empty event"),
+        }
+    }
+}
+
+struct RegisterUser {
+    name: String,
+    email: String,
+}
+
+impl Command for RegisterUser {
+    type Entity = User;
+    type Event = UserEventData;
+    type Error = &'static str;
+
+    fn entity_id(&self) -> Option<Uuid> {
+        None
+    }
+}
+
+struct ChangeEmail {
+    user_id: Uuid,
+    email: String,
+}
+
+impl Command for ChangeEmail {
+    type Entity = User;
+    type Event = UserEventData;
+    type Error = &'static str;
+
+    fn entity_id(&self) -> Option<Uuid> {
+        Some(self.user_id)
+    }
+}
+
+struct UserHandler;
+
+impl CommandHandler<RegisterUser> for UserHandler {
+    fn handle(
+        &self,
+        command: &RegisterUser,
+        _entity: Option<&User>,
+    ) -> Result<Vec<ActionEventBuilder<UserEventData>>, &'static str> {
+        Ok(vec![UserEventData::UserCreated(UserCreated {
+            name: command.name.clone(),
+            email: command.email.clone(),
+        })
+        .into()])
+    }
+}
+
+impl CommandHandler<ChangeEmail> for UserHandler {
+    fn handle(
+        &self,
+        command: &ChangeEmail,
+        entity: Option<&User>,
+    ) -> Result<Vec<ActionEventBuilder<UserEventData>>, &'static str> {
+        let user = entity.ok_or("User does not exist")?;
+
+        if !command.email.contains('@') {
+            return Err("Invalid email address");
+        }
+
+        if user.email == command.email {
+            return Ok(Vec::new());
+        }
+
+        Ok(vec![UserEventData::UserEmailChanged(UserEmailChanged {
+            email: command.email.clone(),
+        })
+        .into()])
+    }
+}
+
+/// Changes the user's email address behind the handler's back every time it is called, until
+/// `interfere` runs out
+struct InterferingHandler {
+    store: InMemoryStore,
+    interfere: AtomicUsize,
+    calls: AtomicUsize,
+}
+
+impl CommandHandler<ChangeEmail> for InterferingHandler {
+    fn handle(
+        &self,
+        command: &ChangeEmail,
+        entity: Option<&User>,
+    ) -> Result<Vec<ActionEventBuilder<UserEventData>>, &'static str> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+
+        let user = entity.ok_or("User does not exist")?;
+
+        if self.interfere.load(Ordering::SeqCst) > 0 {
+            self.interfere.fetch_sub(1, Ordering::SeqCst);
+
+            async_std::task::block_on(
+                user.clone()
+                    .try_update(UserEmailChanged {
+                        email: "concurrent@bob.by".to_string(),
+                    })
+                    .expect("Failed to update User from UserEmailChanged event")
+                    .persist(&self.store),
+            )
+            .expect("Failed to persist concurrent change");
+        }
+
+        Ok(vec![UserEventData::UserEmailChanged(UserEmailChanged {
+            email: command.email.clone(),
+        })
+        .into()])
+    }
+}
+
+async fn register(store: &InMemoryStore) -> Result<User, Error> {
+    let user = store
+        .execute(
+            &UserHandler,
+            RegisterUser {
+                name: "Bobby Beans".to_string(),
+                email: "bobby@bea.ns".to_string(),
+            },
+        )
+        .await?;
+
+    Ok(user.expect("RegisterUser must create a user"))
+}
+
+#[async_std::test]
+async fn create_and_update() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = register(&store).await?;
+
+    assert_eq!(store.get::<User>(user.id), Some(user.clone()));
+
+    let updated = store
+        .execute(
+            &UserHandler,
+            ChangeEmail {
+                user_id: user.id,
+                email: "beans@bob.by".to_string(),
+            },
+        )
+        .await?;
+
+    assert_eq!(
+        updated.as_ref().map(|user| user.email.as_str()),
+        Some("beans@bob.by")
+    );
+    assert_eq!(store.get::<User>(user.id), updated);
+    assert_eq!(
+        store
+            .events(user.id)
+            .iter()
+            .map(|event| event.version)
+            .collect::<Vec<_>>(),
+        vec![Some(1), Some(2)]
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn no_events() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = register(&store).await?;
+
+    let unchanged = store
+        .execute(
+            &UserHandler,
+            ChangeEmail {
+                user_id: user.id,
+                email: user.email.clone(),
+            },
+        )
+        .await?;
+
+    assert_eq!(unchanged, Some(user.clone()));
+    assert_eq!(store.events(user.id).len(), 1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn rejected() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = register(&store).await?;
+
+    let result = store
+        .execute(
+            &UserHandler,
+            ChangeEmail {
+                user_id: user.id,
+                email: "not an email".to_string(),
+            },
+        )
+        .await;
+
+    assert!(matches!(result, Err(Error::Rejected(_))));
+
+    let missing = store
+        .execute(
+            &UserHandler,
+            ChangeEmail {
+                user_id: Uuid::new_v4(),
+                email: "beans@bob.by".to_string(),
+            },
+        )
+        .await;
+
+    assert!(matches!(missing, Err(Error::Rejected(_))));
+    assert_eq!(store.events(user.id).len(), 1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn retry_concurrent_modification() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = register(&store).await?;
+
+    let handler = InterferingHandler {
+        store: store.clone(),
+        interfere: AtomicUsize::new(1),
+        calls: AtomicUsize::new(0),
+    };
+
+    let updated = store
+        .execute(
+            &handler,
+            ChangeEmail {
+                user_id: user.id,
+                email: "beans@bob.by".to_string(),
+            },
+        )
+        .await?
+        .expect("User must exist");
+
+    assert_eq!(handler.calls.load(Ordering::SeqCst), 2);
+
+    // The command was applied on top of the concurrent change
+    assert_eq!(updated.email, "beans@bob.by");
+    assert_eq!(updated.email_changes, 2);
+    assert_eq!(store.events(user.id).len(), 3);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn retries_exhausted() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = register(&store).await?;
+
+    let handler = InterferingHandler {
+        store: store.clone(),
+        interfere: AtomicUsize::new(usize::MAX),
+        calls: AtomicUsize::new(0),
+    };
+
+    let result = store
+        .execute(
+            &handler,
+            ChangeEmail {
+                user_id: user.id,
+                email: "beans@bob.by".to_string(),
+            },
+        )
+        .await;
+
+    assert!(matches!(result, Err(Error::VersionConflict { .. })));
+    assert_eq!(
+        handler.calls.load(Ordering::SeqCst),
+        <ChangeEmail as Command>::MAX_ATTEMPTS
+    );
+
+    Ok(())
+}
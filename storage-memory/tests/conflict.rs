@@ -0,0 +1,454 @@
+use event_sauce::{
+    prelude::*, AggregateAction, AggregateConflict, AggregateCreate, AggregateResolveConflict,
+    AggregateUpdate, CheckedUpdate, ConflictCheck, ConflictData, ConflictEntityBuilder,
+    ConflictResolved, Event, OnConflicted, Persistable, ResolveConflictEntityBuilder,
+};
+use event_sauce_storage_memory::{Error, InMemoryStore, InMemoryStoreTransaction};
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "users")]
+struct User {
+    #[event_sauce(id)]
+    id: Uuid,
+    name: String,
+    email: String,
+    conflicted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::CreateEventData)]
+#[event_sauce(User)]
+struct UserCreated {
+    name: String,
+    email: String,
+}
+
+/// Written by the user's conflict trigger, keyed by the ID of the conflict event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "conflict_notices")]
+struct ConflictNotice {
+    #[event_sauce(id)]
+    id: Uuid,
+    user_id: Uuid,
+    rejected_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User, custom_triggers(OnConflicted))]
+struct UserNameChanged {
+    name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User)]
+struct UserEmailChanged {
+    email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::EnumEventData)]
+#[serde(tag = "event_type", content = "data")]
+#[event_sauce(User)]
+#[allow(clippy::enum_variant_names)]
+enum UserEventData {
+    UserCreated(UserCreated),
+    UserNameChanged(UserNameChanged),
+    UserEmailChanged(UserEmailChanged),
+}
+
+#[async_trait::async_trait]
+impl Persistable<InMemoryStoreTransaction> for User {
+    async fn persist(self, tx: &mut InMemoryStoreTransaction) -> Result<Self, Error> {
+        tx.save_entity(&self);
+
+        Ok(self)
+    }
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to create User from UserCreated event")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.name.clone(),
+            email: data.email.clone(),
+            conflicted: false,
+        })
+    }
+}
+
+impl AggregateUpdate<UserNameChanged> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<UserNameChanged>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to update User from UserNameChanged event")?;
+
+        Ok(User {
+            name: data.name.clone(),
+            ..self
+        })
+    }
+}
+
+impl AggregateUpdate<UserEmailChanged> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<UserEmailChanged>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to update User from UserEmailChanged event")?;
+
+        Ok(User {
+            email: data.email.clone(),
+            ..self
+        })
+    }
+}
+
+impl AggregateAction<UserEventData> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_action(
+        entity: Option<Self>,
+        event: &Event<UserEventData>,
+    ) -> Result<Self, Self::Error> {
+        match event.data {
+            Some(UserEventData::UserCreated(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserCreated>()
+                    .map_err(|_| "Failed to convert event into UserCreated")?;
+
+                Self::try_aggregate_create(&event)
+            }
+            Some(UserEventData::UserNameChanged(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserNameChanged>()
+                    .map_err(|_| "Failed to convert event into UserNameChanged")?;
+
+                entity
+                    .ok_or("User must exist to apply UserNameChanged event")?
+                    .try_aggregate_update(&event)
+            }
+            Some(UserEventData::UserEmailChanged(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserEmailChanged>()
+                    .map_err(|_| "Failed to convert event into UserEmailChanged")?;
+
+                entity
+                    .ok_or("User must exist to apply UserEmailChanged event")?
+                    .try_aggregate_update(&event)
+            }
+            None => entity.ok_or("User must exist to apply an empty event"),
+        }
+    }
+}
+
+/// Name changes conflict with other name changes, but not with changes to other fields
+impl ConflictCheck<UserEventData> for UserNameChanged {
+    fn check_conflict(
+        self,
+        applied_event: &Event<UserEventData>,
+    ) -> Result<Self, ConflictData<UserEventData, Self>> {
+        match applied_event.data {
+            Some(UserEventData::UserNameChanged(_)) => Err(ConflictData {
+                applied_event: applied_event.clone(),
+                conflicting_event_data: self,
+            }),
+            _ => Ok(self),
+        }
+    }
+}
+
+impl AggregateConflict<UserEventData, UserNameChanged> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_conflict(
+        self,
+        _event: &Event<ConflictData<UserEventData, UserNameChanged>>,
+    ) -> Result<Self, Self::Error> {
+        Ok(User {
+            conflicted: true,
+            ..self
+        })
+    }
+}
+
+impl ConflictEntityBuilder<UserEventData, UserNameChanged> for User {}
+
+impl AggregateResolveConflict<UserNameChanged> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_resolve_conflict(
+        self,
+        event: &Event<ConflictResolved<UserNameChanged>>,
+    ) -> Result<Self, Self::Error> {
+        let user = User {
+            conflicted: false,
+            ..self
+        };
+
+        match event.resolved_event() {
+            Some(event) => user.try_aggregate_update(&event),
+            None => Ok(user),
+        }
+    }
+}
+
+impl ResolveConflictEntityBuilder<UserNameChanged> for User {}
+
+#[async_trait::async_trait]
+impl OnConflicted<UserEventData, UserNameChanged, InMemoryStoreTransaction> for User {
+    async fn on_conflicted(
+        &self,
+        event: &Event<ConflictData<UserEventData, UserNameChanged>>,
+        tx: &mut InMemoryStoreTransaction,
+    ) -> Result<(), Error> {
+        let data = event
+            .data
+            .as_ref()
+            .expect("Conflict event data must be populated");
+
+        tx.save_entity(&ConflictNotice {
+            id: event.id,
+            user_id: event.entity_id,
+            rejected_name: data.conflicting_event_data.name.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+fn event_types(store: &InMemoryStore, user_id: Uuid) -> Vec<String> {
+    store
+        .events(user_id)
+        .into_iter()
+        .map(|event| event.event_type)
+        .collect()
+}
+
+async fn create_user(store: &InMemoryStore) -> Result<User, Error> {
+    User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .persist(store)
+    .await
+}
+
+#[async_std::test]
+async fn update_without_conflict() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await?;
+
+    // Another client changes the email address after the user was read at version 1
+    user.clone()
+        .try_update(UserEmailChanged {
+            email: "beans@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await?;
+
+    let result = store
+        .update_checked::<User, UserEventData, _, _>(
+            user.id,
+            1,
+            UserNameChanged {
+                name: "Beans Bobby".to_string(),
+            },
+        )
+        .await?;
+
+    let expected = User {
+        name: "Beans Bobby".to_string(),
+        email: "beans@bob.by".to_string(),
+        ..user.clone()
+    };
+
+    assert_eq!(result, CheckedUpdate::Updated(expected.clone()));
+    assert_eq!(store.get::<User>(user.id), Some(expected));
+    assert_eq!(
+        event_types(&store, user.id),
+        vec!["UserCreated", "UserEmailChanged", "UserNameChanged"]
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn update_with_conflict() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await?;
+
+    user.clone()
+        .try_update(UserNameChanged {
+            name: "Beans Bobby".to_string(),
+        })
+        .expect("Failed to update User from UserNameChanged event")
+        .persist(&store)
+        .await?;
+
+    let result = store
+        .update_checked::<User, UserEventData, _, _>(
+            user.id,
+            1,
+            UserNameChanged {
+                name: "Bob Beans".to_string(),
+            },
+        )
+        .await?;
+
+    let expected = User {
+        name: "Beans Bobby".to_string(),
+        conflicted: true,
+        ..user.clone()
+    };
+
+    assert_eq!(result, CheckedUpdate::Conflicted(expected.clone()));
+    assert_eq!(store.get::<User>(user.id), Some(expected));
+    assert_eq!(
+        event_types(&store, user.id),
+        vec!["UserCreated", "UserNameChanged", "ConflictData"]
+    );
+    assert_eq!(
+        store
+            .events(user.id)
+            .iter()
+            .map(|event| event.version)
+            .collect::<Vec<_>>(),
+        vec![Some(1), Some(2), Some(3)]
+    );
+
+    // Only the conflict trigger is implemented by hand, the update trigger is still a noop
+    let conflict_event = store.events(user.id).pop().expect("No conflict event");
+
+    assert_eq!(
+        store.get::<ConflictNotice>(conflict_event.id),
+        Some(ConflictNotice {
+            id: conflict_event.id,
+            user_id: user.id,
+            rejected_name: "Bob Beans".to_string(),
+        })
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn update_from_current_version() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await?;
+
+    let result = store
+        .update_checked::<User, UserEventData, _, _>(
+            user.id,
+            1,
+            UserNameChanged {
+                name: "Beans Bobby".to_string(),
+            },
+        )
+        .await?;
+
+    assert!(matches!(result, CheckedUpdate::Updated(user) if user.name == "Beans Bobby"));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn update_missing_entity() {
+    let store = InMemoryStore::new();
+
+    let result = store
+        .update_checked::<User, UserEventData, _, _>(
+            Uuid::new_v4(),
+            1,
+            UserNameChanged {
+                name: "Beans Bobby".to_string(),
+            },
+        )
+        .await;
+
+    assert!(matches!(result, Err(Error::NotFound { .. })));
+}
+
+#[async_std::test]
+async fn resolve_conflict() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await?;
+
+    user.clone()
+        .try_update(UserNameChanged {
+            name: "Beans Bobby".to_string(),
+        })
+        .expect("Failed to update User from UserNameChanged event")
+        .persist(&store)
+        .await?;
+
+    let user = match store
+        .update_checked::<User, UserEventData, _, _>(
+            user.id,
+            1,
+            UserNameChanged {
+                name: "Bob Beans".to_string(),
+            },
+        )
+        .await?
+    {
+        CheckedUpdate::Conflicted(user) => user,
+        CheckedUpdate::Updated(_) => panic!("Name changes must conflict"),
+    };
+
+    let conflict_event_id = store
+        .events(user.id)
+        .last()
+        .map(|event| event.id)
+        .expect("Conflict event must be stored");
+
+    // Someone picks the conflicting name by hand later on
+    let user = user
+        .try_resolve_conflict(ConflictResolved::new(
+            conflict_event_id,
+            Some(UserNameChanged {
+                name: "Bob Beans".to_string(),
+            }),
+        ))
+        .expect("Failed to resolve conflict")
+        .persist(&store)
+        .await?;
+
+    assert_eq!(user.name, "Bob Beans");
+    assert!(!user.conflicted);
+    assert_eq!(store.get::<User>(user.id), Some(user.clone()));
+    assert_eq!(
+        event_types(&store, user.id),
+        vec![
+            "UserCreated",
+            "UserNameChanged",
+            "ConflictData",
+            "ConflictResolved"
+        ]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,541 @@
+use event_sauce::{
+    prelude::*, AggregateAction, AggregateCreate, AggregateDelete, AggregateUpdate, DBEvent,
+    Deletable, Event, PartialPurge, Persistable, Upcasters, UpdateEventBuilder,
+};
+use event_sauce_storage_memory::{Error, InMemoryStore, InMemoryStoreTransaction};
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "users")]
+struct User {
+    #[event_sauce(id)]
+    id: Uuid,
+    name: String,
+    email: String,
+}
+
+/// Version 1 of this event stored the user's name in a `username` field
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::CreateEventData)]
+#[event_sauce(User, version = 2)]
+struct UserCreated {
+    name: String,
+    #[event_sauce(pii)]
+    email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User)]
+struct UserEmailChanged {
+    #[event_sauce(pii)]
+    email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::DeleteEventData)]
+#[event_sauce(User)]
+struct UserDeleted;
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::PurgeEventData)]
+#[event_sauce(User)]
+struct UserPurged;
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::EnumEventData)]
+#[serde(tag = "event_type", content = "data")]
+#[event_sauce(User)]
+enum UserEventData {
+    UserCreated(UserCreated),
+    UserEmailChanged(UserEmailChanged),
+}
+
+#[async_trait::async_trait]
+impl Persistable<InMemoryStoreTransaction> for User {
+    async fn persist(self, tx: &mut InMemoryStoreTransaction) -> Result<Self, Error> {
+        tx.save_entity(&self);
+
+        Ok(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl Deletable<InMemoryStoreTransaction> for User {
+    async fn delete(self, tx: &mut InMemoryStoreTransaction) -> Result<(), Error> {
+        tx.remove_entity(&self);
+
+        Ok(())
+    }
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to create User from UserCreated event")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.name.clone(),
+            email: data.email.clone(),
+        })
+    }
+}
+
+impl AggregateUpdate<UserEmailChanged> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<UserEmailChanged>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to update User from UserEmailChanged event")?;
+
+        Ok(User {
+            email: data.email.clone(),
+            ..self
+        })
+    }
+}
+
+impl AggregateDelete<UserDeleted> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_delete(self, _event: &Event<UserDeleted>) -> Result<Self, Self::Error> {
+        Ok(self)
+    }
+}
+
+impl AggregateAction<UserEventData> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_action(
+        entity: Option<Self>,
+        event: &Event<UserEventData>,
+    ) -> Result<Self, Self::Error> {
+        match event.data {
+            Some(UserEventData::UserCreated(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserCreated>()
+                    .map_err(|_| "Failed to convert event into UserCreated")?;
+
+                Self::try_aggregate_create(&event)
+            }
+            Some(UserEventData::UserEmailChanged(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserEmailChanged>()
+                    .map_err(|_| "Failed to convert event into UserEmailChanged")?;
+
+                entity
+                    .ok_or("User must exist to apply UserEmailChanged event")?
+                    .try_aggregate_update(&event)
+            }
+            None => entity.ok_or("User must exist to apply an empty event"),
+        }
+    }
+}
+
+async fn create_user(store: &InMemoryStore) -> User {
+    User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .persist(store)
+    .await
+    .expect("Failed to persist")
+}
+
+#[async_std::test]
+async fn persist() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let user = user
+        .try_update(UserEmailChanged {
+            email: "beans@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await?;
+
+    assert_eq!(store.get::<User>(user.id), Some(user.clone()));
+
+    let versions = store
+        .events(user.id)
+        .iter()
+        .map(|event| event.version)
+        .collect::<Vec<_>>();
+
+    assert_eq!(versions, vec![Some(1), Some(2)]);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn rollback() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let mut tx = store.transaction();
+
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .stage_persist(&mut tx)
+    .await?;
+
+    // Nothing is visible until the transaction is committed
+    assert_eq!(store.get::<User>(user.id), None);
+
+    tx.rollback();
+
+    assert_eq!(store.get::<User>(user.id), None);
+    assert!(store.events(user.id).is_empty());
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn commit() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let mut tx = store.transaction();
+
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .stage_persist(&mut tx)
+    .await?;
+
+    let user = user
+        .try_update(UserEmailChanged {
+            email: "beans@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .stage_persist(&mut tx)
+        .await?;
+
+    tx.commit()?;
+
+    assert_eq!(store.get::<User>(user.id), Some(user.clone()));
+    assert_eq!(store.events(user.id).len(), 2);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn version_conflict() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    user.clone()
+        .try_update(
+            UpdateEventBuilder::new(UserEmailChanged {
+                email: "first@bob.by".to_string(),
+            })
+            .expected_version(1),
+        )
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await?;
+
+    let result = user
+        .try_update(
+            UpdateEventBuilder::new(UserEmailChanged {
+                email: "second@bob.by".to_string(),
+            })
+            .expected_version(1),
+        )
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::VersionConflict {
+            expected_version: Some(1),
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn concurrent_transactions() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let mut first = store.transaction();
+    let mut second = store.transaction();
+
+    user.clone()
+        .try_update(UserEmailChanged {
+            email: "first@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .stage_persist(&mut first)
+        .await?;
+
+    user.clone()
+        .try_update(UserEmailChanged {
+            email: "second@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .stage_persist(&mut second)
+        .await?;
+
+    first.commit()?;
+
+    assert!(matches!(
+        second.commit(),
+        Err(Error::VersionConflict { .. })
+    ));
+
+    assert_eq!(
+        store.get::<User>(user.id).map(|user| user.email),
+        Some("first@bob.by".to_string())
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn delete() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let user_id = user.id;
+
+    user.try_delete(UserDeleted)
+        .expect("Failed to create deletion event")
+        .delete(&store)
+        .await?;
+
+    assert_eq!(store.get::<User>(user_id), None);
+
+    // Event data is retained
+    assert_eq!(store.events(user_id).len(), 2);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn purge() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let user_id = user.id;
+
+    user.try_purge(UserPurged).purge(&store).await?;
+
+    assert_eq!(store.get::<User>(user_id), None);
+
+    let events = store.events(user_id);
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].data, None);
+    assert!(events[0].purged_at.is_some());
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn partial_purge() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let user_id = user.id;
+
+    user.try_purge(UserPurged)
+        .partial(PartialPurge::new::<UserEventData>())
+        .purge(&store)
+        .await?;
+
+    assert_eq!(store.get::<User>(user_id), None);
+
+    let events = store.events(user_id);
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(
+        events[0].data,
+        Some(serde_json::json!({ "name": "Bobby Beans" }))
+    );
+    assert!(events[0].purged_at.is_some());
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn purge_dry_run() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let correlation_id = Uuid::new_v4();
+
+    let user = User::try_create(
+        UserCreated {
+            name: "Bobby Beans".to_string(),
+            email: "bobby@bea.ns".to_string(),
+        }
+        .into_builder()
+        .correlation_id(correlation_id),
+    )
+    .expect("Failed to create User from UserCreated event")
+    .persist(&store)
+    .await?;
+
+    let invited = User::try_create(
+        UserCreated {
+            name: "Beans Bobby".to_string(),
+            email: "beans@bob.by".to_string(),
+        }
+        .into_builder()
+        .correlation_id(correlation_id),
+    )
+    .expect("Failed to create User from UserCreated event")
+    .persist(&store)
+    .await?;
+
+    let user = user
+        .try_update(UserEmailChanged {
+            email: "bobby@beans.com".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await?;
+
+    let user_id = user.id;
+
+    let events = store.events(user_id);
+
+    let report = user.clone().try_purge(UserPurged).dry_run(&store).await?;
+
+    assert_eq!(report.entity_id, user_id);
+    assert_eq!(report.event_count, 2);
+    assert_eq!(report.event_types, vec!["UserCreated", "UserEmailChanged"]);
+    assert_eq!(report.first_event_at, Some(events[0].created_at));
+    assert_eq!(report.last_event_at, Some(events[1].created_at));
+    assert_eq!(report.aggregate_rows, 1);
+    assert_eq!(report.related_entities.len(), 1);
+    assert_eq!(report.related_entities[0].entity_id, invited.id);
+
+    // Nothing is purged
+    assert_eq!(store.get::<User>(user_id), Some(user.clone()));
+    assert_eq!(store.events(user_id).len(), 2);
+    assert!(store.events(user_id)[0].data.is_some());
+
+    let receipt = user
+        .try_purge(UserPurged)
+        .purge_with_receipt(&store)
+        .await?;
+
+    assert_eq!(receipt.report, report);
+    assert!(!receipt.partial);
+    assert_eq!(store.events(user_id)[2].id, receipt.purge_event_id);
+    assert_eq!(store.get::<User>(user_id), None);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn purge_missing() {
+    let store = InMemoryStore::new();
+
+    let user = User {
+        id: Uuid::new_v4(),
+        name: "Nobody".to_string(),
+        email: "nobody@bea.ns".to_string(),
+    };
+
+    let result = user.try_purge(UserPurged).purge(&store).await;
+
+    assert!(matches!(result, Err(Error::NotFound { .. })));
+}
+
+#[async_std::test]
+async fn load() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let user = user
+        .try_update(UserEmailChanged {
+            email: "beans@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await?;
+
+    let loaded = store.load::<User, UserEventData>(user.id).await?;
+
+    assert_eq!(loaded, Some(user));
+
+    assert_eq!(
+        store.load::<User, UserEventData>(Uuid::new_v4()).await?,
+        None
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn load_upcast() -> Result<(), Error> {
+    let upcasters = Upcasters::new().register("UserCreated", 1, |mut data| {
+        if let Some(name) = data
+            .as_object_mut()
+            .and_then(|data| data.remove("username"))
+        {
+            data["name"] = name;
+        }
+
+        data
+    });
+
+    let store = InMemoryStore::new().with_upcasters(upcasters);
+
+    let builder = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event");
+
+    let id = builder.entity.id;
+
+    // Store the event as it would have been written by an older version of the application
+    let mut db_event = DBEvent::try_from(builder.event)?;
+    db_event.schema_version = 1;
+    db_event.data = Some(serde_json::json!({
+        "username": "Bobby Beans",
+        "email": "bobby@bea.ns",
+    }));
+
+    let mut tx = store.transaction();
+    db_event.persist(&mut tx).await?;
+    tx.commit()?;
+
+    let loaded = store.load::<User, UserEventData>(id).await?;
+
+    assert_eq!(
+        loaded.map(|user| user.name),
+        Some("Bobby Beans".to_string())
+    );
+
+    // Events are upgraded as they're read, not in the store
+    assert_eq!(store.events(id)[0].schema_version, 1);
+
+    Ok(())
+}
@@ -0,0 +1,465 @@
+use event_sauce::{
+    prelude::*, ActionEventBuilder, AggregateAction, AggregateCreate, AggregateDelete,
+    AggregateUpdate, Command, CommandHandler, Deletable, Event, OnCreated, OnDeleted, OnPurged,
+    OnUpdated, Persistable,
+};
+use event_sauce_storage_memory::{Error, InMemoryStore, InMemoryStoreTransaction};
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "users")]
+struct User {
+    #[event_sauce(id)]
+    id: Uuid,
+    name: String,
+    email: String,
+}
+
+/// Written by the user's triggers, keyed by the ID of the event that caused it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, event_sauce_derive::Entity)]
+#[event_sauce(entity_name = "audit_entries")]
+struct AuditEntry {
+    #[event_sauce(id)]
+    id: Uuid,
+    user_id: Uuid,
+    message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::CreateEventData)]
+#[event_sauce(User, custom_triggers)]
+struct UserCreated {
+    name: String,
+    email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::UpdateEventData)]
+#[event_sauce(User, custom_triggers)]
+struct UserEmailChanged {
+    email: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::DeleteEventData)]
+#[event_sauce(User, custom_triggers)]
+struct UserDeleted;
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::PurgeEventData)]
+#[event_sauce(User, custom_triggers)]
+struct UserPurged;
+
+#[derive(Debug, Clone, Serialize, Deserialize, event_sauce_derive::EnumEventData)]
+#[serde(tag = "event_type", content = "data")]
+#[event_sauce(User)]
+enum UserEventData {
+    UserCreated(UserCreated),
+    UserEmailChanged(UserEmailChanged),
+}
+
+fn audit<ED>(tx: &mut InMemoryStoreTransaction, event: &Event<ED>, message: String)
+where
+    ED: event_sauce::EventData,
+{
+    tx.save_entity(&AuditEntry {
+        id: event.id,
+        user_id: event.entity_id,
+        message,
+    });
+}
+
+#[async_trait::async_trait]
+impl OnCreated<UserCreated, InMemoryStoreTransaction> for User {
+    async fn on_created(
+        &self,
+        event: &Event<UserCreated>,
+        tx: &mut InMemoryStoreTransaction,
+    ) -> Result<(), Error> {
+        audit(tx, event, format!("Created {}", self.name));
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl OnUpdated<UserEmailChanged, InMemoryStoreTransaction> for User {
+    async fn on_updated(
+        &self,
+        event: &Event<UserEmailChanged>,
+        tx: &mut InMemoryStoreTransaction,
+    ) -> Result<(), Error> {
+        if !self.email.contains('@') {
+            return Err(Error::Trigger(
+                format!("Invalid email address {}", self.email).into(),
+            ));
+        }
+
+        audit(tx, event, format!("Changed email to {}", self.email));
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl OnDeleted<UserDeleted, InMemoryStoreTransaction> for User {
+    async fn on_deleted(
+        &self,
+        event: &Event<UserDeleted>,
+        tx: &mut InMemoryStoreTransaction,
+    ) -> Result<(), Error> {
+        audit(tx, event, format!("Deleted {}", self.name));
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl OnPurged<UserPurged, InMemoryStoreTransaction> for User {
+    async fn on_purged(
+        &self,
+        event: &Event<UserPurged>,
+        tx: &mut InMemoryStoreTransaction,
+    ) -> Result<(), Error> {
+        audit(tx, event, "Purged user".to_string());
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Persistable<InMemoryStoreTransaction> for User {
+    async fn persist(self, tx: &mut InMemoryStoreTransaction) -> Result<Self, Error> {
+        tx.save_entity(&self);
+
+        Ok(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl Deletable<InMemoryStoreTransaction> for User {
+    async fn delete(self, tx: &mut InMemoryStoreTransaction) -> Result<(), Error> {
+        tx.remove_entity(&self);
+
+        Ok(())
+    }
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to create User from UserCreated event")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.name.clone(),
+            email: data.email.clone(),
+        })
+    }
+}
+
+impl AggregateUpdate<UserEmailChanged> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<UserEmailChanged>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to update User from UserEmailChanged event")?;
+
+        Ok(User {
+            email: data.email.clone(),
+            ..self
+        })
+    }
+}
+
+impl AggregateDelete<UserDeleted> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_delete(self, _event: &Event<UserDeleted>) -> Result<Self, Self::Error> {
+        Ok(self)
+    }
+}
+
+impl AggregateAction<UserEventData> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_action(
+        entity: Option<Self>,
+        event: &Event<UserEventData>,
+    ) -> Result<Self, Self::Error> {
+        match event.data {
+            Some(UserEventData::UserCreated(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserCreated>()
+                    .map_err(|_| "Failed to convert event into UserCreated")?;
+
+                Self::try_aggregate_create(&event)
+            }
+            Some(UserEventData::UserEmailChanged(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserEmailChanged>()
+                    .map_err(|_| "Failed to convert event into UserEmailChanged")?;
+
+                entity
+                    .ok_or("User must exist to apply UserEmailChanged event")?
+                    .try_aggregate_update(&event)
+            }
+            None => entity.ok_or("User must exist to apply an empty event"),
+        }
+    }
+}
+
+struct ChangeEmail {
+    user_id: Uuid,
+    email: String,
+}
+
+impl Command for ChangeEmail {
+    type Entity = User;
+    type Event = UserEventData;
+    type Error = &'static str;
+
+    fn entity_id(&self) -> Option<Uuid> {
+        Some(self.user_id)
+    }
+}
+
+struct UserHandler;
+
+impl CommandHandler<ChangeEmail> for UserHandler {
+    fn handle(
+        &self,
+        command: &ChangeEmail,
+        entity: Option<&User>,
+    ) -> Result<Vec<ActionEventBuilder<UserEventData>>, &'static str> {
+        entity.ok_or("User does not exist")?;
+
+        Ok(vec![UserEventData::UserEmailChanged(UserEmailChanged {
+            email: command.email.clone(),
+        })
+        .into()])
+    }
+}
+
+async fn create_user(store: &InMemoryStore) -> User {
+    User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .persist(store)
+    .await
+    .expect("Failed to persist")
+}
+
+/// Get the audit messages written for a user, in the order their events were persisted
+fn audit_log(store: &InMemoryStore, user_id: Uuid) -> Vec<String> {
+    store
+        .events(user_id)
+        .iter()
+        .filter_map(|event| store.get::<AuditEntry>(event.id))
+        .map(|entry| entry.message)
+        .collect()
+}
+
+#[async_std::test]
+async fn created_and_updated() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let user = user
+        .try_update(UserEmailChanged {
+            email: "beans@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await?;
+
+    assert_eq!(
+        audit_log(&store, user.id),
+        vec![
+            "Created Bobby Beans".to_string(),
+            "Changed email to beans@bob.by".to_string()
+        ]
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn failing_trigger_rolls_back() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let result = user
+        .clone()
+        .try_update(UserEmailChanged {
+            email: "not an email".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await;
+
+    assert!(matches!(result, Err(Error::Trigger(_))));
+
+    // Neither the event nor the entity were stored
+    assert_eq!(store.events(user.id).len(), 1);
+    assert_eq!(store.get::<User>(user.id), Some(user));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn staged_trigger() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let mut tx = store.transaction();
+
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .stage_persist(&mut tx)
+    .await?;
+
+    // Changes made by triggers are part of the transaction
+    assert!(audit_log(&store, user.id).is_empty());
+
+    tx.commit()?;
+
+    assert_eq!(audit_log(&store, user.id), vec!["Created Bobby Beans"]);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn deleted() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let user_id = user.id;
+
+    user.try_delete(UserDeleted)
+        .expect("Failed to create deletion event")
+        .delete(&store)
+        .await?;
+
+    assert_eq!(
+        audit_log(&store, user_id),
+        vec!["Created Bobby Beans", "Deleted Bobby Beans"]
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn purged() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    let user_id = user.id;
+
+    user.try_purge(UserPurged).purge(&store).await?;
+
+    assert_eq!(
+        audit_log(&store, user_id),
+        vec!["Created Bobby Beans", "Purged user"]
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn command_triggers() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = create_user(&store).await;
+
+    store
+        .execute(
+            &UserHandler,
+            ChangeEmail {
+                user_id: user.id,
+                email: "beans@bob.by".to_string(),
+            },
+        )
+        .await?;
+
+    assert_eq!(
+        audit_log(&store, user.id),
+        vec!["Created Bobby Beans", "Changed email to beans@bob.by"]
+    );
+
+    // A failing trigger rolls back the command's events and entity
+    let result = store
+        .execute(
+            &UserHandler,
+            ChangeEmail {
+                user_id: user.id,
+                email: "not an email".to_string(),
+            },
+        )
+        .await;
+
+    assert!(matches!(result, Err(Error::Trigger(_))));
+    assert_eq!(store.events(user.id).len(), 2);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn batch_triggers() -> Result<(), Error> {
+    let store = InMemoryStore::new();
+
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .try_update(UserEmailChanged {
+        email: "beans@bob.by".to_string(),
+    })
+    .expect("Failed to update User from UserEmailChanged event")
+    .persist(&store)
+    .await?;
+
+    let user_id = user.id;
+
+    assert_eq!(
+        audit_log(&store, user_id),
+        vec!["Created Bobby Beans", "Changed email to beans@bob.by"]
+    );
+
+    user.try_update(UserEmailChanged {
+        email: "bobby@beans.com".to_string(),
+    })
+    .expect("Failed to update User from UserEmailChanged event")
+    .try_delete(UserDeleted)
+    .expect("Failed to create deletion event")
+    .delete(&store)
+    .await?;
+
+    assert_eq!(
+        audit_log(&store, user_id),
+        vec![
+            "Created Bobby Beans",
+            "Changed email to beans@bob.by",
+            "Changed email to bobby@beans.com",
+            "Deleted Bobby Beans"
+        ]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+//! Legal holds that prevent entities from being deleted or purged
+
+use crate::Error;
+use event_sauce::{
+    AggregateCreate, AggregateUpdate, CreateEntityBuilder, CreateEventBuilder, Entity, Event,
+    EventData, UpdateEntityBuilder, UpdateEventBuilder,
+};
+use serde_derive::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// A legal hold placed on an entity, for example because it's subject to litigation
+///
+/// While a hold is active, deleting or purging the entity fails with [`Error::LegalHold`]. This
+/// applies even if erasure of the entity's data has been requested.
+///
+/// Holds are entities themselves, so placing and releasing them is recorded in the event log.
+/// Their current state is stored in the `legal_holds` table.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use event_sauce_storage_sqlx::{LegalHold, LegalHoldPlaced, LegalHoldReleased};
+///
+/// let hold = LegalHold::try_create(LegalHoldPlaced::new(&user, "Case 2021-0042"))?
+///     .persist(&store)
+///     .await?;
+///
+/// // Fails with `Error::LegalHold`
+/// user.try_purge(UserPurged).purge(&store).await?;
+///
+/// hold.try_update(LegalHoldReleased::new("Case closed"))?
+///     .persist(&store)
+///     .await?;
+/// ```
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct LegalHold {
+    /// The hold's ID
+    pub id: Uuid,
+    /// The type of the held entity
+    pub held_entity_type: String,
+    /// The ID of the held entity
+    pub held_entity_id: Uuid,
+    /// Why the hold was placed
+    pub reason: String,
+    /// The session that placed the hold
+    pub placed_by: Option<Uuid>,
+    /// The time the hold was placed at
+    pub placed_at: DateTime<Utc>,
+    /// The session that released the hold
+    pub released_by: Option<Uuid>,
+    /// The time the hold was released at, or `None` if it's still active
+    pub released_at: Option<DateTime<Utc>>,
+}
+
+impl LegalHold {
+    /// Whether the hold still prevents its entity from being deleted or purged
+    pub fn is_active(&self) -> bool {
+        self.released_at.is_none()
+    }
+}
+
+impl Entity for LegalHold {
+    const ENTITY_TYPE: &'static str = "legal_holds";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+/// Place a [`LegalHold`] on an entity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LegalHoldPlaced {
+    /// The type of the held entity
+    pub held_entity_type: String,
+    /// The ID of the held entity
+    pub held_entity_id: Uuid,
+    /// Why the hold is placed
+    pub reason: String,
+}
+
+impl LegalHoldPlaced {
+    /// Place a hold on the given entity
+    pub fn new<E>(entity: &E, reason: impl Into<String>) -> Self
+    where
+        E: Entity,
+    {
+        Self {
+            held_entity_type: E::entity_type(),
+            held_entity_id: entity.entity_id(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl EventData for LegalHoldPlaced {
+    type Entity = LegalHold;
+
+    type Builder = CreateEventBuilder<Self>;
+
+    fn event_type(&self) -> &'static str {
+        "LegalHoldPlaced"
+    }
+}
+
+/// Release a [`LegalHold`], allowing its entity to be deleted or purged again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LegalHoldReleased {
+    /// Why the hold is released
+    pub reason: String,
+}
+
+impl LegalHoldReleased {
+    /// Release a hold
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+impl EventData for LegalHoldReleased {
+    type Entity = LegalHold;
+
+    type Builder = UpdateEventBuilder<Self>;
+
+    fn event_type(&self) -> &'static str {
+        "LegalHoldReleased"
+    }
+}
+
+impl AggregateCreate<LegalHoldPlaced> for LegalHold {
+    type Error = Error;
+
+    fn try_aggregate_create(event: &Event<LegalHoldPlaced>) -> Result<Self, Self::Error> {
+        let data = event.data.as_ref().ok_or_else(|| {
+            Error::Aggregate("Event data must be populated to place a legal hold".into())
+        })?;
+
+        Ok(Self {
+            id: event.entity_id,
+            held_entity_type: data.held_entity_type.clone(),
+            held_entity_id: data.held_entity_id,
+            reason: data.reason.clone(),
+            placed_by: event.session_id,
+            placed_at: event.created_at,
+            released_by: None,
+            released_at: None,
+        })
+    }
+}
+
+impl AggregateUpdate<LegalHoldReleased> for LegalHold {
+    type Error = Error;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<LegalHoldReleased>) -> Result<Self, Self::Error> {
+        if !self.is_active() {
+            return Err(Error::Aggregate(
+                format!("Legal hold {} has already been released", self.id).into(),
+            ));
+        }
+
+        Ok(Self {
+            released_by: event.session_id,
+            released_at: Some(event.created_at),
+            ..self
+        })
+    }
+}
+
+impl CreateEntityBuilder<LegalHoldPlaced> for LegalHold {}
+
+impl UpdateEntityBuilder<LegalHoldReleased> for LegalHold {}
@@ -0,0 +1,1183 @@
+//! Postgres storage backend
+
+/// Select every column of the `events` table, with the encryption key of each event's entity as
+/// `encryption_key`
+macro_rules! select_events {
+    () => {
+        "select events.*, (select key from encryption_keys where encryption_keys.entity_id = events.entity_id) as encryption_key
+        from events"
+    };
+}
+
+/// Match events that come after the event with the sequence number bound to the given parameter,
+/// in the order of the transactions that stored them, using the given comparison
+///
+/// Events stored by transactions that may still be open are never matched. See
+/// [`Subscription`](self::Subscription#ordering-and-visibility).
+macro_rules! after_position {
+    ($cmp:literal, $param:literal) => {
+        concat!(
+            "transaction_id < pg_snapshot_xmin(pg_current_snapshot()) and (",
+            $param,
+            "::int is null or (transaction_id, sequence_number) ",
+            $cmp,
+            " (coalesce((select transaction_id from events where sequence_number = ",
+            $param,
+            "), '0'), ",
+            $param,
+            "))"
+        )
+    };
+}
+
+mod legal_hold;
+mod outbox;
+mod process_manager;
+mod purge;
+mod query;
+mod retention;
+mod subscription;
+
+pub use self::outbox::{OutboxPublisher, OutboxRelay};
+pub use self::process_manager::ProcessManager;
+pub use self::query::EventQuery;
+pub use self::retention::{Retention, RetentionPolicy, RetentionRunner};
+pub use self::subscription::Subscription;
+
+use crate::Error;
+use event_sauce::StorageBackendTransaction;
+use event_sauce::{
+    ActionEntityBuilder, AggregateAction, AggregateConflict, AggregateUpdate, BatchBuilder,
+    BatchTriggers, CheckedUpdate, CheckedUpdateBuilder, Command, CommandError, CommandHandler,
+    ConflictCheck, ConflictData, ConflictEntityBuilder, ConflictEventBuilder, DBEvent, Deletable,
+    DeleteBatchBuilder, DeleteBuilder, DeleteBuilderPersist, EncryptionKey, Entity, EnumEventData,
+    Event, EventData, Persistable, PurgeBuilder, PurgeBuilderExecute, PurgeBuilderReport,
+    PurgeReceipt, PurgeReport, Purgeable, RelatedEntity, RunEnumTrigger, RunTrigger, Snapshot,
+    StorageBackend, StorageBackendExecute, StorageBackendLoad, StorageBackendLoadSnapshot,
+    StorageBackendUpdateChecked, StorageBuilder, StorageBuilderPersist, Upcasters,
+    UpdateEntityBuilder, UpdateEventBuilder,
+};
+use serde::Deserialize;
+use sqlx::Transaction;
+use sqlx::{PgExecutor, PgPool, Postgres};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+/// Columns added to the `events` table since it was first released, along with their definitions
+///
+/// These are added to existing tables when a store is created. Events stored before a column was
+/// added get its default value, so their `version` is null.
+const EVENTS_ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("version", "integer null"),
+    ("schema_version", "integer not null default 1"),
+    ("correlation_id", "uuid null"),
+    ("causation_id", "uuid null"),
+    ("metadata", "jsonb not null default '{}'"),
+    ("encrypted", "boolean not null default false"),
+    (
+        "transaction_id",
+        "xid8 not null default pg_current_xact_id()",
+    ),
+];
+
+/// [sqlx](https://docs.rs/sqlx)-based Postgres backing store
+#[derive(Debug, Clone)]
+pub struct SqlxPgStore {
+    /// sqlx [`PgPool`](sqlx::PgPool) to communicate with the database
+    pub pool: PgPool,
+
+    /// Upcasters applied to stored events before they are deserialized
+    upcasters: Upcasters,
+
+    /// Whether stored events are also added to the outbox for publishing
+    outbox: bool,
+
+    /// Whether stored event payloads are encrypted with a per-entity key
+    encryption: bool,
+}
+
+impl SqlxPgStore {
+    /// Create a new transaction
+    pub async fn transaction(&self) -> Result<SqlxPgStoreTransaction, sqlx::Error> {
+        let tx = self.pool.begin().await?;
+
+        Ok(SqlxPgStoreTransaction {
+            tx,
+            outbox: self.outbox,
+            encryption: self.encryption,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackend for SqlxPgStore {
+    type Error = Error;
+    type Transaction = SqlxPgStoreTransaction;
+}
+
+#[async_trait::async_trait]
+impl StorageBackendLoad for SqlxPgStore {
+    async fn load<E, EDENUM>(&self, entity_id: Uuid) -> Result<Option<E>, Error>
+    where
+        E: ActionEntityBuilder<EDENUM> + Send,
+        E::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send,
+    {
+        let (entity, _) = self
+            .load_with_version::<E, EDENUM>(&self.pool, entity_id)
+            .await?;
+
+        Ok(entity)
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackendExecute for SqlxPgStore {
+    async fn execute<H, C>(&self, handler: &H, command: C) -> Result<Option<C::Entity>, Error>
+    where
+        H: CommandHandler<C> + Sync,
+        C: Command + Send + Sync,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Entity: Persistable<SqlxPgStoreTransaction> + Send + Sync,
+        <C::Entity as AggregateAction<C::Event>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Event: RunEnumTrigger<C::Entity, SqlxPgStoreTransaction>
+            + for<'de> Deserialize<'de>
+            + Send
+            + Sync,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match self.try_execute(handler, &command).await {
+                Err(Error::VersionConflict { entity_id, .. }) if attempt < C::MAX_ATTEMPTS => {
+                    log::debug!(
+                        "Entity {} was modified concurrently, retrying command (attempt {})",
+                        entity_id,
+                        attempt
+                    );
+
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackendUpdateChecked for SqlxPgStore {
+    async fn update_checked<E, EDENUM, ED, B>(
+        &self,
+        entity_id: Uuid,
+        base_version: i32,
+        builder: B,
+    ) -> Result<CheckedUpdate<E>, Error>
+    where
+        E: ActionEntityBuilder<EDENUM>
+            + UpdateEntityBuilder<ED>
+            + ConflictEntityBuilder<EDENUM, ED>
+            + AggregateUpdate<ED, Output = E>
+            + Persistable<SqlxPgStoreTransaction>
+            + Send
+            + Sync,
+        <E as AggregateAction<EDENUM>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        <E as AggregateUpdate<ED>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        <E as AggregateConflict<EDENUM, ED>>::Error: Into<<E as AggregateUpdate<ED>>::Error>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send + Sync,
+        ED: ConflictCheck<EDENUM> + Send + Sync,
+        ED::Builder: RunTrigger<E, ED, SqlxPgStoreTransaction>,
+        ConflictEventBuilder<EDENUM, ED>:
+            RunTrigger<E, ConflictData<EDENUM, ED>, SqlxPgStoreTransaction>,
+        B: Into<UpdateEventBuilder<ED>> + Send,
+    {
+        let (entity, version) = self
+            .load_with_version::<E, EDENUM>(&self.pool, entity_id)
+            .await?;
+
+        let entity = entity.ok_or_else(|| Error::NotFound {
+            entity_type: E::entity_type(),
+            entity_id,
+        })?;
+
+        let applied_events: Vec<DBEvent> = sqlx::query_as(concat!(
+            select_events!(),
+            r#"
+            where entity_type = $1 and entity_id = $2 and version > $3 and version <= $4
+            order by sequence_number asc"#
+        ))
+        .bind(E::entity_type())
+        .bind(entity_id)
+        .bind(base_version)
+        .bind(version)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let applied_events = applied_events
+            .into_iter()
+            .map(|event| Event::try_from_db_event(self.upcasters.upcast(event)))
+            .collect::<Result<Vec<Event<EDENUM>>, _>>()?;
+
+        let checked =
+            UpdateEntityBuilder::<ED>::try_update_checked(entity, builder, applied_events)
+                .map_err(|e| Error::Aggregate(e.into()))?;
+
+        // Persisting fails if another event was stored since the entity was loaded, as it
+        // hasn't been checked for conflicts
+        match checked {
+            CheckedUpdateBuilder::Update(builder) => Ok(CheckedUpdate::Updated(
+                builder.expected_version(version).persist(self).await?,
+            )),
+            CheckedUpdateBuilder::Conflict(builder) => Ok(CheckedUpdate::Conflicted(
+                builder.expected_version(version).persist(self).await?,
+            )),
+        }
+    }
+}
+
+impl SqlxPgStore {
+    /// Load an entity along with the current version of its event stream
+    async fn load_with_version<'e, E, EDENUM>(
+        &self,
+        executor: impl PgExecutor<'e>,
+        entity_id: Uuid,
+    ) -> Result<(Option<E>, i32), Error>
+    where
+        E: ActionEntityBuilder<EDENUM>,
+        E::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de>,
+    {
+        let db_events: Vec<DBEvent> = sqlx::query_as(concat!(
+            select_events!(),
+            r#"
+            where entity_type = $1 and entity_id = $2
+            order by sequence_number asc"#
+        ))
+        .bind(E::entity_type())
+        .bind(entity_id)
+        .fetch_all(executor)
+        .await?;
+
+        let version = db_events
+            .iter()
+            .filter_map(|event| event.version)
+            .max()
+            .unwrap_or(0);
+
+        // Purged entities no longer exist, and their event payloads can't be replayed
+        if db_events.iter().any(|event| event.purged_at.is_some()) {
+            return Ok((None, version));
+        }
+
+        let events = db_events
+            .into_iter()
+            .map(|event| Event::try_from_db_event(self.upcasters.upcast(event)))
+            .collect::<Result<Vec<Event<EDENUM>>, _>>()?;
+
+        let entity = E::try_replay(events).map_err(|e| Error::Aggregate(e.into()))?;
+
+        Ok((entity, version))
+    }
+
+    /// Run a command once, failing with [`Error::VersionConflict`] if the entity was modified
+    /// since it was loaded
+    async fn try_execute<H, C>(&self, handler: &H, command: &C) -> Result<Option<C::Entity>, Error>
+    where
+        H: CommandHandler<C> + Sync,
+        C: Command + Send + Sync,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Entity: Persistable<SqlxPgStoreTransaction> + Send + Sync,
+        <C::Entity as AggregateAction<C::Event>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Event: RunEnumTrigger<C::Entity, SqlxPgStoreTransaction>
+            + for<'de> Deserialize<'de>
+            + Send
+            + Sync,
+    {
+        let mut tx = self.transaction().await?;
+
+        let entity = self.stage_execute(&mut tx, handler, command).await?;
+
+        tx.commit().await?;
+
+        Ok(entity)
+    }
+
+    /// Run a command once as part of an existing transaction
+    ///
+    /// The entity is loaded through the transaction, so the command sees any changes already
+    /// staged in it.
+    ///
+    /// Unlike [`StorageBackendExecute::execute`], the command is not retried if the entity is
+    /// modified concurrently. [`Error::VersionConflict`] is returned instead, and the transaction
+    /// should be rolled back.
+    pub async fn stage_execute<H, C>(
+        &self,
+        tx: &mut SqlxPgStoreTransaction,
+        handler: &H,
+        command: &C,
+    ) -> Result<Option<C::Entity>, Error>
+    where
+        H: CommandHandler<C> + Sync,
+        C: Command + Send + Sync,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Entity: Persistable<SqlxPgStoreTransaction> + Send + Sync,
+        <C::Entity as AggregateAction<C::Event>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Event: RunEnumTrigger<C::Entity, SqlxPgStoreTransaction>
+            + for<'de> Deserialize<'de>
+            + Send
+            + Sync,
+    {
+        let (entity, version) = match command.entity_id() {
+            Some(entity_id) => {
+                self.load_with_version::<C::Entity, C::Event>(tx.get(), entity_id)
+                    .await?
+            }
+            None => (None, 0),
+        };
+
+        let (entity, events) =
+            handler
+                .try_decide(command, entity, version)
+                .map_err(|e| match e {
+                    CommandError::Rejected(e) => Error::Rejected(e.into()),
+                    CommandError::Aggregate(e) => Error::Aggregate(e.into()),
+                })?;
+
+        let entity = match entity {
+            Some(entity) if !events.is_empty() => entity,
+            // Nothing changed
+            entity => return Ok(entity),
+        };
+
+        for event in events.iter() {
+            DBEvent::try_from_enum_event(event)?.persist(tx).await?;
+        }
+
+        let entity = entity.persist(tx).await?;
+
+        for event in events {
+            C::Event::run_enum_trigger(&entity, event, tx).await?;
+        }
+
+        Ok(Some(entity))
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackendLoadSnapshot for SqlxPgStore {
+    async fn load_from_snapshot<E, EDENUM>(&self, entity_id: Uuid) -> Result<Option<E>, Error>
+    where
+        E: ActionEntityBuilder<EDENUM> + Snapshot + Send + Sync,
+        E::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send,
+    {
+        let snapshot: Option<(i32, serde_json::Value)> = sqlx::query_as(
+            "select sequence_number, data from snapshots where entity_type = $1 and entity_id = $2",
+        )
+        .bind(E::entity_type())
+        .bind(entity_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let (entity, snapshot_sequence_number) = match snapshot {
+            Some((sequence_number, data)) => (Some(serde_json::from_value(data)?), sequence_number),
+            None => (None, 0),
+        };
+
+        let db_events: Vec<DBEvent> = sqlx::query_as(concat!(
+            select_events!(),
+            r#"
+            where entity_type = $1 and entity_id = $2 and sequence_number > $3
+            order by sequence_number asc"#
+        ))
+        .bind(E::entity_type())
+        .bind(entity_id)
+        .bind(snapshot_sequence_number)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Purged entities no longer exist, and their event payloads can't be replayed
+        if db_events.iter().any(|event| event.purged_at.is_some()) {
+            return Ok(None);
+        }
+
+        let replayed = db_events.len();
+        let last_sequence_number = db_events.last().and_then(|event| event.sequence_number);
+
+        let events = db_events
+            .into_iter()
+            .map(|event| Event::try_from_db_event(self.upcasters.upcast(event)))
+            .collect::<Result<Vec<Event<EDENUM>>, _>>()?;
+
+        let entity = E::try_replay_from(entity, events).map_err(|e| Error::Aggregate(e.into()))?;
+
+        if let (Some(entity), Some(sequence_number)) = (&entity, last_sequence_number) {
+            if replayed >= E::SNAPSHOT_FREQUENCY {
+                self.save_snapshot(entity, sequence_number).await?;
+            }
+        }
+
+        Ok(entity)
+    }
+}
+
+/// TODO: Docs
+pub struct SqlxPgStoreTransaction {
+    tx: Transaction<'static, Postgres>,
+    outbox: bool,
+    encryption: bool,
+}
+
+impl SqlxPgStoreTransaction {
+    /// TODO: Docs
+    pub fn get(&mut self) -> &mut Transaction<'static, Postgres> {
+        &mut self.tx
+    }
+
+    /// TODO: Docs
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        self.tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Discard all changes made in this transaction
+    pub async fn rollback(self) -> Result<(), sqlx::Error> {
+        self.tx.rollback().await?;
+
+        Ok(())
+    }
+}
+
+impl SqlxPgStoreTransaction {
+    /// Get the encryption key of an entity, creating one if it doesn't have one yet
+    async fn encryption_key(
+        &mut self,
+        entity_type: &str,
+        entity_id: Uuid,
+    ) -> Result<EncryptionKey, Error> {
+        // The no-op update makes the existing key be returned if there is one
+        let (key,): (EncryptionKey,) = sqlx::query_as(
+            r#"insert into encryption_keys (
+                entity_id,
+                entity_type,
+                key,
+                created_at
+            ) values (
+                $1,
+                $2,
+                $3,
+                now()
+            )
+            on conflict (entity_id)
+            do update set
+            entity_id = excluded.entity_id
+            returning key"#,
+        )
+        .bind(entity_id)
+        .bind(entity_type)
+        .bind(EncryptionKey::generate())
+        .fetch_one(self.get())
+        .await?;
+
+        Ok(key)
+    }
+}
+
+impl StorageBackendTransaction for SqlxPgStoreTransaction {
+    type Error = Error;
+}
+
+impl SqlxPgStore {
+    /// Create a new backing store instance with a given [`PgPool`](sqlx::PgPool)
+    pub async fn new(pool: PgPool) -> Result<Self, sqlx::Error> {
+        Self::create_events_table(&pool).await?;
+
+        Ok(Self {
+            pool,
+            upcasters: Upcasters::default(),
+            outbox: false,
+            encryption: false,
+        })
+    }
+
+    /// Upgrade events stored with an older schema version using the given upcasters when loading
+    /// entities
+    pub fn with_upcasters(mut self, upcasters: Upcasters) -> Self {
+        self.upcasters = upcasters;
+
+        self
+    }
+
+    /// Add every event stored through this store to the outbox, in the same transaction as the
+    /// event itself
+    ///
+    /// Outbox events are published to other systems by an [`OutboxRelay`].
+    pub fn with_outbox(mut self) -> Self {
+        self.outbox = true;
+
+        self
+    }
+
+    /// Encrypt the payload of every event stored through this store with a key unique to the
+    /// event's entity
+    ///
+    /// Keys are stored in the `encryption_keys` table, and payloads are decrypted transparently
+    /// when events are read. Purging an entity destroys its key instead of removing its events'
+    /// payloads, leaving the ciphertext in place. Restoring the event log from a backup then
+    /// doesn't undo a purge, as long as the `encryption_keys` table isn't restored from a backup
+    /// taken before it.
+    ///
+    /// Events stored before encryption was enabled are left as plaintext, and are still removed
+    /// when purged.
+    pub fn with_encryption(mut self) -> Self {
+        self.encryption = true;
+
+        self
+    }
+
+    async fn create_events_table(pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(r#"create extension if not exists "uuid-ossp";"#)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(r#"
+            create table if not exists events(
+                id uuid primary key,
+                sequence_number serial,
+                event_type varchar(64) not null,
+                entity_type varchar(64) not null,
+                entity_id uuid not null,
+                -- This field is null if the event is purged, in such case purged_at and purger_id should be populated.
+                -- Encrypted payloads are kept when purged, but can no longer be decrypted.
+                data jsonb,
+                -- Whether data holds the ciphertext of a payload encrypted with the entity's key
+                encrypted boolean not null default false,
+                session_id uuid null,
+                -- Tracing IDs linking the event to the request, command or event that caused it
+                correlation_id uuid null,
+                causation_id uuid null,
+                metadata jsonb not null default '{}',
+                created_at timestamp with time zone not null,
+                purger_id uuid null,
+                purged_at timestamp with time zone null,
+                -- Position of the event in its entity's stream. Unique per entity to prevent
+                -- concurrent writers from interleaving events.
+                version integer null,
+                -- Schema version of the event payload, used to select upcasters when reading
+                schema_version integer not null default 1,
+                -- ID of the transaction that stored the event, used to read the log in commit-safe order
+                transaction_id xid8 not null default pg_current_xact_id(),
+                unique (entity_id, version)
+            );
+        "#).execute(&mut tx).await?;
+
+        // Bring tables created by older versions up to date
+        for (column, definition) in EVENTS_ADDED_COLUMNS {
+            sqlx::query(&format!(
+                "alter table events add column if not exists {} {}",
+                column, definition
+            ))
+            .execute(&mut tx)
+            .await?;
+        }
+
+        // Has the same name as the index created by the unique constraint above
+        sqlx::query(
+            "create unique index if not exists events_entity_id_version_key on events (entity_id, version)",
+        )
+        .execute(&mut tx)
+        .await?;
+
+        // Used to read the log in order from a sequence number
+        sqlx::query(
+            "create index if not exists events_position on events (transaction_id, sequence_number)",
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "create index if not exists events_sequence_number on events (sequence_number)",
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            create table if not exists snapshots(
+                entity_type varchar(64) not null,
+                entity_id uuid not null,
+                -- Sequence number of the last event applied to the snapshotted entity
+                sequence_number integer not null,
+                data jsonb not null,
+                created_at timestamp with time zone not null,
+                primary key (entity_type, entity_id)
+            );
+        "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            create table if not exists checkpoints(
+                subscription varchar(64) primary key,
+                -- Sequence number of the last event processed by the subscription
+                sequence_number integer not null,
+                updated_at timestamp with time zone not null
+            );
+        "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            create table if not exists outbox(
+                event_id uuid primary key references events (id),
+                -- Number of failed attempts to publish the event
+                attempts integer not null default 0,
+                next_attempt_at timestamp with time zone not null default now(),
+                last_error text null,
+                delivered_at timestamp with time zone null
+            );
+        "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "create index if not exists outbox_pending on outbox (next_attempt_at) where delivered_at is null",
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            create table if not exists encryption_keys(
+                entity_id uuid primary key,
+                entity_type varchar(64) not null,
+                -- AES-256-GCM key used to encrypt the payloads of the entity's events. Deleted when
+                -- the entity is purged.
+                key bytea not null,
+                created_at timestamp with time zone not null
+            );
+        "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            create table if not exists process_states(
+                -- Name of the process manager's subscription
+                process varchar(64) not null,
+                correlation_id uuid not null,
+                state jsonb not null,
+                completed_at timestamp with time zone null,
+                updated_at timestamp with time zone not null,
+                primary key (process, correlation_id)
+            );
+        "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            create table if not exists legal_holds(
+                id uuid primary key,
+                -- The held entity, which can't be deleted or purged until the hold is released
+                held_entity_type varchar(64) not null,
+                held_entity_id uuid not null,
+                reason text not null,
+                placed_by uuid null,
+                placed_at timestamp with time zone not null,
+                released_by uuid null,
+                released_at timestamp with time zone null
+            );
+        "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "create index if not exists legal_holds_active on legal_holds (held_entity_id) where released_at is null",
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Store a snapshot of an entity, replacing any older snapshot
+    async fn save_snapshot<E>(&self, entity: &E, sequence_number: i32) -> Result<(), Error>
+    where
+        E: Snapshot + Sync,
+    {
+        sqlx::query(
+            r#"insert into snapshots (
+                entity_type,
+                entity_id,
+                sequence_number,
+                data,
+                created_at
+            ) values (
+                $1,
+                $2,
+                $3,
+                $4,
+                now()
+            )
+            on conflict (entity_type, entity_id)
+            do update set
+            sequence_number = excluded.sequence_number,
+            data = excluded.data,
+            created_at = excluded.created_at
+            where snapshots.sequence_number < excluded.sequence_number"#,
+        )
+        .bind(E::entity_type())
+        .bind(entity.entity_id())
+        .bind(sequence_number)
+        .bind(serde_json::to_value(entity)?)
+        .execute(&self.pool)
+        .await?;
+
+        log::trace!(
+            "Stored snapshot of {} {} at sequence number {}",
+            E::entity_type(),
+            entity.entity_id(),
+            sequence_number
+        );
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Persistable<SqlxPgStoreTransaction, DBEvent> for DBEvent {
+    async fn persist(mut self, store: &mut SqlxPgStoreTransaction) -> Result<Self, Error> {
+        let entity_id = self.entity_id;
+        let expected_version = self.version.map(|version| version - 1);
+
+        let encryption_key = match (store.encryption, &self.data) {
+            (true, Some(_)) => {
+                let key = store.encryption_key(&self.entity_type, entity_id).await?;
+
+                self.encrypt(&key)?;
+
+                Some(key)
+            }
+            _ => None,
+        };
+
+        // The event is appended to the end of the entity's stream. If the event has a version set,
+        // nothing is inserted unless that version is the next one in the stream, or the event is
+        // already stored at that version and is only being persisted again.
+        let saved: Option<Self> = sqlx::query_as(
+            r#"insert into events (
+                id,
+                event_type,
+                entity_type,
+                entity_id,
+                data,
+                session_id,
+                created_at,
+                version,
+                schema_version,
+                correlation_id,
+                causation_id,
+                metadata,
+                encrypted
+            )
+            select
+                $1,
+                $2,
+                $3,
+                $4,
+                $5,
+                $6,
+                $7,
+                coalesce(max(version), 0) + 1,
+                $9,
+                $10,
+                $11,
+                $12,
+                $13
+            from events
+            where entity_id = $4
+            having $8::integer is null
+            or coalesce(max(version), 0) + 1 = $8
+            or exists (select 1 from events where id = $1 and version = $8)
+            on conflict (id)
+            do update set
+            data = excluded.data,
+            encrypted = excluded.encrypted
+            returning *"#,
+        )
+        .bind(self.id)
+        .bind(self.event_type)
+        .bind(self.entity_type)
+        .bind(self.entity_id)
+        .bind(self.data)
+        .bind(self.session_id)
+        .bind(self.created_at)
+        .bind(self.version)
+        .bind(self.schema_version)
+        .bind(self.correlation_id)
+        .bind(self.causation_id)
+        .bind(self.metadata)
+        .bind(self.encrypted)
+        .fetch_optional(store.get())
+        .await
+        .map_err(|e| match e {
+            // Another transaction inserted an event at the same version first
+            sqlx::Error::Database(ref db_error) if db_error.code().as_deref() == Some("23505") => {
+                Error::VersionConflict {
+                    entity_id,
+                    expected_version,
+                }
+            }
+            e => Error::Sqlx(e),
+        })?;
+
+        let mut saved = saved.ok_or(Error::VersionConflict {
+            entity_id,
+            expected_version,
+        })?;
+
+        // Allow the stored event to be decrypted
+        saved.encryption_key = encryption_key;
+
+        if store.outbox {
+            sqlx::query(
+                "insert into outbox (event_id) values ($1) on conflict (event_id) do nothing",
+            )
+            .bind(saved.id)
+            .execute(store.get())
+            .await?;
+        }
+
+        log::trace!("Persisted event {}: {:?}", saved.id, saved);
+
+        Ok(saved)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> StorageBuilderPersist<SqlxPgStore, E> for StorageBuilder<E, ED>
+where
+    E: Persistable<SqlxPgStoreTransaction> + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, SqlxPgStoreTransaction>,
+{
+    async fn stage_persist(self, tx: &mut SqlxPgStoreTransaction) -> Result<E, Error> {
+        let db_event = DBEvent::try_from(&self.event)?;
+
+        db_event.persist(tx).await?;
+
+        let new = self.entity.persist(tx).await?;
+
+        ED::Builder::run_trigger(&new, &self.event, tx).await?;
+
+        Ok(new)
+    }
+
+    async fn persist(self, store: &SqlxPgStore) -> Result<E, Error> {
+        let mut tx = store.transaction().await?;
+
+        let new = self.stage_persist(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(new)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> DeleteBuilderPersist<SqlxPgStore> for DeleteBuilder<E, ED>
+where
+    E: Deletable<SqlxPgStoreTransaction> + Entity + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, SqlxPgStoreTransaction>,
+{
+    async fn stage_delete(self, tx: &mut SqlxPgStoreTransaction) -> Result<(), Error> {
+        tx.check_legal_hold(E::ENTITY_TYPE, self.entity.entity_id())
+            .await?;
+
+        let db_event = DBEvent::try_from(&self.event)?;
+
+        db_event.persist(tx).await?;
+
+        ED::Builder::run_trigger(&self.entity, &self.event, tx).await?;
+
+        self.entity.delete(tx).await?;
+
+        Ok(())
+    }
+
+    async fn delete(self, store: &SqlxPgStore) -> Result<(), Error> {
+        let mut tx = store.transaction().await?;
+
+        self.stage_delete(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, EV> StorageBuilderPersist<SqlxPgStore, E> for BatchBuilder<E, EV>
+where
+    E: Persistable<SqlxPgStoreTransaction> + Send + Sync,
+    EV: BatchTriggers<E, SqlxPgStoreTransaction>,
+{
+    async fn stage_persist(self, tx: &mut SqlxPgStoreTransaction) -> Result<E, Error> {
+        for db_event in self.db_events()? {
+            db_event.persist(tx).await?;
+        }
+
+        let new = self.entity.persist(tx).await?;
+
+        self.events.run_triggers(&new, tx).await?;
+
+        Ok(new)
+    }
+
+    async fn persist(self, store: &SqlxPgStore) -> Result<E, Error> {
+        let mut tx = store.transaction().await?;
+
+        let new = self.stage_persist(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(new)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, EV> DeleteBuilderPersist<SqlxPgStore> for DeleteBatchBuilder<E, EV>
+where
+    E: Deletable<SqlxPgStoreTransaction> + Entity + Send + Sync,
+    EV: BatchTriggers<E, SqlxPgStoreTransaction>,
+{
+    async fn stage_delete(self, tx: &mut SqlxPgStoreTransaction) -> Result<(), Error> {
+        tx.check_legal_hold(E::ENTITY_TYPE, self.entity.entity_id())
+            .await?;
+
+        for db_event in self.db_events()? {
+            db_event.persist(tx).await?;
+        }
+
+        self.events.run_triggers(&self.entity, tx).await?;
+
+        self.entity.delete(tx).await
+    }
+
+    async fn delete(self, store: &SqlxPgStore) -> Result<(), Error> {
+        let mut tx = store.transaction().await?;
+
+        self.stage_delete(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Report what purging an entity would remove, returning the report along with the entity's events
+///
+/// This only reads from the store. The number of aggregate rows is left for the caller to fill in.
+async fn purge_report<E>(
+    entity_id: Uuid,
+    tx: &mut SqlxPgStoreTransaction,
+) -> Result<(PurgeReport, Vec<DBEvent>), Error>
+where
+    E: Purgeable,
+{
+    let events: Vec<DBEvent> = sqlx::query_as(concat!(
+        select_events!(),
+        r#"
+        where entity_id = $1
+        order by sequence_number asc"#
+    ))
+    .bind(entity_id)
+    .fetch_all(tx.get())
+    .await?;
+
+    if events.is_empty() {
+        return Err(Error::NotFound {
+            entity_type: E::entity_type(),
+            entity_id,
+        });
+    }
+
+    let mut report = PurgeReport::new(E::entity_type(), entity_id, &events);
+
+    // Other entities' events in the same request, or caused by one of this entity's events
+    let related: Vec<(String, Uuid)> = sqlx::query_as(
+        r#"select distinct entity_type, entity_id from events
+        where entity_id <> $1
+        and (
+            correlation_id in (select correlation_id from events where entity_id = $1)
+            or causation_id in (select id from events where entity_id = $1)
+        )
+        order by entity_type, entity_id"#,
+    )
+    .bind(entity_id)
+    .fetch_all(tx.get())
+    .await?;
+
+    report.related_entities = related
+        .into_iter()
+        .map(|(entity_type, entity_id)| RelatedEntity {
+            entity_type,
+            entity_id,
+        })
+        .collect();
+
+    Ok((report, events))
+}
+
+/// Purge an entity in a given transaction, returning a receipt of what was removed
+async fn stage_purge_receipt<E, ED>(
+    builder: PurgeBuilder<E, ED>,
+    tx: &mut SqlxPgStoreTransaction,
+) -> Result<PurgeReceipt, Error>
+where
+    E: Purgeable + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, SqlxPgStoreTransaction>,
+{
+    let entity_id = builder.entity.entity_id();
+
+    tx.check_legal_hold(E::ENTITY_TYPE, entity_id).await?;
+
+    let db_event = DBEvent::try_from(&builder.event)?;
+
+    let (mut report, events) = purge_report::<E>(entity_id, tx).await?;
+
+    report.aggregate_rows = purge::purge_aggregate::<E>(entity_id, tx).await?;
+
+    match &builder.partial {
+        Some(partial) => {
+            // The remaining fields are stored in plaintext, as the entity's key is destroyed below
+            for mut event in events {
+                partial.apply(&mut event)?;
+
+                sqlx::query(
+                    r#"update events set
+                    data = $1,
+                    encrypted = false,
+                    purged_at = $2,
+                    purger_id = $3
+                    where id = $4"#,
+                )
+                .bind(event.data)
+                .bind(db_event.created_at)
+                .bind(db_event.session_id)
+                .bind(event.id)
+                .execute(tx.get())
+                .await?;
+            }
+        }
+        None => {
+            // Payloads encrypted with the entity's key are left in place, as they can't be read
+            // once the key is destroyed
+            sqlx::query(
+                r#"update events set
+                data = case when encrypted then data end,
+                purged_at = $1,
+                purger_id = $2
+                where entity_id = $3"#,
+            )
+            .bind(db_event.created_at)
+            .bind(db_event.session_id)
+            .bind(entity_id)
+            .execute(tx.get())
+            .await?;
+        }
+    }
+
+    // Snapshots contain the entity's data so must be removed too
+    sqlx::query("delete from snapshots where entity_id = $1")
+        .bind(entity_id)
+        .execute(tx.get())
+        .await?;
+
+    sqlx::query("delete from encryption_keys where entity_id = $1")
+        .bind(entity_id)
+        .execute(tx.get())
+        .await?;
+
+    db_event.persist(tx).await?;
+
+    ED::Builder::run_trigger(&builder.entity, &builder.event, tx).await?;
+
+    Ok(builder.receipt(report))
+}
+
+#[async_trait::async_trait]
+impl<E, ED> PurgeBuilderExecute<SqlxPgStore> for PurgeBuilder<E, ED>
+where
+    E: Purgeable + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, SqlxPgStoreTransaction>,
+{
+    async fn stage_purge(self, tx: &mut SqlxPgStoreTransaction) -> Result<(), Error> {
+        stage_purge_receipt(self, tx).await?;
+
+        Ok(())
+    }
+
+    async fn purge(self, store: &SqlxPgStore) -> Result<(), Error> {
+        let mut tx = store.transaction().await?;
+
+        self.stage_purge(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> PurgeBuilderReport<SqlxPgStore> for PurgeBuilder<E, ED>
+where
+    E: Purgeable + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, SqlxPgStoreTransaction>,
+{
+    async fn dry_run(self, store: &SqlxPgStore) -> Result<PurgeReport, Error> {
+        let entity_id = self.entity.entity_id();
+
+        let mut tx = store.transaction().await?;
+
+        tx.check_legal_hold(E::ENTITY_TYPE, entity_id).await?;
+
+        let (mut report, _events) = purge_report::<E>(entity_id, &mut tx).await?;
+
+        report.aggregate_rows = purge::count_aggregate::<E>(entity_id, &mut tx).await?;
+
+        tx.rollback().await?;
+
+        Ok(report)
+    }
+
+    async fn purge_with_receipt(self, store: &SqlxPgStore) -> Result<PurgeReceipt, Error> {
+        let mut tx = store.transaction().await?;
+
+        let receipt = stage_purge_receipt(self, &mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(receipt)
+    }
+}
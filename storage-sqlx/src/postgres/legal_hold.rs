@@ -0,0 +1,119 @@
+//! Storage of legal holds in Postgres
+
+use super::{SqlxPgStore, SqlxPgStoreTransaction};
+use crate::{Error, LegalHold};
+use event_sauce::Persistable;
+use uuid::Uuid;
+
+/// Name of the advisory locks held on entities while their holds are placed, released or checked
+const HOLD_LOCK: &str = "event_sauce_legal_hold";
+
+#[async_trait::async_trait]
+impl Persistable<SqlxPgStoreTransaction> for LegalHold {
+    async fn persist(self, tx: &mut SqlxPgStoreTransaction) -> Result<Self, Error> {
+        tx.lock_held_entity(self.held_entity_id).await?;
+
+        let saved = sqlx::query_as(
+            r#"insert into legal_holds (
+                id,
+                held_entity_type,
+                held_entity_id,
+                reason,
+                placed_by,
+                placed_at,
+                released_by,
+                released_at
+            ) values (
+                $1,
+                $2,
+                $3,
+                $4,
+                $5,
+                $6,
+                $7,
+                $8
+            )
+            on conflict (id)
+            do update set
+            released_by = excluded.released_by,
+            released_at = excluded.released_at
+            returning *"#,
+        )
+        .bind(self.id)
+        .bind(self.held_entity_type)
+        .bind(self.held_entity_id)
+        .bind(self.reason)
+        .bind(self.placed_by)
+        .bind(self.placed_at)
+        .bind(self.released_by)
+        .bind(self.released_at)
+        .fetch_one(tx.get())
+        .await?;
+
+        Ok(saved)
+    }
+}
+
+impl SqlxPgStore {
+    /// Get the active legal holds placed on an entity
+    pub async fn legal_holds(&self, entity_id: Uuid) -> Result<Vec<LegalHold>, Error> {
+        let holds = sqlx::query_as(
+            r#"select * from legal_holds
+            where held_entity_id = $1 and released_at is null
+            order by placed_at asc"#,
+        )
+        .bind(entity_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(holds)
+    }
+}
+
+impl SqlxPgStoreTransaction {
+    /// Lock an entity's holds until the transaction ends
+    ///
+    /// A row lock can't stop a hold from being placed, as there is no row to lock until it is, so
+    /// an advisory lock on the held entity's ID is used instead.
+    async fn lock_held_entity(&mut self, entity_id: Uuid) -> Result<(), Error> {
+        sqlx::query("select pg_advisory_xact_lock(hashtext($1), hashtext($2::text))")
+            .bind(HOLD_LOCK)
+            .bind(entity_id)
+            .execute(self.get())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fail with [`Error::LegalHold`] if the given entity has an active legal hold
+    ///
+    /// The entity's holds are locked until the transaction ends, so a hold can't be placed or
+    /// released part way through.
+    pub(super) async fn check_legal_hold(
+        &mut self,
+        entity_type: &str,
+        entity_id: Uuid,
+    ) -> Result<(), Error> {
+        self.lock_held_entity(entity_id).await?;
+
+        let hold: Option<(Uuid,)> = sqlx::query_as(
+            r#"select id from legal_holds
+            where held_entity_type = $1 and held_entity_id = $2 and released_at is null
+            order by placed_at asc
+            limit 1"#,
+        )
+        .bind(entity_type)
+        .bind(entity_id)
+        .fetch_optional(self.get())
+        .await?;
+
+        match hold {
+            Some((hold_id,)) => Err(Error::LegalHold {
+                entity_type: entity_type.to_string(),
+                entity_id,
+                hold_id,
+            }),
+            None => Ok(()),
+        }
+    }
+}
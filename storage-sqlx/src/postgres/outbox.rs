@@ -0,0 +1,204 @@
+//! Publish stored events to other systems through a transactional outbox
+
+use super::SqlxPgStore;
+use crate::Error;
+use event_sauce::DBEvent;
+use std::time::Duration;
+
+/// A destination for events relayed from the outbox, like a message queue or webhook
+#[async_trait::async_trait]
+pub trait OutboxPublisher {
+    /// Publish a single event
+    ///
+    /// If an error is returned, the event is retried later. Events may be published more than once
+    /// if the relay is interrupted, so consumers should be idempotent.
+    async fn publish(
+        &self,
+        event: &DBEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Reads pending events from the outbox and hands them to an [`OutboxPublisher`]
+///
+/// Events are only added to the outbox by stores created with [`SqlxPgStore::with_outbox`].
+/// Failed events are retried with exponential backoff until `max_attempts` is reached, after which
+/// they remain in the outbox with their last error for manual inspection.
+///
+/// Multiple relays may run against the same database; each pending event is claimed by one of
+/// them at a time. Events are published outside of any transaction, so a slow publisher doesn't
+/// hold database connections or locks. If a relay stops before marking a claimed event as
+/// delivered, the event is handed out again once its claim times out.
+///
+/// Delivered events remain in the outbox until they are removed with [`OutboxRelay::prune`].
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use event_sauce_storage_sqlx::OutboxRelay;
+///
+/// let relay = OutboxRelay::new(&store).max_attempts(5);
+///
+/// loop {
+///     if relay.relay(&publisher).await? == 0 {
+///         relay.prune(Duration::from_secs(7 * 24 * 60 * 60)).await?;
+///
+///         async_std::task::sleep(Duration::from_secs(1)).await;
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OutboxRelay {
+    store: SqlxPgStore,
+    batch_size: i64,
+    max_attempts: i32,
+    base_backoff: Duration,
+    max_backoff: Duration,
+    claim_timeout: Duration,
+}
+
+impl OutboxRelay {
+    /// Create a new relay reading from the given store's outbox
+    pub fn new(store: &SqlxPgStore) -> Self {
+        Self {
+            store: store.clone(),
+            batch_size: 100,
+            max_attempts: 10,
+            base_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60 * 60),
+            claim_timeout: Duration::from_secs(5 * 60),
+        }
+    }
+
+    /// Set the maximum number of events relayed in one call to [`OutboxRelay::relay`]. Defaults
+    /// to `100`.
+    pub fn batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+
+        self
+    }
+
+    /// Set the number of times publishing an event is attempted before giving up. Defaults to
+    /// `10`.
+    pub fn max_attempts(mut self, max_attempts: i32) -> Self {
+        self.max_attempts = max_attempts;
+
+        self
+    }
+
+    /// Set the delay before the first retry of a failed event, and the maximum delay between
+    /// retries
+    ///
+    /// The delay doubles after every failed attempt. Defaults to 1 second and 1 hour respectively.
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_backoff = base;
+        self.max_backoff = max;
+
+        self
+    }
+
+    /// Set how long a batch of events claimed by [`OutboxRelay::relay`] is hidden from other
+    /// relays. Defaults to 5 minutes.
+    ///
+    /// This should be longer than it takes to publish a whole batch, otherwise events may be
+    /// published more than once.
+    pub fn claim_timeout(mut self, claim_timeout: Duration) -> Self {
+        self.claim_timeout = claim_timeout;
+
+        self
+    }
+
+    /// Publish the next batch of pending events in the order they were stored
+    ///
+    /// Returns the number of events handed to the publisher, whether or not publishing succeeded.
+    pub async fn relay<P>(&self, publisher: &P) -> Result<usize, Error>
+    where
+        P: OutboxPublisher + Sync,
+    {
+        // The batch is claimed by pushing back its next attempt, so other relays skip it while it's
+        // being published. Rows locked by another relay claiming them are skipped too.
+        let events: Vec<DBEvent> = sqlx::query_as(concat!(
+            r#"with claimed as (
+                update outbox set
+                next_attempt_at = now() + $3 * interval '1 millisecond'
+                where event_id in (
+                    select outbox.event_id from outbox
+                    join events on events.id = outbox.event_id
+                    where outbox.delivered_at is null
+                    and outbox.next_attempt_at <= now()
+                    and outbox.attempts < $1
+                    order by events.sequence_number asc
+                    limit $2
+                    for update of outbox skip locked
+                )
+                returning event_id
+            )
+            "#,
+            select_events!(),
+            r#"
+            join claimed on claimed.event_id = events.id
+            order by events.sequence_number asc"#
+        ))
+        .bind(self.max_attempts)
+        .bind(self.batch_size)
+        .bind(self.claim_timeout.as_millis() as i64)
+        .fetch_all(&self.store.pool)
+        .await?;
+
+        let count = events.len();
+
+        for event in events {
+            let id = event.id;
+
+            // Each result is saved as soon as it's known, so events that were published aren't
+            // published again if a later one fails to be saved
+            match publisher.publish(&self.store.upcasters.upcast(event)).await {
+                Ok(()) => {
+                    sqlx::query("update outbox set delivered_at = now() where event_id = $1")
+                        .bind(id)
+                        .execute(&self.store.pool)
+                        .await?;
+
+                    log::trace!("Relayed outbox event {}", id);
+                }
+                Err(e) => {
+                    // `attempts` is the number of failures before this one, so the first retry
+                    // waits for the base backoff
+                    sqlx::query(
+                        r#"update outbox set
+                        attempts = attempts + 1,
+                        last_error = $2,
+                        next_attempt_at = now() + least($3 * power(2, attempts), $4) * interval '1 millisecond'
+                        where event_id = $1"#,
+                    )
+                    .bind(id)
+                    .bind(e.to_string())
+                    .bind(self.base_backoff.as_millis() as i64)
+                    .bind(self.max_backoff.as_millis() as i64)
+                    .execute(&self.store.pool)
+                    .await?;
+
+                    log::warn!("Failed to relay outbox event {}: {}", id, e);
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Remove events from the outbox that were delivered longer ago than `older_than`
+    ///
+    /// The events themselves are kept in the event log. Returns the number of outbox rows removed.
+    pub async fn prune(&self, older_than: Duration) -> Result<u64, Error> {
+        let removed = sqlx::query(
+            "delete from outbox where delivered_at < now() - $1 * interval '1 millisecond'",
+        )
+        .bind(older_than.as_millis() as i64)
+        .execute(&self.store.pool)
+        .await?
+        .rows_affected();
+
+        log::trace!("Pruned {} delivered outbox events", removed);
+
+        Ok(removed)
+    }
+}
@@ -0,0 +1,201 @@
+//! Coordinate workflows spanning multiple entities by reacting to events
+
+use super::{SqlxPgStore, SqlxPgStoreTransaction, Subscription};
+use crate::Error;
+use event_sauce::{DBEvent, EnumEventData, Event};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A long running process that reacts to events, for example creating an organisation and
+/// sending an invite once a user signs up
+///
+/// Each instance of the process is identified by the correlation ID of the events it receives, or
+/// the event's own ID if it has no correlation ID. Its state is stored in the `process_states`
+/// table, and is loaded and saved in the same transaction as the handled event's checkpoint.
+///
+/// New events should be built with [`EventBuilder::caused_by`](event_sauce::EventBuilder::caused_by)
+/// so they share the correlation ID of the event that caused them, and can be persisted using the
+/// transaction passed to [`ProcessManager::handle`] with
+/// [`StorageBuilderPersist::stage_persist`](event_sauce::StorageBuilderPersist::stage_persist).
+/// Commands can be run in the same transaction with [`SqlxPgStore::stage_execute`].
+///
+/// Process managers are run with [`SqlxPgStore::run_process_manager`].
+#[async_trait::async_trait]
+pub trait ProcessManager: Sync {
+    /// The events this process manager handles
+    ///
+    /// Must have a variant for every event type received through
+    /// [`ProcessManager::subscription`].
+    type Event: EnumEventData + for<'de> Deserialize<'de> + Send + Sync;
+
+    /// The state kept for each instance of the process
+    ///
+    /// New instances start from the default state.
+    type State: Serialize + for<'de> Deserialize<'de> + Default + Send + Sync;
+
+    /// The subscription used to receive events
+    ///
+    /// The subscription's name identifies this process manager's state and checkpoint, so must
+    /// remain the same between restarts.
+    fn subscription(&self) -> Subscription;
+
+    /// Handle an event, updating the process's state
+    ///
+    /// Returning an error rolls back the transaction, and the event is handled again on the next
+    /// call to [`SqlxPgStore::run_process_manager`].
+    async fn handle(
+        &self,
+        event: &Event<Self::Event>,
+        state: &mut Self::State,
+        store: &SqlxPgStore,
+        tx: &mut SqlxPgStoreTransaction,
+    ) -> Result<(), Error>;
+
+    /// Whether the process has finished
+    ///
+    /// Events received for a finished process are skipped. Defaults to `false`.
+    fn is_complete(&self, _state: &Self::State) -> bool {
+        false
+    }
+}
+
+impl SqlxPgStore {
+    /// Hand every event stored since the process manager's last checkpoint to it
+    ///
+    /// Each event is handled in its own transaction, along with loading and saving the process's
+    /// state and updating the checkpoint. Returns the number of events handled, excluding purged
+    /// events and events for finished processes.
+    pub async fn run_process_manager<P>(&self, manager: &P) -> Result<usize, Error>
+    where
+        P: ProcessManager,
+    {
+        let subscription = manager.subscription();
+
+        let events = self.subscribe(&subscription);
+        futures::pin_mut!(events);
+
+        let mut handled = 0;
+
+        while let Some(event) = events.try_next().await? {
+            if self
+                .handle_process_event(manager, &subscription, event)
+                .await?
+            {
+                handled += 1;
+            }
+        }
+
+        Ok(handled)
+    }
+
+    /// Get the current state of a process manager's instance, if it has received any events
+    pub async fn process_state<P>(
+        &self,
+        manager: &P,
+        correlation_id: Uuid,
+    ) -> Result<Option<P::State>, Error>
+    where
+        P: ProcessManager,
+    {
+        let state: Option<(serde_json::Value,)> = sqlx::query_as(
+            "select state from process_states where process = $1 and correlation_id = $2",
+        )
+        .bind(manager.subscription().name())
+        .bind(correlation_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(state
+            .map(|(state,)| serde_json::from_value(state))
+            .transpose()?)
+    }
+
+    /// Handle a single event, returning whether it was passed to the process manager
+    async fn handle_process_event<P>(
+        &self,
+        manager: &P,
+        subscription: &Subscription,
+        db_event: DBEvent,
+    ) -> Result<bool, Error>
+    where
+        P: ProcessManager,
+    {
+        let sequence_number = db_event.sequence_number.unwrap_or(0);
+        let correlation_id = db_event.correlation_id.unwrap_or(db_event.id);
+
+        let mut tx = self.transaction().await?;
+
+        // Purged events have no data to handle
+        let handled = if db_event.data.is_some() {
+            let event = Event::<P::Event>::try_from_db_event(db_event)?;
+
+            // Locked so concurrent runners can't lose each other's changes
+            let existing: Option<(serde_json::Value, bool)> = sqlx::query_as(
+                r#"select state, completed_at is not null from process_states
+                where process = $1 and correlation_id = $2
+                for update"#,
+            )
+            .bind(subscription.name())
+            .bind(correlation_id)
+            .fetch_optional(tx.get())
+            .await?;
+
+            match existing {
+                Some((_, true)) => false,
+                existing => {
+                    let mut state = match existing {
+                        Some((state, _)) => serde_json::from_value(state)?,
+                        None => P::State::default(),
+                    };
+
+                    manager.handle(&event, &mut state, self, &mut tx).await?;
+
+                    sqlx::query(
+                        r#"insert into process_states (
+                            process,
+                            correlation_id,
+                            state,
+                            completed_at,
+                            updated_at
+                        ) values (
+                            $1,
+                            $2,
+                            $3,
+                            case when $4 then now() else null end,
+                            now()
+                        )
+                        on conflict (process, correlation_id)
+                        do update set
+                        state = excluded.state,
+                        completed_at = excluded.completed_at,
+                        updated_at = excluded.updated_at"#,
+                    )
+                    .bind(subscription.name())
+                    .bind(correlation_id)
+                    .bind(serde_json::to_value(&state)?)
+                    .bind(manager.is_complete(&state))
+                    .execute(tx.get())
+                    .await?;
+
+                    log::trace!(
+                        "Process {} {} handled event {}",
+                        subscription.name(),
+                        correlation_id,
+                        event.id
+                    );
+
+                    true
+                }
+            }
+        } else {
+            false
+        };
+
+        tx.save_checkpoint(subscription, sequence_number).await?;
+
+        tx.commit().await?;
+
+        Ok(handled)
+    }
+}
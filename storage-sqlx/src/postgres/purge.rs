@@ -0,0 +1,52 @@
+//! Removal of an entity's aggregate state when it is purged
+
+use super::SqlxPgStoreTransaction;
+use crate::{purge::aggregate_tables, Error};
+use event_sauce::Purgeable;
+use uuid::Uuid;
+
+/// Remove an entity's aggregate state, returning the number of rows removed
+pub(crate) async fn purge_aggregate<E>(
+    entity_id: Uuid,
+    tx: &mut SqlxPgStoreTransaction,
+) -> Result<u64, Error>
+where
+    E: Purgeable,
+{
+    let mut removed = 0;
+
+    for (table, column) in aggregate_tables::<E>() {
+        removed += sqlx::query(&format!("delete from {} where {} = $1", table, column))
+            .bind(entity_id)
+            .execute(tx.get())
+            .await?
+            .rows_affected();
+    }
+
+    Ok(removed)
+}
+
+/// Count the rows of an entity's aggregate state that purging it would remove
+pub(crate) async fn count_aggregate<E>(
+    entity_id: Uuid,
+    tx: &mut SqlxPgStoreTransaction,
+) -> Result<u64, Error>
+where
+    E: Purgeable,
+{
+    let mut count = 0;
+
+    for (table, column) in aggregate_tables::<E>() {
+        let rows: (i64,) = sqlx::query_as(&format!(
+            "select count(*) from {} where {} = $1",
+            table, column
+        ))
+        .bind(entity_id)
+        .fetch_one(tx.get())
+        .await?;
+
+        count += rows.0 as u64;
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,289 @@
+//! Filtered, paginated and streaming queries against the Postgres event log
+
+use super::SqlxPgStore;
+use crate::Error;
+use event_sauce::{DBEvent, EnumEventData, Event};
+use futures::{Stream, TryStreamExt};
+use serde::Deserialize;
+use sqlx::types::chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Build a query of the `events` table, sorting by position in the given direction and
+/// continuing from a cursor with the given comparison
+macro_rules! events_query {
+    ($order:literal, $cmp:literal) => {
+        concat!(
+            select_events!(),
+            r#"
+            where (cardinality($1::uuid[]) = 0 or entity_id = any($1))
+            and (cardinality($2::varchar[]) = 0 or entity_type = any($2))
+            and (cardinality($3::varchar[]) = 0 or event_type = any($3))
+            and ($4::uuid is null or session_id = $4)
+            and ($5::timestamptz is null or created_at >= $5)
+            and ($6::timestamptz is null or created_at < $6)
+            and ($7::bool is null or (purged_at is not null) = $7)
+            and "#,
+            after_position!($cmp, "$8"),
+            r#"
+            order by transaction_id "#,
+            $order,
+            r#", sequence_number "#,
+            $order,
+            r#"
+            limit $9"#
+        )
+    };
+}
+
+/// A query of the event log
+///
+/// Events can be filtered by any combination of entity ID, entity type, event type, session ID,
+/// creation time and purge status. If no filters are given, every event is matched. Results are
+/// ordered by position in the event log, ascending by default. See
+/// [`Subscription`](super::Subscription#ordering-and-visibility) for how events are ordered, and
+/// when recently stored events become visible.
+///
+/// Large result sets should be fetched a page at a time by passing the sequence number of the
+/// last event of the previous page to [`EventQuery::after`]. Unlike offset based pagination,
+/// this stays fast however deep into the log the page is, and events stored between pages are
+/// not skipped or returned twice. Alternatively, [`SqlxPgStore::stream_events`] reads every
+/// matching event from a cursor in constant memory.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use event_sauce_storage_sqlx::EventQuery;
+///
+/// let mut query = EventQuery::new()
+///     .entity_type("users")
+///     .event_type("UserCreated")
+///     .limit(100);
+///
+/// loop {
+///     let page = store.query_events(&query).await?;
+///
+///     // ...
+///
+///     match page.last().and_then(|event| event.sequence_number) {
+///         Some(last) => query = query.after(last),
+///         None => break,
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EventQuery {
+    entity_ids: Vec<Uuid>,
+    entity_types: Vec<String>,
+    event_types: Vec<String>,
+    session_id: Option<Uuid>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    purged: Option<bool>,
+    descending: bool,
+    after: Option<i32>,
+    limit: Option<i64>,
+}
+
+impl EventQuery {
+    /// Create a new query matching every event
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match events for the given entity ID
+    ///
+    /// This may be called multiple times to match events for multiple entities.
+    pub fn entity_id(mut self, entity_id: Uuid) -> Self {
+        self.entity_ids.push(entity_id);
+
+        self
+    }
+
+    /// Only match events for the given entity type
+    ///
+    /// This may be called multiple times to match events for multiple entity types.
+    pub fn entity_type(mut self, entity_type: &str) -> Self {
+        self.entity_types.push(entity_type.to_string());
+
+        self
+    }
+
+    /// Only match events of the given event type, like `UserCreated`
+    ///
+    /// This may be called multiple times to match multiple event types.
+    pub fn event_type(mut self, event_type: &str) -> Self {
+        self.event_types.push(event_type.to_string());
+
+        self
+    }
+
+    /// Only match events created by the given session
+    pub fn session_id(mut self, session_id: Uuid) -> Self {
+        self.session_id = Some(session_id);
+
+        self
+    }
+
+    /// Only match events created at or after the given time
+    pub fn created_after(mut self, created_after: DateTime<Utc>) -> Self {
+        self.created_after = Some(created_after);
+
+        self
+    }
+
+    /// Only match events created before the given time
+    pub fn created_before(mut self, created_before: DateTime<Utc>) -> Self {
+        self.created_before = Some(created_before);
+
+        self
+    }
+
+    /// Only match purged events if `true`, or events that have not been purged if `false`
+    pub fn purged(mut self, purged: bool) -> Self {
+        self.purged = Some(purged);
+
+        self
+    }
+
+    /// Return the most recent events first
+    pub fn descending(mut self) -> Self {
+        self.descending = true;
+
+        self
+    }
+
+    /// Only match events that come after the event with the given sequence number in the
+    /// query's order
+    ///
+    /// When paginating, this should be set to the sequence number of the last event of the
+    /// previous page.
+    pub fn after(mut self, sequence_number: i32) -> Self {
+        self.after = Some(sequence_number);
+
+        self
+    }
+
+    /// Set the maximum number of events returned. By default, every matching event is returned.
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+
+        self
+    }
+
+    /// Get the SQL for this query, with the filters bound by [`EventQuery::bind`]
+    fn sql(&self) -> &'static str {
+        if self.descending {
+            events_query!("desc", "<")
+        } else {
+            events_query!("asc", ">")
+        }
+    }
+
+    /// Bind this query's filters to its SQL
+    fn bind<'q>(
+        &'q self,
+    ) -> sqlx::query::QueryAs<'q, sqlx::Postgres, DBEvent, sqlx::postgres::PgArguments> {
+        sqlx::query_as(self.sql())
+            .bind(&self.entity_ids)
+            .bind(&self.entity_types)
+            .bind(&self.event_types)
+            .bind(self.session_id)
+            .bind(self.created_after)
+            .bind(self.created_before)
+            .bind(self.purged)
+            .bind(self.after)
+            .bind(self.limit)
+    }
+}
+
+impl SqlxPgStore {
+    /// Fetch every event matching the query
+    ///
+    /// Upcasters are applied to each event, so their payloads are at the latest schema version.
+    pub async fn query_events(&self, query: &EventQuery) -> Result<Vec<DBEvent>, Error> {
+        let events = query.bind().fetch_all(&self.pool).await?;
+
+        Ok(events
+            .into_iter()
+            .map(|event| self.upcasters.upcast(event))
+            .collect())
+    }
+
+    /// Fetch every event matching the query, deserializing their payloads into `EDENUM`
+    ///
+    /// The query should be filtered to event types that `EDENUM` has variants for, otherwise
+    /// this will fail with [`Error::Serde`]. Purged events are returned with an empty payload.
+    pub async fn query_events_as<EDENUM>(
+        &self,
+        query: &EventQuery,
+    ) -> Result<Vec<Event<EDENUM>>, Error>
+    where
+        EDENUM: EnumEventData + for<'de> Deserialize<'de>,
+    {
+        self.query_events(query)
+            .await?
+            .into_iter()
+            .map(decode)
+            .collect()
+    }
+
+    /// Stream every event matching the query
+    ///
+    /// Unlike [`SqlxPgStore::query_events`], events are read from a database cursor as the
+    /// stream is polled instead of being fetched all at once, so arbitrarily large parts of the
+    /// event log can be processed in constant memory. This is useful for rebuilding projections
+    /// from scratch.
+    ///
+    /// The stream holds a connection from the pool until it is dropped or ends. Upcasters are
+    /// applied to each event.
+    pub fn stream_events<'a>(
+        &'a self,
+        query: &'a EventQuery,
+    ) -> impl Stream<Item = Result<DBEvent, Error>> + Send + 'a {
+        query
+            .bind()
+            .fetch(&self.pool)
+            .map_err(Error::from)
+            .map_ok(move |event| self.upcasters.upcast(event))
+    }
+
+    /// Stream every event matching the query, deserializing their payloads into `EDENUM`
+    ///
+    /// See [`SqlxPgStore::stream_events`] and [`SqlxPgStore::query_events_as`].
+    pub fn stream_events_as<'a, EDENUM>(
+        &'a self,
+        query: &'a EventQuery,
+    ) -> impl Stream<Item = Result<Event<EDENUM>, Error>> + Send + 'a
+    where
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send + 'a,
+    {
+        self.stream_events(query)
+            .and_then(|event| futures::future::ready(decode(event)))
+    }
+}
+
+/// Deserialize a stored event's payload, leaving it empty if the event was purged
+fn decode<EDENUM>(db_event: DBEvent) -> Result<Event<EDENUM>, Error>
+where
+    EDENUM: EnumEventData + for<'de> Deserialize<'de>,
+{
+    if db_event.data.is_some() {
+        return Ok(Event::try_from_db_event(db_event)?);
+    }
+
+    Ok(Event {
+        id: db_event.id,
+        event_type: db_event.event_type,
+        entity_type: db_event.entity_type,
+        entity_id: db_event.entity_id,
+        version: db_event.version,
+        session_id: db_event.session_id,
+        correlation_id: db_event.correlation_id,
+        causation_id: db_event.causation_id,
+        metadata: serde_json::from_value(db_event.metadata)?,
+        purger_id: db_event.purger_id,
+        data: None,
+        created_at: db_event.created_at,
+        purged_at: db_event.purged_at,
+    })
+}
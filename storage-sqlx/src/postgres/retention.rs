@@ -0,0 +1,277 @@
+//! Purge entities automatically once their retention period has passed
+
+use super::{SqlxPgStore, SqlxPgStoreTransaction};
+use crate::Error;
+use event_sauce::{
+    ActionEntityBuilder, AggregateAction, EnumEventData, EventBuilder, EventData,
+    PurgeBuilderReport, PurgeEntityBuilder, PurgeEventBuilder, PurgeReceipt, Purgeable, RunTrigger,
+    StorageBackendLoad,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// When entities become eligible to be purged by a [`RetentionRunner`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetentionPolicy {
+    /// Purge entities once no events have been stored against them for the given period
+    SinceLastEvent(Duration),
+
+    /// Purge entities the given period after the latest event of the given type was stored
+    ///
+    /// This is usually the entity's delete event, for example
+    /// `RetentionPolicy::SinceEvent("UserDeleted", Duration::from_secs(30 * 24 * 60 * 60))`.
+    /// Entities without an event of this type are never purged.
+    SinceEvent(&'static str, Duration),
+}
+
+/// An entity that is purged automatically after a retention period
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use event_sauce_storage_sqlx::{Retention, RetentionPolicy};
+///
+/// impl Retention for User {
+///     const RETENTION: RetentionPolicy =
+///         RetentionPolicy::SinceEvent("UserDeleted", Duration::from_secs(30 * 24 * 60 * 60));
+/// }
+/// ```
+pub trait Retention: Purgeable {
+    /// When entities of this type become eligible to be purged
+    const RETENTION: RetentionPolicy;
+}
+
+/// Finds entities whose [`Retention`] period has passed and purges them
+///
+/// Entities are loaded with [`StorageBackendLoad::load`], then purged in the same way as
+/// [`PurgeBuilderExecute::purge`](event_sauce::PurgeBuilderExecute::purge). The purge event is
+/// stored with the runner's system session ID. Entities under an active
+/// [`LegalHold`](crate::LegalHold) are skipped.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use event_sauce_storage_sqlx::RetentionRunner;
+///
+/// let runner = RetentionRunner::new(&store, system_session_id)
+///     .entity::<UserEventData, _>(UserPurged)
+///     .entity::<OrganisationEventData, _>(OrganisationPurged);
+///
+/// loop {
+///     let receipts = runner.run().await?;
+///
+///     if receipts.is_empty() {
+///         async_std::task::sleep(Duration::from_secs(60 * 60)).await;
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RetentionRunner {
+    store: SqlxPgStore,
+    session_id: Uuid,
+    batch_size: i64,
+    entities: Vec<Arc<dyn RetainedEntity>>,
+
+    /// The last entity ID of each type returned by the previous run, so entities that fail to
+    /// purge don't stop the entities after them from being reached
+    cursors: Arc<Mutex<HashMap<&'static str, Uuid>>>,
+}
+
+impl RetentionRunner {
+    /// Create a new runner purging entities from the given store
+    ///
+    /// `session_id` is stored as the session of each purge event, identifying the system as the
+    /// purger.
+    pub fn new(store: &SqlxPgStore, session_id: Uuid) -> Self {
+        Self {
+            store: store.clone(),
+            session_id,
+            batch_size: 100,
+            entities: Vec::new(),
+            cursors: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Set the maximum number of entities of each type purged in one call to
+    /// [`RetentionRunner::run`]. Defaults to `100`.
+    pub fn batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+
+        self
+    }
+
+    /// Purge entities of the type `ED` purges according to their [`Retention`] policy
+    ///
+    /// `EDENUM` must have a variant for every event type stored against the entity, so it can be
+    /// loaded before being purged. `purge_event` is the payload of every purge event stored.
+    pub fn entity<EDENUM, ED>(mut self, purge_event: ED) -> Self
+    where
+        ED: EventData + Clone + Send + Sync + 'static,
+        ED::Entity: Retention
+            + ActionEntityBuilder<EDENUM>
+            + PurgeEntityBuilder<ED>
+            + Send
+            + Sync
+            + 'static,
+        <ED::Entity as AggregateAction<EDENUM>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send + 'static,
+        ED::Builder: RunTrigger<ED::Entity, ED, SqlxPgStoreTransaction>,
+    {
+        self.entities.push(Arc::new(Retained::<EDENUM, ED> {
+            purge_event,
+            events: PhantomData,
+        }));
+
+        self
+    }
+
+    /// Purge the next batch of entities of each type whose retention period has passed
+    ///
+    /// Returns the receipts of the purged entities. Entities that fail to load or purge are
+    /// logged and skipped. Each run continues from the last entity of the previous run, so they
+    /// are retried once the runner has been through every other eligible entity of their type.
+    pub async fn run(&self) -> Result<Vec<PurgeReceipt>, Error> {
+        let mut receipts = Vec::new();
+
+        for entity in self.entities.iter() {
+            for entity_id in self.eligible(entity.as_ref()).await? {
+                match entity.purge(&self.store, entity_id, self.session_id).await {
+                    Ok(Some(receipt)) => receipts.push(receipt),
+                    Ok(None) => log::warn!(
+                        "{} entity {} could not be loaded to purge it",
+                        entity.entity_type(),
+                        entity_id
+                    ),
+                    Err(e) => log::warn!(
+                        "Failed to purge {} entity {}: {}",
+                        entity.entity_type(),
+                        entity_id,
+                        e
+                    ),
+                }
+            }
+        }
+
+        Ok(receipts)
+    }
+
+    /// Find the IDs of entities whose retention period has passed
+    async fn eligible(&self, entity: &dyn RetainedEntity) -> Result<Vec<Uuid>, Error> {
+        let (event_type, period) = match entity.policy() {
+            RetentionPolicy::SinceLastEvent(period) => (None, period),
+            RetentionPolicy::SinceEvent(event_type, period) => (Some(event_type), period),
+        };
+
+        let cursor = self.cursors().get(entity.entity_type()).copied();
+
+        // Entities that have already been purged have events with `purged_at` set
+        let entity_ids: Vec<(Uuid,)> = sqlx::query_as(
+            r#"select entity_id from events
+            where entity_type = $1
+            and ($5::uuid is null or entity_id > $5)
+            and entity_id not in (
+                select held_entity_id from legal_holds
+                where held_entity_type = $1 and released_at is null
+            )
+            group by entity_id
+            having count(purged_at) = 0
+            and max(created_at) filter (where $2::varchar is null or event_type = $2)
+                < now() - $3 * interval '1 second'
+            order by entity_id
+            limit $4"#,
+        )
+        .bind(entity.entity_type())
+        .bind(event_type)
+        .bind(period.as_secs_f64())
+        .bind(self.batch_size)
+        .bind(cursor)
+        .fetch_all(&self.store.pool)
+        .await?;
+
+        let entity_ids = entity_ids
+            .into_iter()
+            .map(|(entity_id,)| entity_id)
+            .collect::<Vec<_>>();
+
+        // Start from the first entity again once the last page has been reached
+        match entity_ids.last() {
+            Some(last) if entity_ids.len() as i64 >= self.batch_size => {
+                self.cursors().insert(entity.entity_type(), *last)
+            }
+            _ => self.cursors().remove(entity.entity_type()),
+        };
+
+        Ok(entity_ids)
+    }
+
+    /// Lock the cursors of each entity type
+    fn cursors(&self) -> MutexGuard<'_, HashMap<&'static str, Uuid>> {
+        // The cursors are always left in a valid state, so a poisoned lock can be ignored
+        self.cursors.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// An entity type registered with a [`RetentionRunner`], with its types erased
+#[async_trait::async_trait]
+trait RetainedEntity: Send + Sync {
+    fn entity_type(&self) -> &'static str;
+
+    fn policy(&self) -> RetentionPolicy;
+
+    /// Load and purge an entity, returning `None` if it can't be loaded
+    async fn purge(
+        &self,
+        store: &SqlxPgStore,
+        entity_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<Option<PurgeReceipt>, Error>;
+}
+
+struct Retained<EDENUM, ED> {
+    purge_event: ED,
+    events: PhantomData<fn() -> EDENUM>,
+}
+
+#[async_trait::async_trait]
+impl<EDENUM, ED> RetainedEntity for Retained<EDENUM, ED>
+where
+    ED: EventData + Clone + Send + Sync,
+    ED::Entity: Retention + ActionEntityBuilder<EDENUM> + PurgeEntityBuilder<ED> + Send + Sync,
+    <ED::Entity as AggregateAction<EDENUM>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send,
+    ED::Builder: RunTrigger<ED::Entity, ED, SqlxPgStoreTransaction>,
+{
+    fn entity_type(&self) -> &'static str {
+        <ED::Entity as event_sauce::Entity>::ENTITY_TYPE
+    }
+
+    fn policy(&self) -> RetentionPolicy {
+        ED::Entity::RETENTION
+    }
+
+    async fn purge(
+        &self,
+        store: &SqlxPgStore,
+        entity_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<Option<PurgeReceipt>, Error> {
+        let entity = match store.load::<ED::Entity, EDENUM>(entity_id).await? {
+            Some(entity) => entity,
+            None => return Ok(None),
+        };
+
+        let receipt = entity
+            .try_purge(PurgeEventBuilder::new(self.purge_event.clone()).session_id(session_id))
+            .purge_with_receipt(store)
+            .await?;
+
+        Ok(Some(receipt))
+    }
+}
@@ -0,0 +1,347 @@
+//! Catch-up and live subscriptions to the Postgres event log
+
+use super::{SqlxPgStore, SqlxPgStoreTransaction};
+use crate::Error;
+use event_sauce::DBEvent;
+use futures::{stream, Stream, TryStreamExt};
+use sqlx::{postgres::PgListener, PgExecutor};
+use std::time::Duration;
+
+/// The channel notified with an event's sequence number whenever one is stored
+const NOTIFY_CHANNEL: &str = "events";
+
+/// A named consumer of the event log
+///
+/// Each subscription stores its position in the log as a checkpoint, so processing can resume
+/// where it left off after a restart. Events can be filtered by entity type and event type; if no
+/// filters are given, every event is received.
+///
+/// # Ordering and visibility
+///
+/// Sequence numbers are assigned when events are inserted, but transactions don't always commit
+/// in the same order. Reading the log in sequence number order could therefore skip an event that
+/// is committed after a later one has already been read.
+///
+/// Instead, events are read in the order of the transactions that stored them, and in sequence
+/// number order within a transaction. Events stored by a transaction are only returned once every
+/// transaction that started before it has ended, so an event can never become visible behind one
+/// that has already been read. This means events may be held back for as long as an older
+/// transaction stays open, even if that transaction doesn't store any events. Events are only
+/// returned in plain sequence number order if their transactions didn't overlap.
+///
+/// This applies to [`SqlxPgStore::subscribe`], [`SqlxPgStore::tail`] and
+/// [`EventQuery`](super::EventQuery).
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// use event_sauce_storage_sqlx::Subscription;
+/// use futures::TryStreamExt;
+///
+/// let subscription = Subscription::new("user_emails")
+///     .entity_type("users")
+///     .event_type("UserCreated");
+///
+/// let mut events = store.subscribe(&subscription);
+///
+/// while let Some(event) = events.try_next().await? {
+///     // ...
+///
+///     store.save_checkpoint(&subscription, event.sequence_number.unwrap_or(0)).await?;
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    name: String,
+    entity_types: Vec<String>,
+    event_types: Vec<String>,
+    batch_size: i64,
+    poll_interval: Duration,
+}
+
+impl Subscription {
+    /// Create a new subscription with a unique name
+    ///
+    /// The name identifies the subscription's checkpoint, so must remain the same between restarts.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            entity_types: Vec::new(),
+            event_types: Vec::new(),
+            batch_size: 100,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Only receive events for the given entity type
+    ///
+    /// This may be called multiple times to receive events for multiple entity types.
+    pub fn entity_type(mut self, entity_type: &str) -> Self {
+        self.entity_types.push(entity_type.to_string());
+
+        self
+    }
+
+    /// Only receive events of the given event type, like `UserCreated`
+    ///
+    /// This may be called multiple times to receive multiple event types.
+    pub fn event_type(mut self, event_type: &str) -> Self {
+        self.event_types.push(event_type.to_string());
+
+        self
+    }
+
+    /// Set the maximum number of events fetched from the database at once. Defaults to `100`.
+    pub fn batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+
+        self
+    }
+
+    /// Set how often [`SqlxPgStore::tail`] reads the log when no notifications are received.
+    /// Defaults to one second.
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+
+        self
+    }
+
+    /// Get the subscription's name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl SqlxPgStore {
+    /// Stream all events matching the subscription that were stored after its checkpoint
+    ///
+    /// Events are returned in the order described in
+    /// [`Subscription`](Subscription#ordering-and-visibility), so none are skipped by a checkpoint.
+    /// The stream ends once it has caught up with the end of the event log. The checkpoint is not
+    /// updated automatically; call [`SqlxPgStore::save_checkpoint`] once an event has been
+    /// processed. Events may therefore be received more than once if processing is interrupted, so
+    /// consumers should be idempotent.
+    pub fn subscribe<'a>(
+        &'a self,
+        subscription: &'a Subscription,
+    ) -> impl Stream<Item = Result<DBEvent, Error>> + Send + 'a {
+        let batches = stream::try_unfold(None, move |position: Option<i32>| async move {
+            let position = match position {
+                Some(position) => position,
+                None => self.checkpoint(subscription).await?,
+            };
+
+            let batch = self.events_after(subscription, position).await?;
+
+            let next = batch
+                .last()
+                .and_then(|event| event.sequence_number)
+                .map(|last| (batch, Some(last)));
+
+            Ok::<_, Error>(next)
+        });
+
+        batches
+            .map_ok(|batch| stream::iter(batch.into_iter().map(Ok)))
+            .try_flatten()
+    }
+
+    /// Stream all events matching the subscription that were stored after its checkpoint, then
+    /// wait for new events to be committed
+    ///
+    /// This behaves like [`SqlxPgStore::subscribe`], but instead of ending once the end of the
+    /// event log is reached, it listens for notifications of newly committed events. The stream
+    /// never ends unless an error occurs.
+    ///
+    /// Events held back by an open transaction, as described in
+    /// [`Subscription`](Subscription#ordering-and-visibility), don't send another notification
+    /// once it ends, so the log is also read again every
+    /// [`poll_interval`](Subscription::poll_interval).
+    ///
+    /// Notifications are only sent once [`SqlxPgStore::enable_notifications`] has been called.
+    /// Without them, new events are only received when the log is polled.
+    pub async fn tail<'a>(
+        &'a self,
+        subscription: &'a Subscription,
+    ) -> Result<impl Stream<Item = Result<DBEvent, Error>> + Send + 'a, Error> {
+        // Start listening before the historical read so no events are missed in between
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(NOTIFY_CHANNEL).await?;
+
+        let position = self.checkpoint(subscription).await?;
+
+        let batches = stream::try_unfold(
+            (listener, position),
+            move |(mut listener, position)| async move {
+                loop {
+                    let batch = self.events_after(subscription, position).await?;
+
+                    if let Some(last) = batch.last().and_then(|event| event.sequence_number) {
+                        return Ok::<_, Error>(Some((batch, (listener, last))));
+                    }
+
+                    // Caught up, so wait for another event to be committed or the poll interval to
+                    // pass. If the connection was lost, notifications may have been missed so the
+                    // log is read again. The wait is boxed to keep the stream's state small.
+                    if let Ok(notification) = async_std::future::timeout(
+                        subscription.poll_interval,
+                        Box::pin(listener.try_recv()),
+                    )
+                    .await
+                    {
+                        notification?;
+                    }
+                }
+            },
+        );
+
+        Ok(batches
+            .map_ok(|batch| stream::iter(batch.into_iter().map(Ok)))
+            .try_flatten())
+    }
+
+    /// Notify listeners whenever an event is committed to the event log
+    ///
+    /// This installs a trigger on the `events` table that calls `pg_notify` for every inserted
+    /// event, which is required by [`SqlxPgStore::tail`]. It only needs to be called once per
+    /// database, but is safe to call multiple times.
+    pub async fn enable_notifications(&self) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(&format!(
+            r#"
+            create or replace function events_notify() returns trigger as $$
+            begin
+                perform pg_notify('{}', new.sequence_number::text);
+                return new;
+            end;
+            $$ language plpgsql;
+        "#,
+            NOTIFY_CHANNEL
+        ))
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query("drop trigger if exists events_notify on events")
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            create trigger events_notify
+            after insert on events
+            for each row execute procedure events_notify();
+        "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Fetch the next batch of events matching the subscription that come after the event with
+    /// the given sequence number
+    pub async fn events_after(
+        &self,
+        subscription: &Subscription,
+        sequence_number: i32,
+    ) -> Result<Vec<DBEvent>, Error> {
+        let events: Vec<DBEvent> = sqlx::query_as(concat!(
+            select_events!(),
+            r#"
+            where "#,
+            after_position!(">", "$1"),
+            r#"
+            and (cardinality($2::varchar[]) = 0 or entity_type = any($2))
+            and (cardinality($3::varchar[]) = 0 or event_type = any($3))
+            order by transaction_id asc, sequence_number asc
+            limit $4"#
+        ))
+        .bind(sequence_number)
+        .bind(&subscription.entity_types)
+        .bind(&subscription.event_types)
+        .bind(subscription.batch_size)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events
+            .into_iter()
+            .map(|event| self.upcasters.upcast(event))
+            .collect())
+    }
+
+    /// Get the sequence number of the last event processed by the subscription
+    ///
+    /// Returns `0` if no checkpoint has been saved.
+    pub async fn checkpoint(&self, subscription: &Subscription) -> Result<i32, Error> {
+        let checkpoint: Option<(i32,)> =
+            sqlx::query_as("select sequence_number from checkpoints where subscription = $1")
+                .bind(&subscription.name)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(checkpoint.map_or(0, |(sequence_number,)| sequence_number))
+    }
+
+    /// Store the sequence number of the last event processed by the subscription
+    ///
+    /// Saving a lower sequence number than the current checkpoint rewinds the subscription, which
+    /// can be used to rebuild a read model from scratch.
+    pub async fn save_checkpoint(
+        &self,
+        subscription: &Subscription,
+        sequence_number: i32,
+    ) -> Result<(), Error> {
+        save_checkpoint(&self.pool, subscription, sequence_number).await
+    }
+}
+
+impl SqlxPgStoreTransaction {
+    /// Store the sequence number of the last event processed by the subscription as part of this
+    /// transaction
+    ///
+    /// This allows a read model to be updated and its checkpoint saved atomically.
+    pub async fn save_checkpoint(
+        &mut self,
+        subscription: &Subscription,
+        sequence_number: i32,
+    ) -> Result<(), Error> {
+        save_checkpoint(self.get(), subscription, sequence_number).await
+    }
+}
+
+async fn save_checkpoint<'e>(
+    executor: impl PgExecutor<'e>,
+    subscription: &Subscription,
+    sequence_number: i32,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"insert into checkpoints (
+            subscription,
+            sequence_number,
+            updated_at
+        ) values (
+            $1,
+            $2,
+            now()
+        )
+        on conflict (subscription)
+        do update set
+        sequence_number = excluded.sequence_number,
+        updated_at = excluded.updated_at"#,
+    )
+    .bind(&subscription.name)
+    .bind(sequence_number)
+    .execute(executor)
+    .await?;
+
+    log::trace!(
+        "Saved checkpoint for subscription {} at sequence number {}",
+        subscription.name,
+        sequence_number
+    );
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+//! Removal of an entity's aggregate state when it is purged
+
+use event_sauce::Purgeable;
+
+/// Get the tables holding an entity's aggregate state, along with the column holding the entity's
+/// ID, in the order their rows must be removed
+///
+/// Identifiers are quoted so they can be used in a query.
+pub(crate) fn aggregate_tables<E>() -> Vec<(String, String)>
+where
+    E: Purgeable,
+{
+    E::DEPENDENT_TABLES
+        .iter()
+        .chain(&[(E::AGGREGATE_TABLE, E::AGGREGATE_ID_COLUMN)])
+        .map(|(table, column)| (quote_identifier(table), quote_identifier(column)))
+        .collect()
+}
+
+/// Quote a possibly schema-qualified identifier so it can be used in a query
+fn quote_identifier(identifier: &str) -> String {
+    identifier
+        .split('.')
+        .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
@@ -0,0 +1,877 @@
+//! SQLite storage backend
+
+use crate::{purge::aggregate_tables, Error, LegalHold};
+use event_sauce::StorageBackendTransaction;
+use event_sauce::{
+    ActionEntityBuilder, AggregateAction, AggregateConflict, AggregateUpdate, BatchBuilder,
+    BatchTriggers, CheckedUpdate, CheckedUpdateBuilder, Command, CommandError, CommandHandler,
+    ConflictCheck, ConflictData, ConflictEntityBuilder, ConflictEventBuilder, DBEvent, Deletable,
+    DeleteBatchBuilder, DeleteBuilder, DeleteBuilderPersist, Entity, EnumEventData, Event,
+    EventData, Persistable, PurgeBuilder, PurgeBuilderExecute, PurgeBuilderReport, PurgeReceipt,
+    PurgeReport, Purgeable, RelatedEntity, RunEnumTrigger, RunTrigger, StorageBackend,
+    StorageBackendExecute, StorageBackendLoad, StorageBackendUpdateChecked, StorageBuilder,
+    StorageBuilderPersist, Upcasters, UpdateEntityBuilder, UpdateEventBuilder,
+};
+use serde::Deserialize;
+use sqlx::Transaction;
+use sqlx::{Sqlite, SqliteExecutor, SqlitePool};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+/// Columns added to the `events` table since it was first released, along with their definitions
+///
+/// These are added to existing tables when a store is created. Events stored before a column was
+/// added get its default value.
+const EVENTS_ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("schema_version", "integer not null default 1"),
+    ("correlation_id", "blob null"),
+    ("causation_id", "blob null"),
+    ("metadata", "text not null default '{}'"),
+];
+
+/// [sqlx](https://docs.rs/sqlx)-based SQLite backing store
+#[derive(Debug, Clone)]
+pub struct SqlxSqliteStore {
+    /// sqlx [`SqlitePool`](sqlx::SqlitePool) to communicate with the database
+    pub pool: SqlitePool,
+
+    /// Upcasters applied to stored events before they are deserialized
+    upcasters: Upcasters,
+}
+
+impl SqlxSqliteStore {
+    /// Create a new transaction
+    pub async fn transaction(&self) -> Result<SqlxSqliteStoreTransaction, sqlx::Error> {
+        let tx = self.pool.begin().await?;
+
+        Ok(SqlxSqliteStoreTransaction(tx))
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackend for SqlxSqliteStore {
+    type Error = Error;
+    type Transaction = SqlxSqliteStoreTransaction;
+}
+
+#[async_trait::async_trait]
+impl StorageBackendLoad for SqlxSqliteStore {
+    async fn load<E, EDENUM>(&self, entity_id: Uuid) -> Result<Option<E>, Error>
+    where
+        E: ActionEntityBuilder<EDENUM> + Send,
+        E::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send,
+    {
+        let (entity, _) = self
+            .load_with_version::<E, EDENUM>(&self.pool, entity_id)
+            .await?;
+
+        Ok(entity)
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackendExecute for SqlxSqliteStore {
+    async fn execute<H, C>(&self, handler: &H, command: C) -> Result<Option<C::Entity>, Error>
+    where
+        H: CommandHandler<C> + Sync,
+        C: Command + Send + Sync,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Entity: Persistable<SqlxSqliteStoreTransaction> + Send + Sync,
+        <C::Entity as AggregateAction<C::Event>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Event: RunEnumTrigger<C::Entity, SqlxSqliteStoreTransaction>
+            + for<'de> Deserialize<'de>
+            + Send
+            + Sync,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match self.try_execute(handler, &command).await {
+                Err(Error::VersionConflict { entity_id, .. }) if attempt < C::MAX_ATTEMPTS => {
+                    log::debug!(
+                        "Entity {} was modified concurrently, retrying command (attempt {})",
+                        entity_id,
+                        attempt
+                    );
+
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackendUpdateChecked for SqlxSqliteStore {
+    async fn update_checked<E, EDENUM, ED, B>(
+        &self,
+        entity_id: Uuid,
+        base_version: i32,
+        builder: B,
+    ) -> Result<CheckedUpdate<E>, Error>
+    where
+        E: ActionEntityBuilder<EDENUM>
+            + UpdateEntityBuilder<ED>
+            + ConflictEntityBuilder<EDENUM, ED>
+            + AggregateUpdate<ED, Output = E>
+            + Persistable<SqlxSqliteStoreTransaction>
+            + Send
+            + Sync,
+        <E as AggregateAction<EDENUM>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        <E as AggregateUpdate<ED>>::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        <E as AggregateConflict<EDENUM, ED>>::Error: Into<<E as AggregateUpdate<ED>>::Error>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de> + Send + Sync,
+        ED: ConflictCheck<EDENUM> + Send + Sync,
+        ED::Builder: RunTrigger<E, ED, SqlxSqliteStoreTransaction>,
+        ConflictEventBuilder<EDENUM, ED>:
+            RunTrigger<E, ConflictData<EDENUM, ED>, SqlxSqliteStoreTransaction>,
+        B: Into<UpdateEventBuilder<ED>> + Send,
+    {
+        let (entity, version) = self
+            .load_with_version::<E, EDENUM>(&self.pool, entity_id)
+            .await?;
+
+        let entity = entity.ok_or_else(|| Error::NotFound {
+            entity_type: E::entity_type(),
+            entity_id,
+        })?;
+
+        let applied_events: Vec<DBEvent> = sqlx::query_as(
+            r#"select * from events
+            where entity_type = ?1 and entity_id = ?2 and version > ?3 and version <= ?4
+            order by sequence_number asc"#,
+        )
+        .bind(E::entity_type())
+        .bind(entity_id)
+        .bind(base_version)
+        .bind(version)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let applied_events = applied_events
+            .into_iter()
+            .map(|event| Event::try_from_db_event(self.upcasters.upcast(event)))
+            .collect::<Result<Vec<Event<EDENUM>>, _>>()?;
+
+        let checked =
+            UpdateEntityBuilder::<ED>::try_update_checked(entity, builder, applied_events)
+                .map_err(|e| Error::Aggregate(e.into()))?;
+
+        // Persisting fails if another event was stored since the entity was loaded, as it
+        // hasn't been checked for conflicts
+        match checked {
+            CheckedUpdateBuilder::Update(builder) => Ok(CheckedUpdate::Updated(
+                builder.expected_version(version).persist(self).await?,
+            )),
+            CheckedUpdateBuilder::Conflict(builder) => Ok(CheckedUpdate::Conflicted(
+                builder.expected_version(version).persist(self).await?,
+            )),
+        }
+    }
+}
+
+impl SqlxSqliteStore {
+    /// Load an entity along with the current version of its event stream
+    async fn load_with_version<'e, E, EDENUM>(
+        &self,
+        executor: impl SqliteExecutor<'e>,
+        entity_id: Uuid,
+    ) -> Result<(Option<E>, i32), Error>
+    where
+        E: ActionEntityBuilder<EDENUM>,
+        E::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        EDENUM: EnumEventData + for<'de> Deserialize<'de>,
+    {
+        let db_events: Vec<DBEvent> = sqlx::query_as(
+            "select * from events where entity_type = ?1 and entity_id = ?2 order by sequence_number asc",
+        )
+        .bind(E::entity_type())
+        .bind(entity_id)
+        .fetch_all(executor)
+        .await?;
+
+        let version = db_events
+            .iter()
+            .filter_map(|event| event.version)
+            .max()
+            .unwrap_or(0);
+
+        // Purged entities no longer exist, and their event payloads can't be replayed
+        if db_events.iter().any(|event| event.purged_at.is_some()) {
+            return Ok((None, version));
+        }
+
+        let events = db_events
+            .into_iter()
+            .map(|event| Event::try_from_db_event(self.upcasters.upcast(event)))
+            .collect::<Result<Vec<Event<EDENUM>>, _>>()?;
+
+        let entity = E::try_replay(events).map_err(|e| Error::Aggregate(e.into()))?;
+
+        Ok((entity, version))
+    }
+
+    /// Run a command once, failing with [`Error::VersionConflict`] if the entity was modified
+    /// since it was loaded
+    async fn try_execute<H, C>(&self, handler: &H, command: &C) -> Result<Option<C::Entity>, Error>
+    where
+        H: CommandHandler<C> + Sync,
+        C: Command + Send + Sync,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Entity: Persistable<SqlxSqliteStoreTransaction> + Send + Sync,
+        <C::Entity as AggregateAction<C::Event>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Event: RunEnumTrigger<C::Entity, SqlxSqliteStoreTransaction>
+            + for<'de> Deserialize<'de>
+            + Send
+            + Sync,
+    {
+        let mut tx = self.transaction().await?;
+
+        let (entity, version) = match command.entity_id() {
+            Some(entity_id) => {
+                self.load_with_version::<C::Entity, C::Event>(tx.get(), entity_id)
+                    .await?
+            }
+            None => (None, 0),
+        };
+
+        let (entity, events) =
+            handler
+                .try_decide(command, entity, version)
+                .map_err(|e| match e {
+                    CommandError::Rejected(e) => Error::Rejected(e.into()),
+                    CommandError::Aggregate(e) => Error::Aggregate(e.into()),
+                })?;
+
+        let entity = match entity {
+            Some(entity) if !events.is_empty() => entity,
+            // Nothing changed
+            entity => return Ok(entity),
+        };
+
+        for event in events.iter() {
+            DBEvent::try_from_enum_event(event)?
+                .persist(&mut tx)
+                .await?;
+        }
+
+        let entity = entity.persist(&mut tx).await?;
+
+        for event in events {
+            C::Event::run_enum_trigger(&entity, event, &mut tx).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(entity))
+    }
+}
+
+/// A transaction against a [`SqlxSqliteStore`]
+pub struct SqlxSqliteStoreTransaction(Transaction<'static, Sqlite>);
+
+impl SqlxSqliteStoreTransaction {
+    /// Get the underlying sqlx transaction
+    pub fn get(&mut self) -> &mut Transaction<'static, Sqlite> {
+        &mut self.0
+    }
+
+    /// Commit the transaction
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        self.0.commit().await?;
+
+        Ok(())
+    }
+
+    /// Discard all changes made in this transaction
+    pub async fn rollback(self) -> Result<(), sqlx::Error> {
+        self.0.rollback().await?;
+
+        Ok(())
+    }
+}
+
+impl SqlxSqliteStoreTransaction {
+    /// Fail with [`Error::LegalHold`] if the given entity has an active legal hold
+    ///
+    /// SQLite transactions are serializable, so if a hold is placed by another transaction after
+    /// this check, one of the two transactions fails instead of both succeeding.
+    async fn check_legal_hold(&mut self, entity_type: &str, entity_id: Uuid) -> Result<(), Error> {
+        let hold: Option<(Uuid,)> = sqlx::query_as(
+            r#"select id from legal_holds
+            where held_entity_type = ?1 and held_entity_id = ?2 and released_at is null
+            order by placed_at asc
+            limit 1"#,
+        )
+        .bind(entity_type)
+        .bind(entity_id)
+        .fetch_optional(self.get())
+        .await?;
+
+        match hold {
+            Some((hold_id,)) => Err(Error::LegalHold {
+                entity_type: entity_type.to_string(),
+                entity_id,
+                hold_id,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl StorageBackendTransaction for SqlxSqliteStoreTransaction {
+    type Error = Error;
+}
+
+impl SqlxSqliteStore {
+    /// Create a new backing store instance with a given [`SqlitePool`](sqlx::SqlitePool)
+    pub async fn new(pool: SqlitePool) -> Result<Self, sqlx::Error> {
+        Self::create_events_table(&pool).await?;
+
+        Ok(Self {
+            pool,
+            upcasters: Upcasters::default(),
+        })
+    }
+
+    /// Upgrade events stored with an older schema version using the given upcasters when loading
+    /// entities
+    pub fn with_upcasters(mut self, upcasters: Upcasters) -> Self {
+        self.upcasters = upcasters;
+
+        self
+    }
+
+    async fn create_events_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(r#"
+            create table if not exists events(
+                -- Events are read back in insertion order, so the sequence number must never be
+                -- reused.
+                sequence_number integer primary key autoincrement,
+                id blob not null unique,
+                event_type varchar(64) not null,
+                entity_type varchar(64) not null,
+                entity_id blob not null,
+                -- This field is null if the event is purged, in such case purged_at and purger_id should be populated.
+                data text,
+                session_id blob null,
+                -- Tracing IDs linking the event to the request, command or event that caused it
+                correlation_id blob null,
+                causation_id blob null,
+                metadata text not null default '{}',
+                created_at text not null,
+                purger_id blob null,
+                purged_at text null,
+                -- Position of the event in its entity's stream. Unique per entity to prevent
+                -- concurrent writers from interleaving events.
+                version integer null,
+                -- Schema version of the event payload, used to select upcasters when reading
+                schema_version integer not null default 1,
+                unique (entity_id, version)
+            );
+        "#).execute(&mut tx).await?;
+
+        // Bring tables created by older versions up to date. SQLite can't add a column only if it
+        // doesn't exist, so the existing columns are checked first.
+        let columns: Vec<(String,)> =
+            sqlx::query_as("select name from pragma_table_info('events')")
+                .fetch_all(&mut tx)
+                .await?;
+
+        for (column, definition) in EVENTS_ADDED_COLUMNS {
+            if columns.iter().all(|(name,)| name != column) {
+                sqlx::query(&format!(
+                    "alter table events add column {} {}",
+                    column, definition
+                ))
+                .execute(&mut tx)
+                .await?;
+            }
+        }
+
+        sqlx::query(
+            "create unique index if not exists events_entity_id_version on events (entity_id, version)",
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            create table if not exists legal_holds(
+                id blob primary key,
+                -- The held entity, which can't be deleted or purged until the hold is released
+                held_entity_type varchar(64) not null,
+                held_entity_id blob not null,
+                reason text not null,
+                placed_by blob null,
+                placed_at text not null,
+                released_by blob null,
+                released_at text null
+            );
+        "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "create index if not exists legal_holds_active on legal_holds (held_entity_id) where released_at is null",
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Persistable<SqlxSqliteStoreTransaction, DBEvent> for DBEvent {
+    async fn persist(self, store: &mut SqlxSqliteStoreTransaction) -> Result<Self, Error> {
+        let entity_id = self.entity_id;
+        let expected_version = self.version.map(|version| version - 1);
+
+        // The event is appended to the end of the entity's stream. If the event has a version set,
+        // nothing is inserted unless that version is the next one in the stream, or the event is
+        // already stored at that version and is only being persisted again.
+        let saved: Option<Self> = sqlx::query_as(
+            r#"insert into events (
+                id,
+                event_type,
+                entity_type,
+                entity_id,
+                data,
+                session_id,
+                created_at,
+                version,
+                schema_version,
+                correlation_id,
+                causation_id,
+                metadata
+            )
+            select
+                ?1,
+                ?2,
+                ?3,
+                ?4,
+                ?5,
+                ?6,
+                ?7,
+                stream.next_version,
+                ?9,
+                ?10,
+                ?11,
+                ?12
+            from (
+                select coalesce(max(version), 0) + 1 as next_version
+                from events
+                where entity_id = ?4
+            ) as stream
+            where ?8 is null
+            or stream.next_version = ?8
+            or exists (select 1 from events where id = ?1 and version = ?8)
+            on conflict (id)
+            do update set
+            data = excluded.data
+            returning *"#,
+        )
+        .bind(self.id)
+        .bind(self.event_type)
+        .bind(self.entity_type)
+        .bind(self.entity_id)
+        .bind(self.data)
+        .bind(self.session_id)
+        .bind(self.created_at)
+        .bind(self.version)
+        .bind(self.schema_version)
+        .bind(self.correlation_id)
+        .bind(self.causation_id)
+        .bind(self.metadata)
+        .fetch_optional(store.get())
+        .await
+        .map_err(|e| match e {
+            // Another transaction inserted an event at the same version first
+            // (`SQLITE_CONSTRAINT_UNIQUE`)
+            sqlx::Error::Database(ref db_error) if db_error.code().as_deref() == Some("2067") => {
+                Error::VersionConflict {
+                    entity_id,
+                    expected_version,
+                }
+            }
+            e => Error::Sqlx(e),
+        })?;
+
+        let saved = saved.ok_or(Error::VersionConflict {
+            entity_id,
+            expected_version,
+        })?;
+
+        log::trace!("Persisted event {}: {:?}", saved.id, saved);
+
+        Ok(saved)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> StorageBuilderPersist<SqlxSqliteStore, E> for StorageBuilder<E, ED>
+where
+    E: Persistable<SqlxSqliteStoreTransaction> + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, SqlxSqliteStoreTransaction>,
+{
+    async fn stage_persist(self, tx: &mut SqlxSqliteStoreTransaction) -> Result<E, Error> {
+        let db_event = DBEvent::try_from(&self.event)?;
+
+        db_event.persist(tx).await?;
+
+        let new = self.entity.persist(tx).await?;
+
+        ED::Builder::run_trigger(&new, &self.event, tx).await?;
+
+        Ok(new)
+    }
+
+    async fn persist(self, store: &SqlxSqliteStore) -> Result<E, Error> {
+        let mut tx = store.transaction().await?;
+
+        let new = self.stage_persist(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(new)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> DeleteBuilderPersist<SqlxSqliteStore> for DeleteBuilder<E, ED>
+where
+    E: Deletable<SqlxSqliteStoreTransaction> + Entity + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, SqlxSqliteStoreTransaction>,
+{
+    async fn stage_delete(self, tx: &mut SqlxSqliteStoreTransaction) -> Result<(), Error> {
+        tx.check_legal_hold(E::ENTITY_TYPE, self.entity.entity_id())
+            .await?;
+
+        let db_event = DBEvent::try_from(&self.event)?;
+
+        db_event.persist(tx).await?;
+
+        ED::Builder::run_trigger(&self.entity, &self.event, tx).await?;
+
+        self.entity.delete(tx).await?;
+
+        Ok(())
+    }
+
+    async fn delete(self, store: &SqlxSqliteStore) -> Result<(), Error> {
+        let mut tx = store.transaction().await?;
+
+        self.stage_delete(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, EV> StorageBuilderPersist<SqlxSqliteStore, E> for BatchBuilder<E, EV>
+where
+    E: Persistable<SqlxSqliteStoreTransaction> + Send + Sync,
+    EV: BatchTriggers<E, SqlxSqliteStoreTransaction>,
+{
+    async fn stage_persist(self, tx: &mut SqlxSqliteStoreTransaction) -> Result<E, Error> {
+        for db_event in self.db_events()? {
+            db_event.persist(tx).await?;
+        }
+
+        let new = self.entity.persist(tx).await?;
+
+        self.events.run_triggers(&new, tx).await?;
+
+        Ok(new)
+    }
+
+    async fn persist(self, store: &SqlxSqliteStore) -> Result<E, Error> {
+        let mut tx = store.transaction().await?;
+
+        let new = self.stage_persist(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(new)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, EV> DeleteBuilderPersist<SqlxSqliteStore> for DeleteBatchBuilder<E, EV>
+where
+    E: Deletable<SqlxSqliteStoreTransaction> + Entity + Send + Sync,
+    EV: BatchTriggers<E, SqlxSqliteStoreTransaction>,
+{
+    async fn stage_delete(self, tx: &mut SqlxSqliteStoreTransaction) -> Result<(), Error> {
+        tx.check_legal_hold(E::ENTITY_TYPE, self.entity.entity_id())
+            .await?;
+
+        for db_event in self.db_events()? {
+            db_event.persist(tx).await?;
+        }
+
+        self.events.run_triggers(&self.entity, tx).await?;
+
+        self.entity.delete(tx).await
+    }
+
+    async fn delete(self, store: &SqlxSqliteStore) -> Result<(), Error> {
+        let mut tx = store.transaction().await?;
+
+        self.stage_delete(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Report what purging an entity would remove
+///
+/// This only reads from the store, so it can be run before the purge in the same transaction.
+async fn purge_report<E>(
+    entity_id: Uuid,
+    tx: &mut SqlxSqliteStoreTransaction,
+) -> Result<PurgeReport, Error>
+where
+    E: Purgeable,
+{
+    let events: Vec<DBEvent> =
+        sqlx::query_as("select * from events where entity_id = ?1 order by sequence_number asc")
+            .bind(entity_id)
+            .fetch_all(tx.get())
+            .await?;
+
+    if events.is_empty() {
+        return Err(Error::NotFound {
+            entity_type: E::entity_type(),
+            entity_id,
+        });
+    }
+
+    let mut report = PurgeReport::new(E::entity_type(), entity_id, &events);
+
+    // Other entities' events in the same request, or caused by one of this entity's events
+    let related: Vec<(String, Uuid)> = sqlx::query_as(
+        r#"select distinct entity_type, entity_id from events
+        where entity_id <> ?1
+        and (
+            correlation_id in (select correlation_id from events where entity_id = ?1)
+            or causation_id in (select id from events where entity_id = ?1)
+        )
+        order by entity_type, entity_id"#,
+    )
+    .bind(entity_id)
+    .fetch_all(tx.get())
+    .await?;
+
+    report.related_entities = related
+        .into_iter()
+        .map(|(entity_type, entity_id)| RelatedEntity {
+            entity_type,
+            entity_id,
+        })
+        .collect();
+
+    for (table, column) in aggregate_tables::<E>() {
+        let rows: (i64,) = sqlx::query_as(&format!(
+            "select count(*) from {} where {} = ?1",
+            table, column
+        ))
+        .bind(entity_id)
+        .fetch_one(tx.get())
+        .await?;
+
+        report.aggregate_rows += rows.0 as u64;
+    }
+
+    Ok(report)
+}
+
+#[async_trait::async_trait]
+impl<E, ED> PurgeBuilderExecute<SqlxSqliteStore> for PurgeBuilder<E, ED>
+where
+    E: Purgeable + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, SqlxSqliteStoreTransaction>,
+{
+    async fn stage_purge(self, tx: &mut SqlxSqliteStoreTransaction) -> Result<(), Error> {
+        tx.check_legal_hold(E::ENTITY_TYPE, self.entity.entity_id())
+            .await?;
+
+        let db_event = DBEvent::try_from(&self.event)?;
+
+        for (table, column) in aggregate_tables::<E>() {
+            sqlx::query(&format!("delete from {} where {} = ?1", table, column))
+                .bind(self.entity.entity_id())
+                .execute(tx.get())
+                .await?;
+        }
+
+        let purged = match &self.partial {
+            Some(partial) => {
+                let events: Vec<DBEvent> =
+                    sqlx::query_as("select * from events where entity_id = ?1")
+                        .bind(self.entity.entity_id())
+                        .fetch_all(tx.get())
+                        .await?;
+
+                let purged = events.len() as u64;
+
+                for mut event in events {
+                    partial.apply(&mut event)?;
+
+                    sqlx::query(
+                        "update events set data = ?1, purged_at = ?2, purger_id = ?3 where id = ?4",
+                    )
+                    .bind(event.data)
+                    .bind(db_event.created_at)
+                    .bind(db_event.session_id)
+                    .bind(event.id)
+                    .execute(tx.get())
+                    .await?;
+                }
+
+                purged
+            }
+            None => sqlx::query(
+                "update events set data = null, purged_at = ?1, purger_id = ?2 where entity_id = ?3",
+            )
+            .bind(db_event.created_at)
+            .bind(db_event.session_id)
+            .bind(self.entity.entity_id())
+            .execute(tx.get())
+            .await?
+            .rows_affected(),
+        };
+
+        if purged == 0 {
+            return Err(Error::NotFound {
+                entity_type: E::entity_type(),
+                entity_id: self.entity.entity_id(),
+            });
+        }
+
+        db_event.persist(tx).await?;
+
+        ED::Builder::run_trigger(&self.entity, &self.event, tx).await
+    }
+
+    async fn purge(self, store: &SqlxSqliteStore) -> Result<(), Error> {
+        let mut tx = store.transaction().await?;
+
+        self.stage_purge(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<E, ED> PurgeBuilderReport<SqlxSqliteStore> for PurgeBuilder<E, ED>
+where
+    E: Purgeable + Send + Sync,
+    ED: EventData + Send + Sync,
+    ED::Builder: RunTrigger<E, ED, SqlxSqliteStoreTransaction>,
+{
+    async fn dry_run(self, store: &SqlxSqliteStore) -> Result<PurgeReport, Error> {
+        let mut tx = store.transaction().await?;
+
+        tx.check_legal_hold(E::ENTITY_TYPE, self.entity.entity_id())
+            .await?;
+
+        let report = purge_report::<E>(self.entity.entity_id(), &mut tx).await?;
+
+        tx.rollback().await?;
+
+        Ok(report)
+    }
+
+    async fn purge_with_receipt(self, store: &SqlxSqliteStore) -> Result<PurgeReceipt, Error> {
+        let mut tx = store.transaction().await?;
+
+        // The report is taken in the purge's transaction, so it matches what the purge removes
+        let receipt = self.receipt(purge_report::<E>(self.entity.entity_id(), &mut tx).await?);
+
+        self.stage_purge(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(receipt)
+    }
+}
+
+#[async_trait::async_trait]
+impl Persistable<SqlxSqliteStoreTransaction> for LegalHold {
+    async fn persist(self, tx: &mut SqlxSqliteStoreTransaction) -> Result<Self, Error> {
+        let saved = sqlx::query_as(
+            r#"insert into legal_holds (
+                id,
+                held_entity_type,
+                held_entity_id,
+                reason,
+                placed_by,
+                placed_at,
+                released_by,
+                released_at
+            ) values (
+                ?1,
+                ?2,
+                ?3,
+                ?4,
+                ?5,
+                ?6,
+                ?7,
+                ?8
+            )
+            on conflict (id)
+            do update set
+            released_by = excluded.released_by,
+            released_at = excluded.released_at
+            returning *"#,
+        )
+        .bind(self.id)
+        .bind(self.held_entity_type)
+        .bind(self.held_entity_id)
+        .bind(self.reason)
+        .bind(self.placed_by)
+        .bind(self.placed_at)
+        .bind(self.released_by)
+        .bind(self.released_at)
+        .fetch_one(tx.get())
+        .await?;
+
+        Ok(saved)
+    }
+}
+
+impl SqlxSqliteStore {
+    /// Get the active legal holds placed on an entity
+    pub async fn legal_holds(&self, entity_id: Uuid) -> Result<Vec<LegalHold>, Error> {
+        let holds = sqlx::query_as(
+            r#"select * from legal_holds
+            where held_entity_id = ?1 and released_at is null
+            order by placed_at asc"#,
+        )
+        .bind(entity_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(holds)
+    }
+}
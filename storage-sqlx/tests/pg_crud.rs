@@ -2,7 +2,7 @@ use event_sauce::{
     prelude::*, AggregateCreate, AggregateUpdate, CreateEventBuilder, Deletable, Entity, Event,
     EventData, Persistable, UpdateEventBuilder,
 };
-use event_sauce_storage_sqlx::SqlxPgStoreTransaction;
+use event_sauce_storage_sqlx::{Error, SqlxPgStoreTransaction};
 // use event_sauce::UpdateEntity;
 use event_sauce_storage_sqlx::SqlxPgStore;
 use sqlx::PgPool;
@@ -57,7 +57,7 @@ impl EventData for UserEmailChanged {
 
 #[async_trait::async_trait]
 impl Persistable<SqlxPgStoreTransaction, User> for User {
-    async fn persist(self, tx: &mut SqlxPgStoreTransaction) -> Result<Self, sqlx::Error> {
+    async fn persist(self, tx: &mut SqlxPgStoreTransaction) -> Result<Self, Error> {
         let blah = format!(
             "insert into {}
                     (id, name, email)
@@ -84,7 +84,7 @@ impl Persistable<SqlxPgStoreTransaction, User> for User {
 
 #[async_trait::async_trait]
 impl Deletable<SqlxPgStoreTransaction> for User {
-    async fn delete(self, tx: &mut SqlxPgStoreTransaction) -> Result<(), sqlx::Error> {
+    async fn delete(self, tx: &mut SqlxPgStoreTransaction) -> Result<(), Error> {
         sqlx::query(&format!(
             "delete from {} where id = $1",
             User::entity_type()
@@ -204,3 +204,46 @@ async fn update() -> Result<(), sqlx::Error> {
 
     Ok(())
 }
+
+#[async_std::test]
+async fn create_and_update_batch() -> Result<(), sqlx::Error> {
+    let store = connect().await?;
+
+    // Create user and change their email address twice in one transaction
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .try_update(UserEmailChanged {
+        email: "beans@bob.by".to_string(),
+    })
+    .expect("Failed to update User from UserEmailChanged event")
+    .try_update(UserEmailChanged {
+        email: "bobby@beans.com".to_string(),
+    })
+    .expect("Failed to update User from UserEmailChanged event")
+    .persist(&store)
+    .await
+    .expect("Failed to persist");
+
+    assert_eq!(user.email, "bobby@beans.com".to_string());
+
+    let events: Vec<(String, Option<i32>)> = sqlx::query_as(
+        "select event_type, version from events where entity_id = $1 order by sequence_number asc",
+    )
+    .bind(user.id)
+    .fetch_all(&store.pool)
+    .await?;
+
+    assert_eq!(
+        events,
+        vec![
+            ("UserCreated".to_string(), Some(1)),
+            ("UserEmailChanged".to_string(), Some(2)),
+            ("UserEmailChanged".to_string(), Some(3)),
+        ]
+    );
+
+    Ok(())
+}
@@ -1,10 +1,12 @@
 use event_sauce::{
-    prelude::*, AggregateCreate, AggregateDelete, AggregateUpdate, Deletable, Event, Persistable,
+    prelude::*, AggregateCreate, AggregateDelete, AggregateUpdate, DBEvent, Deletable, Event,
+    Persistable,
 };
-use event_sauce_storage_sqlx::SqlxPgStoreTransaction;
+use event_sauce_storage_sqlx::{Error, SqlxPgStoreTransaction};
 // use event_sauce::UpdateEntity;
 use event_sauce_storage_sqlx::SqlxPgStore;
 use sqlx::PgPool;
+use std::convert::TryFrom;
 use uuid::Uuid;
 
 #[derive(
@@ -57,7 +59,7 @@ struct UserDeleted;
 
 #[async_trait::async_trait]
 impl Persistable<SqlxPgStoreTransaction, User> for User {
-    async fn persist(self, tx: &mut SqlxPgStoreTransaction) -> Result<Self, sqlx::Error> {
+    async fn persist(self, tx: &mut SqlxPgStoreTransaction) -> Result<Self, Error> {
         let blah = format!(
             "insert into {}
                     (id, name, email)
@@ -84,7 +86,7 @@ impl Persistable<SqlxPgStoreTransaction, User> for User {
 
 #[async_trait::async_trait]
 impl Deletable<SqlxPgStoreTransaction> for User {
-    async fn delete(self, tx: &mut SqlxPgStoreTransaction) -> Result<(), sqlx::Error> {
+    async fn delete(self, tx: &mut SqlxPgStoreTransaction) -> Result<(), Error> {
         sqlx::query(&format!(
             "delete from {} where id = $1",
             User::entity_type()
@@ -280,7 +282,7 @@ async fn update() -> Result<(), sqlx::Error> {
 }
 
 #[async_std::test]
-async fn delete() -> Result<(), sqlx::Error> {
+async fn delete() -> Result<(), Error> {
     let store = connect().await?;
 
     let user = User::try_create(UserCreated {
@@ -332,3 +334,41 @@ async fn delete() -> Result<(), sqlx::Error> {
 
     Ok(())
 }
+
+#[async_std::test]
+async fn create_with_tracing() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let correlation_id = Uuid::new_v4();
+    let causation_id = Uuid::new_v4();
+
+    let user = User::try_create(
+        UserCreated {
+            name: "Bobby Beans".to_string(),
+            email: "bobby@bea.ns".to_string(),
+        }
+        .into_builder()
+        .correlation_id(correlation_id)
+        .causation_id(causation_id)
+        .metadata("request_id", serde_json::json!("abc123")),
+    )
+    .expect("Failed to create User from UserCreated event")
+    .persist(&store)
+    .await?;
+
+    let db_event: DBEvent = sqlx::query_as("select * from events where entity_id = $1")
+        .bind(user.id)
+        .fetch_one(&store.pool)
+        .await?;
+
+    let event = Event::<UserCreated>::try_from(db_event)?;
+
+    assert_eq!(event.correlation_id, Some(correlation_id));
+    assert_eq!(event.causation_id, Some(causation_id));
+    assert_eq!(
+        event.metadata.get("request_id"),
+        Some(&serde_json::json!("abc123"))
+    );
+
+    Ok(())
+}
@@ -0,0 +1,646 @@
+use event_sauce::{
+    prelude::*, AggregateAction, AggregateCreate, AggregateDelete, AggregateUpdate, DBEvent,
+    Deletable, Event, Persistable, Upcasters, UpdateEventBuilder,
+};
+use event_sauce_storage_sqlx::{
+    Error, LegalHold, LegalHoldPlaced, LegalHoldReleased, SqlxSqliteStore,
+    SqlxSqliteStoreTransaction,
+};
+use sqlx::sqlite::SqlitePoolOptions;
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+#[derive(
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+    sqlx::FromRow,
+    event_sauce_derive::Entity,
+    PartialEq,
+    Debug,
+    Clone,
+)]
+#[event_sauce(entity_name = "crud_test_users")]
+struct User {
+    #[event_sauce(id)]
+    id: Uuid,
+    name: String,
+    email: String,
+}
+
+/// Version 1 of this event stored the user's name in a `username` field
+#[derive(
+    serde_derive::Serialize, serde_derive::Deserialize, event_sauce_derive::CreateEventData, Clone,
+)]
+#[event_sauce(User, version = 2)]
+struct UserCreated {
+    name: String,
+    email: String,
+}
+
+#[derive(
+    serde_derive::Serialize, serde_derive::Deserialize, event_sauce_derive::UpdateEventData, Clone,
+)]
+#[event_sauce(User)]
+struct UserEmailChanged {
+    email: String,
+}
+
+#[derive(
+    serde_derive::Serialize, serde_derive::Deserialize, event_sauce_derive::DeleteEventData,
+)]
+#[event_sauce(User)]
+struct UserDeleted;
+
+#[derive(
+    serde_derive::Serialize, serde_derive::Deserialize, event_sauce_derive::PurgeEventData,
+)]
+#[event_sauce(User)]
+struct UserPurged;
+
+#[derive(
+    serde_derive::Serialize, serde_derive::Deserialize, event_sauce_derive::EnumEventData, Clone,
+)]
+#[serde(tag = "event_type", content = "data")]
+#[event_sauce(User)]
+enum UserEventData {
+    UserCreated(UserCreated),
+    UserEmailChanged(UserEmailChanged),
+}
+
+#[async_trait::async_trait]
+impl Persistable<SqlxSqliteStoreTransaction> for User {
+    async fn persist(self, tx: &mut SqlxSqliteStoreTransaction) -> Result<Self, Error> {
+        let blah = format!(
+            "insert into {}
+                    (id, name, email)
+                values
+                    (?1, ?2, ?3)
+                on conflict (id)
+                do update set
+                name = excluded.name,
+                email = excluded.email
+            returning *",
+            User::entity_type()
+        );
+
+        let new = sqlx::query_as(&blah)
+            .bind(self.id)
+            .bind(self.name)
+            .bind(self.email)
+            .fetch_one(tx.get())
+            .await?;
+
+        Ok(new)
+    }
+}
+
+#[async_trait::async_trait]
+impl Deletable<SqlxSqliteStoreTransaction> for User {
+    async fn delete(self, tx: &mut SqlxSqliteStoreTransaction) -> Result<(), Error> {
+        sqlx::query(&format!(
+            "delete from {} where id = ?1",
+            User::entity_type()
+        ))
+        .bind(self.id)
+        .execute(tx.get())
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl AggregateCreate<UserCreated> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_create(event: &Event<UserCreated>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to create User from UserCreated event")?;
+
+        Ok(User {
+            id: event.entity_id,
+            name: data.name.clone(),
+            email: data.email.clone(),
+        })
+    }
+}
+
+impl AggregateUpdate<UserEmailChanged> for User {
+    type Error = &'static str;
+    type Output = Self;
+
+    fn try_aggregate_update(self, event: &Event<UserEmailChanged>) -> Result<Self, Self::Error> {
+        let data = event
+            .data
+            .as_ref()
+            .ok_or("Event data must be populated to update User from UserEmailChanged event")?;
+
+        Ok(User {
+            email: data.email.clone(),
+            ..self
+        })
+    }
+}
+
+impl AggregateDelete<UserDeleted> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_delete(self, _event: &Event<UserDeleted>) -> Result<Self, Self::Error> {
+        Ok(self)
+    }
+}
+
+impl AggregateAction<UserEventData> for User {
+    type Error = &'static str;
+
+    fn try_aggregate_action(
+        entity: Option<Self>,
+        event: &Event<UserEventData>,
+    ) -> Result<Self, Self::Error> {
+        match event.data {
+            Some(UserEventData::UserCreated(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserCreated>()
+                    .map_err(|_| "Failed to convert event into UserCreated")?;
+
+                Self::try_aggregate_create(&event)
+            }
+            Some(UserEventData::UserEmailChanged(_)) => {
+                let event = event
+                    .clone()
+                    .try_into_variant::<UserEmailChanged>()
+                    .map_err(|_| "Failed to convert event into UserEmailChanged")?;
+
+                entity
+                    .ok_or("User must exist to apply UserEmailChanged event")?
+                    .try_aggregate_update(&event)
+            }
+            None => entity.ok_or("User must exist to apply an empty event"),
+        }
+    }
+}
+
+async fn connect() -> Result<SqlxSqliteStore, sqlx::Error> {
+    // Every connection to an in-memory database gets its own database, so only one may be opened
+    let sqlite = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Error creating sqlite pool");
+
+    sqlx::query(&format!(
+        r#"
+            create table if not exists {} (
+                id blob primary key,
+                name varchar not null,
+                email varchar not null
+            );
+        "#,
+        User::entity_type()
+    ))
+    .execute(&sqlite)
+    .await
+    .expect("Failed to create test users table");
+
+    let store = SqlxSqliteStore::new(sqlite).await?;
+
+    Ok(store)
+}
+
+async fn create_user(store: &SqlxSqliteStore) -> User {
+    User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .persist(store)
+    .await
+    .expect("Failed to persist")
+}
+
+async fn events(store: &SqlxSqliteStore, entity_id: Uuid) -> Result<Vec<DBEvent>, sqlx::Error> {
+    sqlx::query_as("select * from events where entity_id = ?1 order by sequence_number asc")
+        .bind(entity_id)
+        .fetch_all(&store.pool)
+        .await
+}
+
+#[async_std::test]
+async fn create() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let user = create_user(&store).await;
+
+    assert_eq!(user.name, "Bobby Beans".to_string());
+    assert_eq!(user.email, "bobby@bea.ns".to_string());
+
+    let events = events(&store, user.id).await?;
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event_type, "UserCreated".to_string());
+    assert_eq!(events[0].version, Some(1));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn update() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let user = create_user(&store).await;
+
+    let user = user
+        .try_update(UserEmailChanged {
+            email: "beans@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await?;
+
+    assert_eq!(user.email, "beans@bob.by".to_string());
+
+    let versions = events(&store, user.id)
+        .await?
+        .iter()
+        .map(|event| event.version)
+        .collect::<Vec<_>>();
+
+    assert_eq!(versions, vec![Some(1), Some(2)]);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn stale_expected_version() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let user = create_user(&store).await;
+
+    let result = user
+        .try_update(
+            UpdateEventBuilder::new(UserEmailChanged {
+                email: "beans@bob.by".to_string(),
+            })
+            .expected_version(2),
+        )
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::VersionConflict {
+            expected_version: Some(2),
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn persist_versioned_event_twice() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let user = create_user(&store).await;
+
+    let event = events(&store, user.id).await?.remove(0);
+
+    assert_eq!(event.version, Some(1));
+
+    let mut tx = store.transaction().await?;
+
+    let saved = event.clone().persist(&mut tx).await?;
+
+    tx.commit().await?;
+
+    assert_eq!(saved.id, event.id);
+    assert_eq!(saved.version, Some(1));
+    assert_eq!(events(&store, user.id).await?.len(), 1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn delete() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let user = create_user(&store).await;
+
+    let id = user.id;
+
+    user.try_delete(UserDeleted)
+        .expect("Failed to create deletion event")
+        .delete(&store)
+        .await?;
+
+    let (found,): (i64,) = sqlx::query_as(&format!(
+        "select count(*) from {} where id = ?1",
+        User::entity_type()
+    ))
+    .bind(id)
+    .fetch_one(&store.pool)
+    .await?;
+
+    assert_eq!(found, 0);
+
+    // Event data is retained
+    assert_eq!(events(&store, id).await?.len(), 2);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn purge() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let mut tx = store.transaction().await?;
+
+    let user = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event")
+    .stage_persist(&mut tx)
+    .await?;
+
+    let id = user.id;
+
+    user.try_purge(UserPurged).stage_purge(&mut tx).await?;
+
+    tx.commit().await?;
+
+    let events = events(&store, id).await?;
+
+    // Both the create and purge events exist in the database, but only the create event is purged
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].data, None);
+    assert!(events[0].purged_at.is_some());
+    assert_eq!(events[1].event_type, "UserPurged".to_string());
+    assert!(events[1].purged_at.is_none());
+
+    // The aggregate row is removed too
+    let rows: (i64,) = sqlx::query_as(&format!(
+        "select count(*) from {} where id = ?1",
+        User::entity_type()
+    ))
+    .bind(id)
+    .fetch_one(&store.pool)
+    .await?;
+
+    assert_eq!(rows.0, 0);
+
+    assert_eq!(store.load::<User, UserEventData>(id).await?, None);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn purge_dry_run() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let user = create_user(&store).await;
+
+    let id = user.id;
+
+    let report = User {
+        id,
+        name: user.name.clone(),
+        email: user.email.clone(),
+    }
+    .try_purge(UserPurged)
+    .dry_run(&store)
+    .await?;
+
+    assert_eq!(report.event_count, 1);
+    assert_eq!(report.event_types, vec!["UserCreated"]);
+    assert_eq!(report.aggregate_rows, 1);
+
+    // The dry run doesn't purge anything
+    assert!(events(&store, id).await?[0].data.is_some());
+
+    let receipt = user
+        .try_purge(UserPurged)
+        .purge_with_receipt(&store)
+        .await?;
+
+    assert_eq!(receipt.report, report);
+
+    let events = events(&store, id).await?;
+
+    assert_eq!(events[0].data, None);
+    assert_eq!(events[1].id, receipt.purge_event_id);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn legal_hold() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let user = create_user(&store).await;
+
+    let hold = LegalHold::try_create(LegalHoldPlaced::new(&user, "Case 2021-0042"))?
+        .persist(&store)
+        .await?;
+
+    assert_eq!(store.legal_holds(user.id).await?, vec![hold.clone()]);
+
+    let result = user.clone().try_purge(UserPurged).purge(&store).await;
+
+    assert!(matches!(
+        result,
+        Err(Error::LegalHold { hold_id, .. }) if hold_id == hold.id
+    ));
+
+    let result = user
+        .clone()
+        .try_delete(UserDeleted)
+        .expect("Failed to create deletion event")
+        .delete(&store)
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::LegalHold { hold_id, .. }) if hold_id == hold.id
+    ));
+
+    hold.try_update(LegalHoldReleased::new("Case closed"))?
+        .persist(&store)
+        .await?;
+
+    assert!(store.legal_holds(user.id).await?.is_empty());
+
+    user.try_purge(UserPurged).purge(&store).await?;
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn load() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let user = create_user(&store).await;
+
+    let user = user
+        .try_update(UserEmailChanged {
+            email: "beans@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .persist(&store)
+        .await?;
+
+    let loaded = store.load::<User, UserEventData>(user.id).await?;
+
+    assert_eq!(loaded, Some(user));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn load_upcast() -> Result<(), Error> {
+    let upcasters = Upcasters::new().register("UserCreated", 1, |mut data| {
+        if let Some(name) = data
+            .as_object_mut()
+            .and_then(|data| data.remove("username"))
+        {
+            data["name"] = name;
+        }
+
+        data
+    });
+
+    let store = connect().await?.with_upcasters(upcasters);
+
+    let builder = User::try_create(UserCreated {
+        name: "Bobby Beans".to_string(),
+        email: "bobby@bea.ns".to_string(),
+    })
+    .expect("Failed to create User from UserCreated event");
+
+    let id = builder.entity.id;
+
+    // Store the event as it would have been written by an older version of the application
+    let mut db_event = DBEvent::try_from(builder.event)?;
+    db_event.schema_version = 1;
+    db_event.data = Some(serde_json::json!({
+        "username": "Bobby Beans",
+        "email": "bobby@bea.ns",
+    }));
+
+    let mut tx = store.transaction().await?;
+    db_event.persist(&mut tx).await?;
+    tx.commit().await?;
+
+    let loaded = store.load::<User, UserEventData>(id).await?;
+
+    assert_eq!(
+        loaded.map(|user| user.name),
+        Some("Bobby Beans".to_string())
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn create_with_tracing() -> Result<(), Error> {
+    let store = connect().await?;
+
+    let correlation_id = Uuid::new_v4();
+
+    let user = User::try_create(
+        UserCreated {
+            name: "Bobby Beans".to_string(),
+            email: "bobby@bea.ns".to_string(),
+        }
+        .into_builder()
+        .correlation_id(correlation_id)
+        .metadata("request_id", serde_json::json!("abc123")),
+    )
+    .expect("Failed to create User from UserCreated event")
+    .persist(&store)
+    .await?;
+
+    let events = events(&store, user.id).await?;
+
+    assert_eq!(events[0].correlation_id, Some(correlation_id));
+    assert_eq!(events[0].causation_id, None);
+    assert_eq!(
+        events[0].metadata,
+        serde_json::json!({ "request_id": "abc123" })
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn upgrade_existing_events_table() -> Result<(), Error> {
+    let sqlite = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Error creating sqlite pool");
+
+    // The events table as created before schema versions and tracing IDs were added
+    sqlx::query(
+        r#"
+            create table events(
+                sequence_number integer primary key autoincrement,
+                id blob not null unique,
+                event_type varchar(64) not null,
+                entity_type varchar(64) not null,
+                entity_id blob not null,
+                data text,
+                session_id blob null,
+                created_at text not null,
+                purger_id blob null,
+                purged_at text null,
+                version integer null
+            );
+        "#,
+    )
+    .execute(&sqlite)
+    .await?;
+
+    sqlx::query(&format!(
+        "create table {} (id blob primary key, name varchar not null, email varchar not null)",
+        User::entity_type()
+    ))
+    .execute(&sqlite)
+    .await?;
+
+    let store = SqlxSqliteStore::new(sqlite).await?;
+
+    let user = create_user(&store).await;
+
+    let result = user
+        .try_update(UserEmailChanged {
+            email: "first@bob.by".to_string(),
+        })
+        .expect("Failed to update User from UserEmailChanged event")
+        .expected_version(1)
+        .persist(&store)
+        .await?;
+
+    assert_eq!(result.email, "first@bob.by".to_string());
+
+    // The unique index still prevents two events at the same version
+    let duplicate = sqlx::query(
+        "insert into events (id, event_type, entity_type, entity_id, created_at, version) values (?1, 'UserEmailChanged', ?2, ?3, '', 2)",
+    )
+    .bind(Uuid::new_v4())
+    .bind(User::entity_type())
+    .bind(result.id)
+    .execute(&store.pool)
+    .await;
+
+    assert!(duplicate.is_err());
+
+    Ok(())
+}
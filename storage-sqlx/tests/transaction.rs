@@ -1,7 +1,7 @@
 use event_sauce::{
     prelude::*, AggregateCreate, AggregateDelete, AggregateUpdate, Deletable, Event, Persistable,
 };
-use event_sauce_storage_sqlx::SqlxPgStoreTransaction;
+use event_sauce_storage_sqlx::{Error, SqlxPgStoreTransaction};
 // use event_sauce::UpdateEntity;
 use event_sauce_storage_sqlx::SqlxPgStore;
 use sqlx::PgPool;
@@ -62,7 +62,7 @@ struct UserDeleted;
 
 #[async_trait::async_trait]
 impl Persistable<SqlxPgStoreTransaction> for User {
-    async fn persist(self, tx: &mut SqlxPgStoreTransaction) -> Result<Self, sqlx::Error> {
+    async fn persist(self, tx: &mut SqlxPgStoreTransaction) -> Result<Self, Error> {
         let blah = format!(
             "insert into {}
                     (id, name, email)
@@ -89,7 +89,7 @@ impl Persistable<SqlxPgStoreTransaction> for User {
 
 #[async_trait::async_trait]
 impl Deletable<SqlxPgStoreTransaction> for User {
-    async fn delete(self, tx: &mut SqlxPgStoreTransaction) -> Result<(), sqlx::Error> {
+    async fn delete(self, tx: &mut SqlxPgStoreTransaction) -> Result<(), Error> {
         sqlx::query(&format!(
             "delete from {} where id = $1",
             User::entity_type()